@@ -7,6 +7,7 @@ use crate::btree::btree::btreeinterface::BTreeKVInterface;
 use crate::btree::btree::btreeinterface::BTreeInterface;
 use crate::btree::btree::request::DeleteRequest;
 use crate::btree::btree::request::InsertReqest;
+use crate::btree::btree::merge;
 
 
 use crate::btree::kv::node::BNode;
@@ -15,6 +16,7 @@ use crate::btree::scan::comp::OP_CMP;
 
 pub struct BTree<'a> {
     context: &'a mut dyn KVContextInterface,    
+    fillfactor: usize,
 }
 
 impl<'a> ScanInterface for BTree<'a>
@@ -116,9 +118,16 @@ impl <'a> BTree <'a>{
     pub fn new(context:&'a mut dyn KVContextInterface) -> Self {
         BTree {
             context: context,
+            fillfactor: crate::btree::BTREE_MIN_FILL_FACTOR,
         }
     }
 
+    // set the minimum fill factor (percent of a page) used by delete
+    pub fn set_fillfactor(&mut self, fillfactor:usize){
+        assert!(fillfactor > 0 && fillfactor <= 50);
+        self.fillfactor = fillfactor;
+    }
+
     pub fn print(&self){
         let root = self.context.get_root();
 
@@ -461,40 +470,6 @@ impl <'a> BTree <'a>{
         }
     }
 
-    fn shouldMerge<T:BNodeReadInterface>(&self, treenode: &T, idx: u16, updated: &BNode)-> (i16,Option<u64>) {
-        if updated.nbytes() > crate::btree::BTREE_PAGE_SIZE / 4 {
-            return (0, None);
-        }
-
-        if  idx > 0 {
-            let sibling = self.context.get(treenode.get_ptr(idx as usize - 1));
-            match sibling{
-                Some(n) => {
-                    let merged:usize = n.nbytes() as usize + updated.nbytes() as usize - crate::btree::HEADER as usize;
-                    if merged <= crate::btree::BTREE_PAGE_SIZE {
-                        return (-1, Some(treenode.get_ptr(idx as usize - 1)));
-                    }        
-                },
-                None => panic!("Get Node Exception idx: {:?}", idx - 1)
-            }
-
-        }
-        if  idx + 1 < treenode.nkeys() {
-            let sibling = self.context.get(treenode.get_ptr(idx as usize + 1));
-            match sibling{
-                Some(n) => {
-                    let merged:usize = n.nbytes() as usize + updated.nbytes() as usize - crate::btree::HEADER as usize;
-                    if merged <= crate::btree::BTREE_PAGE_SIZE {
-                        return (1, Some(treenode.get_ptr(idx as usize + 1)));
-                    }        
-                },
-                None => panic!("Get Node Exception idx: {:?}", idx - 1)
-            }
-        }
-
-        return (0,None);
-    }
-
     fn nodeDelete<T:BNodeReadInterface>(&mut self, treenode:&T, idx: u16, key: &[u8]) -> Option<BNode> {
         // recurse into the kid
         let kptr = treenode.get_ptr(idx as usize);
@@ -509,61 +484,7 @@ impl <'a> BTree <'a>{
             return None; // not found
         }
         _ = self.context.del(kptr);
-
-        let mut nodeUpdated = updated.unwrap();
-        let mut newNode = BNode::new(crate::btree::BTREE_PAGE_SIZE);
-        // check for merging
-        let (flagMerged,sibling) = self.shouldMerge(treenode, idx, &nodeUpdated);
-        match flagMerged {
-            0 => {
-                assert!(nodeUpdated.nkeys() > 0);
-                let ptr = self.context.add(nodeUpdated);
-
-                let updatedNode = self.context.get(ptr).unwrap();
-                let key = updatedNode.get_key(0);
-                let nodes = vec![(ptr,key.to_vec())]; 
-                newNode.nodeReplaceKidN(treenode, idx,nodes);
-            },
-            -1 => { //left
-                //print!("Merge Left.\n");
-                let mut merged = BNode::new(crate::btree::BTREE_PAGE_SIZE);
-                let nodeMerged = self.context.get(sibling.unwrap());
-                match nodeMerged
-                {
-                    Some(n) => {
-                        merged.nodeMerge(&n, &nodeUpdated);
-                        let prtMerged = self.context.add(merged);
-                        _ = self.context.del(treenode.get_ptr(idx as usize - 1));
-
-                        let nodeMerged = self.context.get(prtMerged).unwrap();
-                        newNode.nodeReplace2Kid(treenode, idx - 1, prtMerged, nodeMerged.get_key(0));
-                    },
-                    None => panic!("Get Node Exception idx: {:?}", sibling)
-                }
-            },
-            1 => { //right
-                //std.debug.print("Merge Right.\n", .{});
-                let mut merged = BNode::new(crate::btree::BTREE_PAGE_SIZE);
-                let nodeMerged = self.context.get(sibling.unwrap());
-                match nodeMerged
-                {
-                    Some(n) => {
-                        merged.nodeMerge( &nodeUpdated,&n);
-                        let prtMerged = self.context.add(merged);
-                        _ = self.context.del(treenode.get_ptr(idx as usize + 1));
-
-                        let nodeMerged = self.context.get(prtMerged).unwrap();
-                        newNode.nodeReplace2Kid(treenode, idx, prtMerged, nodeMerged.get_key(0));
-                    },
-                    None => panic!("Get Node Exception idx: {:?}", sibling)
-                }
-            },
-            other => {
-                panic!("Exception Merge Flag!");
-            },
-        }
-
-        return Some(newNode);
+        return Some(merge::replaceKid(self.context, self.fillfactor, treenode, idx, updated.unwrap()));
     }
     
     fn nodeDeleteEx<T:BNodeReadInterface>(&mut self, treenode:&T, idx: u16, request: &mut DeleteRequest) -> Option<BNode> {
@@ -580,61 +501,7 @@ impl <'a> BTree <'a>{
             return None; // not found
         }
         _ = self.context.del(kptr);
-
-        let mut nodeUpdated = updated.unwrap();
-        let mut newNode = BNode::new(crate::btree::BTREE_PAGE_SIZE);
-        // check for merging
-        let (flagMerged,sibling) = self.shouldMerge(treenode, idx, &nodeUpdated);
-        match flagMerged {
-            0 => {
-                assert!(nodeUpdated.nkeys() > 0);
-                let ptr = self.context.add(nodeUpdated);
-
-                let updatedNode = self.context.get(ptr).unwrap();
-                let key = updatedNode.get_key(0);
-                let nodes = vec![(ptr,key.to_vec())]; 
-                newNode.nodeReplaceKidN(treenode, idx,nodes);
-            },
-            -1 => { //left
-                //print!("Merge Left.\n");
-                let mut merged = BNode::new(crate::btree::BTREE_PAGE_SIZE);
-                let nodeMerged = self.context.get(sibling.unwrap());
-                match nodeMerged
-                {
-                    Some(n) => {
-                        merged.nodeMerge(&n, &nodeUpdated);
-                        let prtMerged = self.context.add(merged);
-                        _ = self.context.del(treenode.get_ptr(idx as usize - 1));
-
-                        let nodeMerged = self.context.get(prtMerged).unwrap();
-                        newNode.nodeReplace2Kid(treenode, idx - 1, prtMerged, nodeMerged.get_key(0));
-                    },
-                    None => panic!("Get Node Exception idx: {:?}", sibling)
-                }
-            },
-            1 => { //right
-                //std.debug.print("Merge Right.\n", .{});
-                let mut merged = BNode::new(crate::btree::BTREE_PAGE_SIZE);
-                let nodeMerged = self.context.get(sibling.unwrap());
-                match nodeMerged
-                {
-                    Some(n) => {
-                        merged.nodeMerge( &nodeUpdated,&n);
-                        let prtMerged = self.context.add(merged);
-                        _ = self.context.del(treenode.get_ptr(idx as usize + 1));
-
-                        let nodeMerged = self.context.get(prtMerged).unwrap();
-                        newNode.nodeReplace2Kid(treenode, idx, prtMerged, nodeMerged.get_key(0));
-                    },
-                    None => panic!("Get Node Exception idx: {:?}", sibling)
-                }
-            },
-            other => {
-                panic!("Exception Merge Flag!");
-            },
-        }
-
-        return Some(newNode);
+        return Some(merge::replaceKid(self.context, self.fillfactor, treenode, idx, updated.unwrap()));
     }
 
     // insert a KV into a node, the result might be split into 2 nodes.
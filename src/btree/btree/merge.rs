@@ -0,0 +1,215 @@
+use crate::btree::kv::contextinterface::KVContextInterface;
+use crate::btree::kv::node::BNode;
+use crate::btree::kv::nodeinterface::{BNodeOperationInterface, BNodeReadInterface, BNodeWriteInterface};
+use crate::btree::tx::tx::Tx;
+use crate::btree::tx::txinterface::TxReadContext;
+
+// the pages a delete reads, allocates and frees. BTree works on a KVContextInterface and txwriter on a Tx
+pub trait MergeContext {
+    fn getNode(&self, ptr:u64) -> Option<BNode>;
+    fn addNode(&mut self, node:BNode) -> u64;
+    fn delNode(&mut self, ptr:u64);
+}
+
+impl<'a> MergeContext for dyn KVContextInterface + 'a {
+    fn getNode(&self, ptr:u64) -> Option<BNode> {
+        self.get(ptr)
+    }
+
+    fn addNode(&mut self, node:BNode) -> u64 {
+        self.add(node)
+    }
+
+    fn delNode(&mut self, ptr:u64) {
+        _ = self.del(ptr);
+    }
+}
+
+impl MergeContext for Tx {
+    fn getNode(&self, ptr:u64) -> Option<BNode> {
+        self.get(ptr)
+    }
+
+    fn addNode(&mut self, node:BNode) -> u64 {
+        self.add(node)
+    }
+
+    fn delNode(&mut self, ptr:u64) {
+        _ = self.del(ptr);
+    }
+}
+
+// the kid at idx was rewritten by a delete, the old page is already freed. a kid below the fill factor
+// (percent of a page) is merged with a sibling or borrows from one. returns the new parent
+pub fn replaceKid<C:MergeContext + ?Sized, T:BNodeReadInterface>(context:&mut C, fillfactor:usize, treenode:&T, idx:u16, nodeUpdated:BNode) -> BNode {
+    let mut newNode = BNode::new(crate::btree::BTREE_PAGE_SIZE);
+    // check for merging
+    let (mut flagMerged,sibling) = shouldMerge(context, fillfactor, treenode, idx, &nodeUpdated);
+    if flagMerged == -2 || flagMerged == 2 {
+        //borrow from sibling
+        let nodeSibling = context.getNode(sibling.unwrap());
+        let done = match nodeSibling
+        {
+            Some(n) => {
+                if flagMerged == -2 {
+                    nodeRedistribute(context, &mut newNode, treenode, idx - 1, &n, &nodeUpdated)
+                } else {
+                    nodeRedistribute(context, &mut newNode, treenode, idx, &nodeUpdated, &n)
+                }
+            },
+            None => panic!("Get Node Exception idx: {:?}", sibling)
+        };
+        if done {
+            context.delNode(sibling.unwrap());
+            return newNode;
+        }
+        flagMerged = 0;
+    }
+    match flagMerged {
+        0 => {
+            assert!(nodeUpdated.nkeys() > 0);
+            let ptr = context.addNode(nodeUpdated);
+
+            let updatedNode = context.getNode(ptr).unwrap();
+            let key = updatedNode.get_key(0);
+            let nodes = vec![(ptr,key.to_vec())];
+            newNode.nodeReplaceKidN(treenode, idx,nodes);
+        },
+        -1 => { //left
+            let mut merged = BNode::new(crate::btree::BTREE_PAGE_SIZE);
+            let nodeMerged = context.getNode(sibling.unwrap());
+            match nodeMerged
+            {
+                Some(n) => {
+                    merged.nodeMerge(&n, &nodeUpdated);
+                    let prtMerged = context.addNode(merged);
+                    context.delNode(treenode.get_ptr(idx as usize - 1));
+
+                    let nodeMerged = context.getNode(prtMerged).unwrap();
+                    newNode.nodeReplace2Kid(treenode, idx - 1, prtMerged, nodeMerged.get_key(0));
+                },
+                None => panic!("Get Node Exception idx: {:?}", sibling)
+            }
+        },
+        1 => { //right
+            let mut merged = BNode::new(crate::btree::BTREE_PAGE_SIZE);
+            let nodeMerged = context.getNode(sibling.unwrap());
+            match nodeMerged
+            {
+                Some(n) => {
+                    merged.nodeMerge( &nodeUpdated,&n);
+                    let prtMerged = context.addNode(merged);
+                    context.delNode(treenode.get_ptr(idx as usize + 1));
+
+                    let nodeMerged = context.getNode(prtMerged).unwrap();
+                    newNode.nodeReplace2Kid(treenode, idx, prtMerged, nodeMerged.get_key(0));
+                },
+                None => panic!("Get Node Exception idx: {:?}", sibling)
+            }
+        },
+        other => {
+            panic!("Exception Merge Flag!");
+        },
+    }
+
+    newNode
+}
+
+// check whether the updated kid should be merged with a sibling (-1 left, 1 right),
+// borrow from a sibling (-2 left, 2 right) or be kept as it is (0)
+fn shouldMerge<C:MergeContext + ?Sized, T:BNodeReadInterface>(context:&C, fillfactor:usize, treenode: &T, idx: u16, updated: &BNode)-> (i16,Option<u64>) {
+    if updated.nbytes() as usize > crate::btree::BTREE_PAGE_SIZE * fillfactor / 100 {
+        return (0, None);
+    }
+
+    let mut borrow:(i16,Option<u64>) = (0,None);
+    if  idx > 0 {
+        let sibling = context.getNode(treenode.get_ptr(idx as usize - 1));
+        match sibling{
+            Some(n) => {
+                let merged:usize = n.nbytes() as usize + updated.nbytes() as usize - crate::btree::HEADER as usize;
+                if merged <= crate::btree::BTREE_PAGE_SIZE {
+                    return (-1, Some(treenode.get_ptr(idx as usize - 1)));
+                }
+                borrow = (-2, Some(treenode.get_ptr(idx as usize - 1)));
+            },
+            None => panic!("Get Node Exception idx: {:?}", idx - 1)
+        }
+
+    }
+    if  idx + 1 < treenode.nkeys() {
+        let sibling = context.getNode(treenode.get_ptr(idx as usize + 1));
+        match sibling{
+            Some(n) => {
+                let merged:usize = n.nbytes() as usize + updated.nbytes() as usize - crate::btree::HEADER as usize;
+                if merged <= crate::btree::BTREE_PAGE_SIZE {
+                    return (1, Some(treenode.get_ptr(idx as usize + 1)));
+                }
+                if borrow.0 == 0 {
+                    borrow = (2, Some(treenode.get_ptr(idx as usize + 1)));
+                }
+            },
+            None => panic!("Get Node Exception idx: {:?}", idx + 1)
+        }
+    }
+
+    return borrow;
+}
+
+// split the keys of two kids where the halves are closest in bytes. nodeSplit2 fills the right one
+// up to 2/3 of a page, which may leave the left one as small as it was. None when no split fits a page
+fn splitEven(merged:&BNode) -> Option<(BNode,BNode)> {
+    let n = merged.nkeys();
+    let size = |from:u16,to:u16| crate::btree::HEADER as usize + 10 * (to - from) as usize + (merged.get_offSet(to) - merged.get_offSet(from)) as usize;
+
+    let mut best:Option<(u16,usize)> = None;
+    for k in 1..n {
+        let (l,r) = (size(0,k), size(k,n));
+        if l > crate::btree::BTREE_PAGE_SIZE || r > crate::btree::BTREE_PAGE_SIZE {
+            continue;
+        }
+        let diff = l.abs_diff(r);
+        if best.map_or(true, |(_,d)| diff < d) {
+            best = Some((k,diff));
+        }
+    }
+
+    let (k,_) = best?;
+    let mut left = BNode::new(crate::btree::BTREE_PAGE_SIZE);
+    let mut right = BNode::new(crate::btree::BTREE_PAGE_SIZE);
+    left.set_header(merged.btype(), k);
+    left.node_append_range(merged, 0, 0, k);
+    right.set_header(merged.btype(), n - k);
+    right.node_append_range(merged, 0, k, n - k);
+    Some((left,right))
+}
+
+// redistribute the keys of two adjacent kids (idx, idx+1) so both are above the fill factor.
+// returns false when the parent can't hold the new separator keys.
+fn nodeRedistribute<C:MergeContext + ?Sized, T:BNodeReadInterface>(context:&mut C, newNode: &mut BNode, treenode: &T, idx: u16, left: &BNode, right: &BNode) -> bool {
+    let mut merged = BNode::new(crate::btree::BTREE_PAGE_SIZE * 2);
+    merged.nodeMerge(left, right);
+    let splited = match splitEven(&merged) {
+        Some((l,r)) => vec![l,r],
+        None => return false,
+    };
+
+    let mut size = treenode.nbytes() as usize;
+    size -= treenode.get_key(idx).len() + treenode.get_key(idx + 1).len();
+    for n in &splited {
+        size += n.get_key(0).len();
+    }
+    if size > crate::btree::BTREE_PAGE_SIZE {
+        return false;
+    }
+
+    let mut nodes = Vec::new();
+    for n in splited {
+        let ptr = context.addNode(n);
+        let subnode = context.getNode(ptr).unwrap();
+        let key = subnode.get_key(0);
+        nodes.push((ptr,key.to_vec()));
+    }
+    newNode.nodeReplace2KidN(treenode, idx, nodes);
+    return true;
+}
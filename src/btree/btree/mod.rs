@@ -1,3 +1,4 @@
 pub mod btreeinterface;
 pub mod request;
-pub mod btree;
\ No newline at end of file
+pub mod btree;
+pub mod merge;
\ No newline at end of file
@@ -402,7 +402,7 @@ mod tests {
             Cols : vec!["id".as_bytes().to_vec() , "name".as_bytes().to_vec(),"address".as_bytes().to_vec(),"age".as_bytes().to_vec(),"married".as_bytes().to_vec() ] ,
             PKeys : 0,
             Indexes : vec![vec!["address".as_bytes().to_vec() , "married".as_bytes().to_vec()],vec!["age".as_bytes().to_vec()]],
-            IndexPrefixes : vec![],
+            ..Default::default()
         };
         //table.FixIndexes();
 
@@ -471,7 +471,7 @@ mod tests {
             Cols : vec!["id".as_bytes().to_vec() , "name".as_bytes().to_vec(),"address".as_bytes().to_vec(),"age".as_bytes().to_vec(),"married".as_bytes().to_vec() ] ,
             PKeys : 0,
             Indexes : vec![vec!["address".as_bytes().to_vec() , "married".as_bytes().to_vec()],vec!["name".as_bytes().to_vec()]],
-            IndexPrefixes : vec![],
+            ..Default::default()
         };
         //table.FixIndexes();
 
@@ -548,7 +548,7 @@ mod tests {
                 Cols : vec!["id".as_bytes().to_vec() , "name".as_bytes().to_vec(),"address".as_bytes().to_vec(),"age".as_bytes().to_vec(),"married".as_bytes().to_vec() ] ,
                 PKeys : 0,
                 Indexes : vec![vec!["address".as_bytes().to_vec() , "married".as_bytes().to_vec()],vec!["name".as_bytes().to_vec()]],
-                IndexPrefixes : vec![],
+                ..Default::default()
             };
             //table.FixIndexes();
             
@@ -15,6 +15,12 @@ lazy_static! {
         PKeys : 0,
         Indexes : vec![],
         IndexPrefixes : vec![],
+        Nullable : vec![],
+        Defaults : vec![],
+        Checks : vec![],
+        AutoIncrement : vec![],
+        Unique : vec![],
+        ForeignKeys : vec![],
     };
 
     pub static ref TDEF_TABLE: TableDef = TableDef{
@@ -25,6 +31,12 @@ lazy_static! {
         PKeys : 0,
         Indexes : vec![],
         IndexPrefixes : vec![],
+        Nullable : vec![],
+        Defaults : vec![],
+        Checks : vec![],
+        AutoIncrement : vec![],
+        Unique : vec![],
+        ForeignKeys : vec![],
     };
 }
 
@@ -6,7 +6,7 @@ pub mod windowsfilecontext;
 pub mod nodeinterface;
 pub mod contextinterface;
 
-#[derive(Debug)]
+#[derive(Debug,Clone)]
 pub enum ContextError{
     OpenFileError,
     GetFileSizeError,
@@ -22,6 +22,14 @@ pub enum ContextError{
     RootNotFound,
     
     CreateReaderError,
+    TxConflict,
+    SnapshotAlreadyExist,
+    SnapshotNotFound,
+    ReaderSlotError,
+    ReaderSlotsFull,
+    ReadOnly,
+    NotDurable,
+    LegacyFormat,
 }
 
 impl fmt::Display for ContextError {
@@ -39,6 +47,14 @@ impl fmt::Display for ContextError {
             ContextError::RootNotFound => write!(f,"Exception occured when trying to get root node!"),
             ContextError::NodeNotFound => write!(f,"Exception occured when trying to get node!"),
             ContextError::CreateReaderError => write!(f,"Get Reader Error!"),
+            ContextError::TxConflict => write!(f,"Transaction conflicts with a committed transaction, please retry it!"),
+            ContextError::SnapshotAlreadyExist => write!(f,"Snapshot already exists!"),
+            ContextError::SnapshotNotFound => write!(f,"Snapshot is not found!"),
+            ContextError::ReaderSlotError => write!(f,"Reader slot file error!"),
+            ContextError::ReaderSlotsFull => write!(f,"All the reader slots are in use!"),
+            ContextError::ReadOnly => write!(f,"Database is opened read only!"),
+            ContextError::LegacyFormat => write!(f,"Database file has the old integer key format, it can't be opened here!"),
+            ContextError::NotDurable => write!(f,"Transaction is committed but not synced, it becomes durable with the next successful sync!"),
         }
     }
 }
@@ -48,4 +64,6 @@ pub const BNODE_FREE_LIST: u16 = 3;
 pub const FREE_LIST_HEADER: usize = 4 + 8 + 8;
 pub const FREE_LIST_CAP: usize = (BTREE_PAGE_SIZE - FREE_LIST_HEADER) / 8;
 pub const FREE_LIST_CAP_WITH_VERSION: usize = (BTREE_PAGE_SIZE - FREE_LIST_HEADER) / 16;
-pub const DB_SIG:&[u8] = "BuildYourOwnDB24".as_bytes();
\ No newline at end of file
+pub const DB_SIG:&[u8] = "BuildYourOwnDB25".as_bytes();
+// files written before the integer keys were order-preserving
+pub const DB_SIG_V1:&[u8] = "BuildYourOwnDB24".as_bytes();
\ No newline at end of file
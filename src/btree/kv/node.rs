@@ -1,3 +1,5 @@
+use std::borrow::Cow;
+
 use crate::btree::kv::nodeinterface::BNodeReadInterface;
 use crate::btree::kv::nodeinterface::BNodeWriteInterface;
 use crate::btree::kv::nodeinterface::BNodeOperationInterface;
@@ -33,6 +35,51 @@ impl BNode{
     }
 }
 
+// a node read in place from a pending page, or a copy of a mapped page
+#[derive(Clone)]
+pub struct BNodeRef<'a> {
+    data: Cow<'a,[u8]>,
+}
+
+impl<'a> BNodeRef<'a>{
+    pub fn new(data:&'a [u8]) -> Self {
+        BNodeRef {
+            data: Cow::Borrowed(data),
+        }
+    }
+
+    pub fn to_node(&self) -> BNode {
+        BNode {
+            data: self.data.to_vec().into_boxed_slice(),
+            size: self.data.len(),
+        }
+    }
+}
+
+impl From<BNode> for BNodeRef<'_> {
+    fn from(node: BNode) -> Self {
+        BNodeRef {
+            data: Cow::Owned(node.data.into_vec()),
+        }
+    }
+}
+
+impl<'a> BNodeReadInterface for BNodeRef<'a> {
+
+    fn size(&self) ->usize {
+        self.data.len()
+    }
+
+    fn data(&self) ->&[u8]
+    {
+        return &self.data;
+    }
+
+    fn print(&self) {
+        self.to_node().print();
+    }
+}
+
 impl BNodeOperationInterface for BNode{
 
     fn findSplitIdx(&self)-> u16{
@@ -129,6 +176,20 @@ impl BNodeOperationInterface for BNode{
 
         self.node_append_range(oldNode, idx + kvs.len() as u16, idx + 1, oldNode.nkeys() - (idx + 1));
     }
+
+    // replace the two kids at idx and idx+1 with the given kids (used when redistributing siblings)
+    fn nodeReplace2KidN<T:BNodeReadInterface>(&mut self, oldNode: &T, idx: u16,kvs:Vec<(u64,Vec<u8>)>) {
+
+        self.set_header(crate::btree::BNODE_NODE, oldNode.nkeys() + kvs.len() as u16 - 2);
+        self.node_append_range(oldNode, 0, 0, idx);
+
+        for i in 0..kvs.len()
+        {
+            self.node_append_kv(idx + i as u16, kvs[i].0, &kvs[i].1, &[0;1]);
+        }
+
+        self.node_append_range(oldNode, idx + kvs.len() as u16, idx + 2, oldNode.nkeys() - (idx + 2));
+    }
 }
 
 impl BNodeWriteInterface for BNode{
@@ -274,76 +335,6 @@ impl BNodeReadInterface for BNode {
         return &self.data;
     }
 
-    fn btype(&self)->u16{
-        return u16::from_le_bytes(self.data[0..2].try_into().unwrap());
-    }
-
-    fn nkeys(&self) -> u16 {
-        return u16::from_le_bytes(self.data[2..4].try_into().unwrap());
-    }
-    fn get_ptr(&self, idx: usize) -> u64 {
-        assert!(idx < self.nkeys().into(), "Assertion failed: idx is large or equal nkeys!");
-        let pos:usize = (HEADER + 8 * (idx as u16)) as usize;
-        let value: u64 = u64::from_le_bytes(self.data[pos..pos + 8].try_into().unwrap());
-
-        return value;
-    }
-
-    fn offset_pos(&self, idx: u16)->usize{
-        assert!(1 <= idx && idx <= self.nkeys());
-        let r =  8 * self.nkeys() + 2 * (idx - 1);
-        let value_usize: usize = (HEADER +  r) as usize;
-        return value_usize;
-    }
-
-    fn get_offSet(&self,idx:u16) -> u16{
-        if idx == 0
-        {
-            return 0;
-        }
-
-        let pos = self.offset_pos(idx);
-        return u16::from_le_bytes(self.data[pos..pos+2].try_into().unwrap());
-    }
-    fn kvPos(&self, idx: u16)-> usize{
-        assert!(idx <= self.nkeys());
-        let r =  8 * self.nkeys() + 2 * self.nkeys() + self.get_offSet(idx);
-        let value_usize: usize = (HEADER +  r) as usize;
-        return value_usize;
-    }
-
-    fn get_val(&self, idx: u16)-> &[u8]{
-        assert!(idx <= self.nkeys());
-        let pos = self.kvPos(idx);
-        let klen = u16::from_le_bytes(self.data[pos..pos+2].try_into().unwrap()) as usize;
-        let vlen = u16::from_le_bytes(self.data[pos+2..pos+4].try_into().unwrap()) as usize;
-        return &self.data[pos+4+klen..pos+4+klen+vlen];
-    }
-
-    fn get_key(&self, idx: u16)-> &[u8]{
-        assert!(idx <= self.nkeys());
-        let pos = self.kvPos(idx);
-        let klen = u16::from_le_bytes(self.data[pos..pos+2].try_into().unwrap()) as usize;
-        return &self.data[pos+4..pos+4+klen];
-    }
-
-    fn nodeLookupLE(&self, key: &[u8])-> u16{
-        let count = self.nkeys();
-        let mut found:u16 = 0;
-        for i in 0..count{
-            let k = self.get_key(i);
-            let comp = crate::btree::util::compare_arrays(k,key);
-            if comp <= 0 {found = i;}
-            if comp > 0 { break; } 
-        }
-        return found;
-    }
-
-        //node size in bytes
-        fn nbytes(&self)-> usize {
-            return self.kvPos(self.nkeys());
-        }
-
     fn print(&self) {
         for i in 0..self.size {
             if  i > 0 {
@@ -572,4 +563,26 @@ mod tests {
         let t = n1.flnSize();
         assert_eq!(0,t);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_noderef()
+    {
+        let mut root = BNode::new(1024);
+        root.set_header(BNODE_LEAF, 3);
+        root.node_append_kv(0, 0, "".as_bytes(), "".as_bytes());
+        root.node_append_kv(1, 0, "1111".as_bytes(), "1111111".as_bytes());
+        root.node_append_kv(2, 0, "3333".as_bytes(), "3333333".as_bytes());
+
+        let view = BNodeRef::new(root.data());
+        assert_eq!(root.nkeys(),view.nkeys());
+        assert_eq!(root.nbytes(),view.nbytes());
+        assert_eq!("3333333".as_bytes(),view.get_val(2));
+        assert_eq!(1,view.nodeLookupLE("2222".as_bytes()));
+
+        let mut node = BNode::new(1024);
+        node.set_header(BNODE_LEAF, 4);
+        node.leaf_insert(&view,2,"2222".as_bytes(), "2222222".as_bytes());
+        assert_eq!("2222".as_bytes(),node.get_key(2));
+        assert_eq!(view.data(),view.to_node().data());
+    }
+}
@@ -1,21 +1,87 @@
+use crate::btree::HEADER;
 use super::{node::BNode, ContextError};
 
 
 // 定义读取接口
+// the reads only need data(), so a node owned or borrowed from the mapping reads the same way
 pub trait BNodeReadInterface {
     fn size(&self) ->usize;
     fn data(&self) ->&[u8];
-    fn nkeys(&self) -> u16;
-    fn get_ptr(&self, idx: usize) -> u64 ;
     fn print(&self);
-    fn btype(&self)->u16;
-    fn offset_pos(&self, idx: u16)->usize;
-    fn get_offSet(&self,idx:u16) -> u16;
-    fn kvPos(&self, idx: u16)-> usize;
-    fn get_key(&self, idx: u16)-> &[u8];
-    fn get_val(&self, idx: u16)-> &[u8];
-    fn nodeLookupLE(&self, key: &[u8])-> u16;
-    fn nbytes(&self)-> usize;
+
+    fn btype(&self)->u16{
+        return u16::from_le_bytes(self.data()[0..2].try_into().unwrap());
+    }
+
+    fn nkeys(&self) -> u16 {
+        return u16::from_le_bytes(self.data()[2..4].try_into().unwrap());
+    }
+
+    fn get_ptr(&self, idx: usize) -> u64 {
+        assert!(idx < self.nkeys().into(), "Assertion failed: idx is large or equal nkeys!");
+        let pos:usize = (HEADER + 8 * (idx as u16)) as usize;
+        let value: u64 = u64::from_le_bytes(self.data()[pos..pos + 8].try_into().unwrap());
+
+        return value;
+    }
+
+    fn offset_pos(&self, idx: u16)->usize{
+        assert!(1 <= idx && idx <= self.nkeys());
+        let r =  8 * self.nkeys() + 2 * (idx - 1);
+        let value_usize: usize = (HEADER +  r) as usize;
+        return value_usize;
+    }
+
+    fn get_offSet(&self,idx:u16) -> u16{
+        if idx == 0
+        {
+            return 0;
+        }
+
+        let pos = self.offset_pos(idx);
+        return u16::from_le_bytes(self.data()[pos..pos+2].try_into().unwrap());
+    }
+
+    fn kvPos(&self, idx: u16)-> usize{
+        assert!(idx <= self.nkeys());
+        let r =  8 * self.nkeys() + 2 * self.nkeys() + self.get_offSet(idx);
+        let value_usize: usize = (HEADER +  r) as usize;
+        return value_usize;
+    }
+
+    fn get_key(&self, idx: u16)-> &[u8]{
+        assert!(idx <= self.nkeys());
+        let data = self.data();
+        let pos = self.kvPos(idx);
+        let klen = u16::from_le_bytes(data[pos..pos+2].try_into().unwrap()) as usize;
+        return &data[pos+4..pos+4+klen];
+    }
+
+    fn get_val(&self, idx: u16)-> &[u8]{
+        assert!(idx <= self.nkeys());
+        let data = self.data();
+        let pos = self.kvPos(idx);
+        let klen = u16::from_le_bytes(data[pos..pos+2].try_into().unwrap()) as usize;
+        let vlen = u16::from_le_bytes(data[pos+2..pos+4].try_into().unwrap()) as usize;
+        return &data[pos+4+klen..pos+4+klen+vlen];
+    }
+
+    fn nodeLookupLE(&self, key: &[u8])-> u16{
+        let count = self.nkeys();
+        let mut found:u16 = 0;
+        for i in 0..count{
+            let k = self.get_key(i);
+            let comp = crate::btree::util::compare_arrays(k,key);
+            if comp <= 0 {found = i;}
+            if comp > 0 { break; } 
+        }
+        return found;
+    }
+
+    //node size in bytes
+    fn nbytes(&self)-> usize {
+        return self.kvPos(self.nkeys());
+    }
 }
 
 pub trait BNodeWriteInterface{
@@ -39,6 +105,7 @@ pub trait BNodeOperationInterface{
     fn nodeMerge<T:BNodeReadInterface>(&mut self, left: &T, right: &T);
     fn nodeReplace2Kid<T:BNodeReadInterface>(&mut self, oldNode: &T, idx: u16, ptrMergedNode: u64, key: &[u8]); 
     fn nodeReplaceKidN<T:BNodeReadInterface>(&mut self, oldNode: &T, idx: u16,kvs:Vec<(u64,Vec<u8>)>);
+    fn nodeReplace2KidN<T:BNodeReadInterface>(&mut self, oldNode: &T, idx: u16,kvs:Vec<(u64,Vec<u8>)>);
 }
 
 pub trait BNodeFreeListInterface{
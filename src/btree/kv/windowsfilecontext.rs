@@ -2,7 +2,7 @@ use std::collections::HashMap;
 use std::ffi::{CString};
 
 use crate::btree::kv::nodeinterface::{BNodeFreeListInterface, BNodeReadInterface};
-use crate::btree::kv::{BTREE_PAGE_SIZE, DB_SIG, FREE_LIST_CAP};
+use crate::btree::kv::{BTREE_PAGE_SIZE, DB_SIG, DB_SIG_V1, FREE_LIST_CAP};
 #[cfg(windows)]extern crate ntapi;
 use ntapi::ntmmapi::{NtExtendSection,NtUnmapViewOfSection,NtMapViewOfSection,NtCreateSection,ViewUnmap,};
 use winapi::shared::ntdef::PHANDLE;
@@ -515,12 +515,16 @@ impl WindowsFileContext {
         //Load Db File
         unsafe {
             let buffer = self.lpBaseAddress as *mut u8;
-            for i in 0..16
+            let sig = std::slice::from_raw_parts(buffer, 16);
+            // only the transaction layer migrates the integer keys,
+            // the file would be marked as the new format with its old keys on the next commit
+            if sig == DB_SIG_V1
             {
-                if *buffer.add(i) != DB_SIG[i]
-                {
-                    return Err(ContextError::NotDataBaseFile);
-                }
+                return Err(ContextError::LegacyFormat);
+            }
+            if sig != DB_SIG
+            {
+                return Err(ContextError::NotDataBaseFile);
             }
 
             let mut pos: usize = 16;
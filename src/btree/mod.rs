@@ -24,6 +24,9 @@ pub const MODE_INSERT_ONLY: u16 = 2; // only add new keys
 
 pub const TABLE_PREFIX_MIN: u32 = 4;
 
+// default minimum fill factor (percent of a page) below which a node is merged with or borrows from a sibling
+pub const BTREE_MIN_FILL_FACTOR: usize = 25;
+
 #[derive(Debug)]
 pub enum BTreeError{
     ColumnNotFound(String),    
@@ -45,7 +48,20 @@ pub enum BTreeError{
     OperationNotSupported(String),
     ParamNotFound(String),
     BadSQLStatement,
-
+    ContextError(kv::ContextError),
+    SavePointNotFound(String),
+    SnapshotNotFound(String),
+    ReaderExpired,
+    NullNotAllowed(String),
+    NumericOverflow(String),
+    CheckViolation(String),
+    SequenceNotFound(String),
+    SequenceAlreadyExist(String),
+    UniqueViolation(String),
+    BadForeignKey(String),
+    ForeignKeyViolation(String),
+    NotANumber(String),
+    BadExpression(String),
 }
 
 // 实现 fmt::Display 特征
@@ -71,10 +87,30 @@ impl fmt::Display for BTreeError {
             BTreeError::OperationNotSupported(v) => write!(f, "Operation is not supported! :{}", v),
             BTreeError::ParamNotFound(v) => write!(f, "Param is not found! :{}", v),
             BTreeError::BadSQLStatement => write!(f, "Exception occured when trying to parse SQL Statement!"),
+            BTreeError::ContextError(err) => write!(f, "Storage error! :{}", err),
+            BTreeError::SavePointNotFound(v) => write!(f, "Savepoint is not found! :{}", v),
+            BTreeError::SnapshotNotFound(v) => write!(f, "Snapshot is not found! :{}", v),
+            BTreeError::ReaderExpired => write!(f, "Reader is older than the maximum reader age, please begin a new one!"),
+            BTreeError::NullNotAllowed(v) => write!(f, "Column can't be null! :{}", v),
+            BTreeError::NumericOverflow(v) => write!(f, "Numeric value is out of range! :{}", v),
+            BTreeError::CheckViolation(v) => write!(f, "Check constraint is violated! :{}", v),
+            BTreeError::SequenceNotFound(v) => write!(f, "Sequence is not found! :{}", v),
+            BTreeError::SequenceAlreadyExist(v) => write!(f, "Sequence already exists! :{}", v),
+            BTreeError::UniqueViolation(v) => write!(f, "Unique index is violated! :{}", v),
+            BTreeError::BadForeignKey(v) => write!(f, "Foreign key must reference a primary key and be indexed! :{}", v),
+            BTreeError::ForeignKeyViolation(v) => write!(f, "Foreign key is violated! :{}", v),
+            BTreeError::NotANumber(v) => write!(f, "NaN can't be stored! :{}", v),
+            BTreeError::BadExpression(v) => write!(f, "Expression can't be parsed or has the wrong type! :{}", v),
         }
     }
 }
 
+impl From<kv::ContextError> for BTreeError {
+    fn from(err: kv::ContextError) -> Self {
+        BTreeError::ContextError(err)
+    }
+}
+
 // // 实现 std::error::Error 特征
 // impl Error for BTreeError {
 //     fn source(&self) -> Option<&(dyn Error + 'static)> {
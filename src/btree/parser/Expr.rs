@@ -1,8 +1,8 @@
 use std::{cmp::Ordering, fmt};
-use crate::btree::{scan::comp::OP_CMP, table::{record::Record, table::TableDef, value::{Value, ValueError, ValueType}}, tx::txRecord::DataRow, BTreeError};
+use crate::btree::{scan::comp::OP_CMP, util, table::{datetime::{self, DatePart}, decimal, record::Record, table::TableDef, value::{Value, ValueError, ValueType}}, tx::txRecord::DataRow, BTreeError};
 use super::lib::*;
 
-const KEYS: [&str; 19] = ["select", "not", "and", "index", "from","filter","or","limit","by","as","insert","into","values","create","table","primary","key","true","false"];
+const KEYS: [&str; 24] = ["select", "not", "and", "index", "from","filter","or","limit","by","as","insert","into","values","create","table","primary","key","true","false","null","is","date","timestamp","interval"];
 
 #[derive(Clone, Debug, PartialEq)]
 pub struct Expr {
@@ -77,6 +77,29 @@ impl Expr{
         }
     }
 
+    // a copy with every nextval('seq') replaced by the value the writer allocates
+    pub fn resolveNextVal(&self,nextval:&mut dyn FnMut(&[u8])->Result<Value,BTreeError>)->Result<Expr,BTreeError>
+    {
+        if self.op == ExpressionType::NextVal
+        {
+            if let Some(Value::BYTES(name)) = self.left.as_ref().and_then(|l| l.val.as_ref())
+            {
+                return Ok(Expr::constExpr(nextval(name)?));
+            }
+        }
+
+        let mut expr = self.clone();
+        if let Some(l) = &self.left
+        {
+            expr.left = Some(Box::new(l.resolveNextVal(nextval)?));
+        }
+        if let Some(r) = &self.right
+        {
+            expr.right = Some(Box::new(r.resolveNextVal(nextval)?));
+        }
+        Ok(expr)
+    }
+
     pub fn eval(&self,tdef:&TableDef,row:&Vec<Value>)->Result<Value,BTreeError>
     {
         Self::evalNode(self,tdef,&row)
@@ -97,6 +120,7 @@ impl Expr{
                         return Err(BTreeError::EvalException);
                     }
                 }
+                return Err(BTreeError::ParamNotFound(String::from_utf8(id.to_vec()).unwrap()));
             }
             else {
                 return Ok(node.val.as_ref().unwrap().clone());
@@ -153,6 +177,47 @@ impl Expr{
         
     }
 
+    // the expression on sample values of the column types, nextval gives a sample too.
+    // only the type of the result tells something, a mistyped expression fails here as it would on any row
+    pub fn sample(&self,tdef:&TableDef)->Result<Value,BTreeError>
+    {
+        Ok(Self::sampleNode(self,tdef)?.0)
+    }
+
+    // the value and whether it depends on a column
+    fn sampleNode(node:&Expr,tdef:&TableDef)->Result<(Value,bool),BTreeError>
+    {
+        if let Some(v) = &node.val
+        {
+            return match v {
+                Value::ID(id) => match tdef.GetColumnIndex(id) {
+                    Some(idx) => Ok((Value::sample(&tdef.Types[idx]),true)),
+                    None => Err(BTreeError::ParamNotFound(String::from_utf8_lossy(id).to_string())),
+                },
+                _Other => Ok((v.clone(),false)),
+            };
+        }
+        if node.op == ExpressionType::NextVal
+        {
+            return Ok((Value::INT64(1),true));
+        }
+
+        let (left,l) = Self::sampleNode(node.left.as_ref().ok_or(BTreeError::EvalException)?,tdef)?;
+        let (v,varies) = match &node.right {
+            None => (Self::EvalUnaryExpr(&node.op,tdef,&left,&Vec::new())?,l),
+            Some(right) => {
+                let (right,r) = Self::sampleNode(right,tdef)?;
+                (Self::EvalBinaryExpr(&node.op,tdef,&left,&right,&Vec::new())?,l || r)
+            },
+        };
+        // a result that depends on a column is replaced by a sample, so a divisor is never zero
+        if varies && !v.isNull()
+        {
+            return Ok((Value::sample(&v.GetValueType()),true));
+        }
+        Ok((v,varies))
+    }
+
     fn EvalParam(id:&Vec<u8>,rc:&Record)->Option<Value>
     {
         rc.Get(&id)
@@ -196,6 +261,16 @@ impl Expr{
             }
         }
 
+        // comparing with an unknown value is unknown
+        if left.isNull() || right.isNull()
+        {
+            match op {
+                ExpressionType::LT | ExpressionType::LE | ExpressionType::GE | ExpressionType::GT |
+                ExpressionType::EQ | ExpressionType::UnEQ => return Ok(Value::NULL),
+                _Other => {}
+            }
+        }
+
         match op {
             ExpressionType::Add => return left + right,
             ExpressionType::Subtract => return left - right,
@@ -230,8 +305,8 @@ impl Expr{
                     Some(Ordering::Greater)=> return Ok(Value::BOOL(true)),
                     None => return Err(BTreeError::OperationNotSupported(String::from("GT")) ),
             },
-            ExpressionType::AND => return left.LogicOp(right.clone(), |x,y| x && y),
-            ExpressionType::OR => return left.LogicOp(right.clone(), |x,y| x || y),
+            ExpressionType::AND => return left.And(&right),
+            ExpressionType::OR => return left.Or(&right),
             ExpressionType::EQ =>{
                 match left.partial_cmp(&right)
                 {
@@ -274,6 +349,32 @@ impl Expr{
         }
 
 
+        // only a writer can allocate from a sequence
+        if *op == ExpressionType::NextVal
+        {
+            return Err(BTreeError::OperationNotSupported(String::from("nextval")));
+        }
+
+        if *op == ExpressionType::IsNull
+        {
+            return Ok(Value::BOOL(value.isNull()));
+        }
+
+        if *op == ExpressionType::IsNotNull
+        {
+            return Ok(Value::BOOL(!value.isNull()));
+        }
+
+        if value.isNull()
+        {
+            return Ok(Value::NULL);
+        }
+
+        if let ExpressionType::Extract(part) = op
+        {
+            return value.Extract(part);
+        }
+
         if *op == ExpressionType::UnOP
         {
             match &value {
@@ -281,6 +382,8 @@ impl Expr{
                 Value::INT32(ref v) => return Ok(Value::INT32(-v)),
                 Value::INT16(ref v) => return Ok(Value::INT16(-v)),
                 Value::INT8(ref v) => return Ok(Value::INT8(-v)),
+                Value::FLOAT64(ref v) => return Ok(Value::FLOAT64(-v)),
+                Value::DECIMAL(ref v,s) => return Ok(Value::DECIMAL(-v,*s)),
                 _Other => return Err(BTreeError::OperationNotSupported(String::from("-")) ),
             };
         }
@@ -316,6 +419,10 @@ pub enum ExpressionType
     UnOP,
     EQ,
     UnEQ,
+    IsNull,
+    IsNotNull,
+    Extract(DatePart),
+    NextVal,
     None,
 }
 
@@ -339,6 +446,10 @@ impl fmt::Display for ExpressionType {
             ExpressionType::None => write!(f, ""),
             ExpressionType::EQ => write!(f, " = "),
             ExpressionType::UnEQ => write!(f, " != "),
+            ExpressionType::IsNull => write!(f, " IS NULL"),
+            ExpressionType::IsNotNull => write!(f, " IS NOT NULL"),
+            ExpressionType::Extract(part) => write!(f, " {:?}", part),
+            ExpressionType::NextVal => write!(f, " nextval"),
 
         }
     }
@@ -422,6 +533,46 @@ pub fn number_i64<'a>() -> impl Parser<'a,Value>
     )
 }
 
+// 12.5, exact
+pub fn number_decimal<'a>() -> impl Parser<'a,Value>
+{
+    map(
+        tuple3(
+            one_or_more(any_char.pred( |c| c.is_numeric())),
+            match_literal("."),
+            one_or_more(any_char.pred( |c| c.is_numeric())),
+        ),
+    |(int,_,frac)| 
+        {
+            let s:String = int.into_iter().chain(std::iter::once('.')).chain(frac.into_iter()).collect();
+            match decimal::parseDecimal(&s) {
+                Some((v,scale)) => Value::DECIMAL(v,scale),
+                None => Value::FLOAT64(s.parse::<f64>().unwrap()),
+            }
+        }
+    )
+}
+
+// 1.25e3 or 5e-1, a float
+pub fn number_f64<'a>() -> impl Parser<'a,Value>
+{
+    map(
+        tuple3(
+            one_or_more(any_char.pred( |c| c.is_numeric() || *c == '.')),
+            either(match_literal("e"),match_literal("E")),
+            pair(
+                zero_or_more(any_char.pred( |c| *c == '-' || *c == '+')),
+                one_or_more(any_char.pred( |c| c.is_numeric())),
+            ),
+        ).pred(|(mantissa,_,(sign,_))| sign.len() <= 1 && mantissa[0] != '.' && mantissa.iter().filter(|c| **c == '.').count() <= 1),
+    |(mantissa,_,(sign,exp))| 
+        {
+            let s:String = mantissa.into_iter().chain(std::iter::once('e')).chain(sign.into_iter()).chain(exp.into_iter()).collect();
+            Value::FLOAT64(s.parse::<f64>().unwrap())
+        }
+    )
+}
+
 pub fn id<'a>() -> impl Parser<'a,Value>{
     pred(identifier,|v| notKey(v)).map( |v| {
         let mut bytes: Vec<u8> = Vec::new();
@@ -435,6 +586,41 @@ pub fn id<'a>() -> impl Parser<'a,Value>{
     })
 }
 
+fn null<'a>() -> impl Parser<'a,Value>
+{
+    pred(identifier,|v| v == "null").map(|_| Value::NULL)
+}
+
+// date '2024-01-31', timestamp '2024-01-31 10:00:00', interval '1 month', x'00ff'
+fn typedLiteral<'a>() -> impl Parser<'a,Value>
+{
+    either4(
+        right(pair(match_literal("date"),space1()),singlequoted_string())
+            .pred(|v| textOf(v).and_then(datetime::parseDate).is_some())
+            .map(|v| Value::DATE(textOf(&v).and_then(datetime::parseDate).unwrap())),
+        right(pair(match_literal("timestamp"),space1()),singlequoted_string())
+            .pred(|v| textOf(v).and_then(datetime::parseTimestamp).is_some())
+            .map(|v| Value::TIMESTAMP(textOf(&v).and_then(datetime::parseTimestamp).unwrap())),
+        right(pair(match_literal("interval"),space1()),singlequoted_string())
+            .pred(|v| textOf(v).map_or(false,|s| datetime::parseInterval(s).is_ok()))
+            .map(|v| {
+                let (m,d,u) = datetime::parseInterval(textOf(&v).unwrap()).unwrap();
+                Value::INTERVAL(m,d,u)
+            }),
+        right(either(match_literal("x"),match_literal("X")),singlequoted_string())
+            .pred(|v| textOf(v).and_then(util::fromHex).is_some())
+            .map(|v| Value::BLOB(textOf(&v).and_then(util::fromHex).unwrap())),
+    )
+}
+
+fn textOf(v:&Value)->Option<&str>
+{
+    match v {
+        Value::BYTES(b) | Value::TEXT(b) => std::str::from_utf8(b).ok(),
+        _Other => None,
+    }
+}
+
 fn truelfase<'a>() -> impl Parser<'a,Value>
 {
     either(
@@ -479,8 +665,8 @@ fn singlequoted_string<'a>() -> impl Parser<'a,Value>
 pub fn Constant<'a>() -> impl Parser<'a,Value>
 {
     either4(
-        number_i64(), 
-        id(),
+        either4(typedLiteral(), number_f64(), number_decimal(), number_i64()), 
+        either(null(), id()),
         singlequoted_string(),
         truelfase()
     )
@@ -491,9 +677,55 @@ fn OpNegtive<'a>() -> impl Parser<'a,ExpressionType>
     either(OpUnOp() ,OpNot())
 }
 
+fn OpExtract<'a>() -> impl Parser<'a,ExpressionType>
+{
+    either(
+        either3(
+            match_literal("year").map(|_| ExpressionType::Extract(DatePart::Year)),
+            match_literal("month").map(|_| ExpressionType::Extract(DatePart::Month)),
+            match_literal("day").map(|_| ExpressionType::Extract(DatePart::Day)),
+        ),
+        either3(
+            match_literal("hour").map(|_| ExpressionType::Extract(DatePart::Hour)),
+            match_literal("minute").map(|_| ExpressionType::Extract(DatePart::Minute)),
+            match_literal("second").map(|_| ExpressionType::Extract(DatePart::Second)),
+        ),
+    )
+}
+
+// year(expr), a fn so that the nested Expr doesn't make the parser type recursive
+fn ExprExtract(input:&str) -> ParserResult<Expr>
+{
+    pair(
+        left(OpExtract(),pair(space0(),match_literal("("))),
+        left(right(space0(),Expr()),pair(space0(),match_literal(")")))
+    ).map(|(op,expr)| Expr{
+        op:op,
+        left:Some(Box::new(expr)),
+        right:None,
+        val:None,
+    }).parse(input)
+}
+
+// nextval('seq')
+fn ExprNextVal<'a>() -> impl Parser<'a,Expr>
+{
+    right(
+        pair(match_literal("nextval"),pair(space0(),match_literal("("))),
+        left(right(space0(),singlequoted_string()),pair(space0(),match_literal(")")))
+    ).map(|name| Expr{
+        op:ExpressionType::NextVal,
+        left:Some(Box::new(Expr::constExpr(name))),
+        right:None,
+        val:None,
+    })
+}
+
 fn Operand<'a>() -> impl Parser<'a,Expr>
 {   
-    either(
+    either4(
+    ExprExtract,
+    ExprNextVal(),
     Constant().map(|v| Expr::constExpr(v)),
     either(
         pair(OpUnOp(),Constant()),
@@ -584,6 +816,35 @@ fn ExprCmp<'a>() -> impl Parser<'a,Expr>
     )
 }
 
+// is null | is not null
+fn OpIsType<'a>() -> impl Parser<'a,ExpressionType>
+{
+    right(
+        match_literal("is"),
+        right(space1(),
+            either(
+                right(match_literal("not"),right(space1(),match_literal("null"))).map(|_| ExpressionType::IsNotNull),
+                match_literal("null").map(|_| ExpressionType::IsNull)
+            )
+        )
+    )
+}
+
+fn ExprIs<'a>() -> impl Parser<'a,Expr>
+{
+    pair(
+        ExprCmp(),
+        zero_or_more(right(space1(),OpIsType()))
+    ).map(|(expr,ops)|
+        ops.into_iter().fold(expr,|left,op| Expr{
+            op:op,
+            left:Some(Box::new(left)),
+            right:None,
+            val:None,
+        })
+    )
+}
+
 fn OpEQType<'a>() -> impl Parser<'a,ExpressionType>
 {
     right(space0(),
@@ -597,8 +858,8 @@ fn OpEQType<'a>() -> impl Parser<'a,ExpressionType>
 fn ExprEq<'a>() -> impl Parser<'a,Expr>
 {
     chain(
-        ExprCmp(),
-        pair( OpEQType(),right(space0(),ExprCmp())),
+        ExprIs(),
+        pair( OpEQType(),right(space0(),ExprIs())),
         newExpr,
         initExpr,
         addExpr
@@ -627,6 +888,13 @@ pub fn Expr<'a>() -> impl Parser<'a,Expr>
     )
 }
 
+// the text of an expression, a table definition stores it rather than the tree
+pub fn ExprSource(input:&str) -> ParserResult<String>
+{
+    let (rest,_) = Expr().parse(input)?;
+    Ok((rest,input[..input.len() - rest.len()].trim().to_string()))
+}
+
 #[test]
 fn EExpr_eval()
 {
@@ -637,7 +905,7 @@ fn EExpr_eval()
         Cols : vec!["id".as_bytes().to_vec() , "name".as_bytes().to_vec(),"address".as_bytes().to_vec(),"age".as_bytes().to_vec(),"married".as_bytes().to_vec() ] ,
         PKeys : 0,
         Indexes : vec![vec!["address".as_bytes().to_vec() , "married".as_bytes().to_vec()],vec!["name".as_bytes().to_vec()]],
-        IndexPrefixes : vec![],
+        ..Default::default()
     };
 
     let mut r = Record::new(&table);
@@ -729,6 +997,103 @@ fn Expr_()
 
 }
 
+#[test]
+fn test_number_f64()
+{
+    assert_eq!(
+        Ok((" * abc",Value::DECIMAL(325,2))),
+        Constant().parse("3.25 * abc"));
+    assert_eq!(
+        Ok((" * abc",Value::FLOAT64(3250.0))),
+        Constant().parse("3.25e3 * abc"));
+    assert_eq!(
+        Ok(("",Value::FLOAT64(0.05))),
+        Constant().parse("5E-2"));
+    assert_eq!(
+        Ok((".abc",Value::INT64(3))),
+        Constant().parse("3.abc"));
+    let table = TableDef{
+        Prefix:0,
+        Name: "price".as_bytes().to_vec(),
+        Types : vec!["FLOAT64".into()] ,
+        Cols : vec!["amount".as_bytes().to_vec() ] ,
+        PKeys : 0,
+        Indexes : vec![],
+        ..Default::default()
+    };
+    let exp = Expr().parse("amount * 2 + -0.5").unwrap().1;
+    assert_eq!(Value::FLOAT64(2.5),exp.eval(&table,&vec![Value::FLOAT64(1.5)]).unwrap());
+}
+
+#[test]
+fn test_null_expr()
+{
+    let table = TableDef{
+        Prefix:0,
+        Name: "person".as_bytes().to_vec(),
+        Types : vec!["BYTES".into(), "INT16".into(), "BOOL".into()] ,
+        Cols : vec!["id".as_bytes().to_vec() , "age".as_bytes().to_vec(),"married".as_bytes().to_vec() ] ,
+        PKeys : 0,
+        Indexes : vec![],
+        Nullable : vec![false,true,true],
+        ..Default::default()
+    };
+    let row = vec![Value::BYTES("1".as_bytes().to_vec()),Value::NULL,Value::BOOL(true)];
+
+    let eval = |s:&str| Expr().parse(s).unwrap().1.eval(&table,&row).unwrap();
+    assert_eq!(Value::NULL,eval("age > 20"));
+    assert_eq!(Value::NULL,eval("age + 1 = 21"));
+    assert_eq!(Value::BOOL(true),eval("age is null"));
+    assert_eq!(Value::BOOL(false),eval("age is not null"));
+    assert_eq!(Value::BOOL(true),eval("married is not null and age is null"));
+    assert_eq!(Value::NULL,eval("married and age > 20"));
+    assert_eq!(Value::BOOL(true),eval("married or age > 20"));
+    assert_eq!(Value::NULL,eval("null"));
+    assert_eq!(Value::BOOL(true),eval("null is null"));
+}
+
+#[test]
+fn test_date_expr()
+{
+    let table = TableDef{
+        Prefix:0,
+        Name: "event".as_bytes().to_vec(),
+        Types : vec!["INT64".into(), "TIMESTAMP".into()] ,
+        Cols : vec!["id".as_bytes().to_vec() , "at".as_bytes().to_vec() ] ,
+        PKeys : 0,
+        Indexes : vec![],
+        Nullable : vec![false,true],
+        ..Default::default()
+    };
+    let at = datetime::parseTimestamp("2024-01-31 10:20:30").unwrap();
+    let row = vec![Value::INT64(1),Value::TIMESTAMP(at)];
+
+    assert_eq!(Ok(("",Value::DATE(19753))),Constant().parse("date '2024-01-31'"));
+    assert_eq!(Ok(("",Value::INTERVAL(1,2,0))),Constant().parse("interval '1 month 2 days'"));
+    assert!(!matches!(Constant().parse("date '2024-02-30'"),Ok(("",_))));
+    //the type names are keywords, not columns
+    assert!(id().parse("date").is_err());
+    assert!(id().parse("timestamp").is_err());
+    assert!(id().parse("interval").is_err());
+
+    let eval = |s:&str| Expr().parse(s).unwrap().1.eval(&table,&row).unwrap();
+    assert_eq!(Value::INT64(2024),eval("year(at)"));
+    assert_eq!(Value::INT64(20),eval("minute ( at )"));
+    assert_eq!(Value::INT64(29),eval("day(at + interval '1 month')"));
+    assert_eq!(Value::BOOL(true),eval("at > timestamp '2024-01-31 10:00:00'"));
+    assert_eq!(Value::BOOL(true),eval("at - interval '1 day' < date '2024-01-31'"));
+}
+
+#[test]
+fn test_blob_literal()
+{
+    assert_eq!(Ok((" = b",Value::BLOB(vec![0x00,0xab,0xff]))),Constant().parse("x'00aBff' = b"));
+    assert_eq!(Ok(("",Value::BLOB(vec![]))),Constant().parse("X''"));
+    assert!(!matches!(Constant().parse("x'abc'"),Ok(("",_))));
+    assert!(!matches!(Constant().parse("x'zz'"),Ok(("",_))));
+    assert_eq!(Ok((" + 1",Value::ID("xval".as_bytes().to_vec()))),Constant().parse("xval + 1"));
+}
+
 #[test]
 fn Op_Parser()
 {
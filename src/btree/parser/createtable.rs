@@ -1,14 +1,14 @@
 use std::fmt;
 use serde_json::Value;
 
-use crate::btree::table::{table::TableDef, value::ValueType};
+use crate::btree::table::{decimal, table::{ForeignKey, TableDef}, value::ValueType};
 
-use super::{lib::*, statement::*, expr::{ Expr}};
+use super::{lib::*, statement::*, expr::{ Expr, ExprSource}};
 
 
 fn ExprValueType<'a>() -> impl Parser<'a,ValueType>
 {
-    either(
+    either3(
         either4(
             match_literal("int8").map(|_| ValueType::INT8), 
             match_literal("int16").map(|_| ValueType::INT16), 
@@ -16,12 +16,43 @@ fn ExprValueType<'a>() -> impl Parser<'a,ValueType>
             match_literal("int64").map(|_| ValueType::INT64), 
         ),
         either(
-            match_literal("vchar").map(|_| ValueType::BYTES), 
-            match_literal("bool").map(|_| ValueType::BOOL), 
+            either4(
+                match_literal("vchar").map(|_| ValueType::BYTES), 
+                match_literal("bool").map(|_| ValueType::BOOL), 
+                match_literal("float64").map(|_| ValueType::FLOAT64), 
+                match_literal("double").map(|_| ValueType::FLOAT64), 
+            ),
+            either4(
+                match_literal("date").map(|_| ValueType::DATE), 
+                match_literal("timestamp").map(|_| ValueType::TIMESTAMP), 
+                match_literal("interval").map(|_| ValueType::INTERVAL), 
+                ExprDecimal(),
+            ),
+        ),
+        either(
+            match_literal("text").map(|_| ValueType::TEXT), 
+            match_literal("blob").map(|_| ValueType::BLOB), 
         ),
     )
 }  
 
+// decimal(10,2), decimal(10) has no fraction digits
+fn ExprDecimal<'a>() -> impl Parser<'a,ValueType>
+{
+    tuple4(
+        match_literal("decimal"),
+        remove_lead_space(match_literal("(")),
+        remove_lead_space(number_string()),
+        left(
+            zero_or_more(right(remove_lead_space(match_literal(",")),remove_lead_space(number_string()))),
+            remove_lead_space(match_literal(")")),
+        ),
+    )
+    .map(|(_,_,p,s)| (p.parse::<u8>().unwrap_or(0),s.first().map_or(Some(0),|s| s.parse::<u8>().ok())))
+    .pred(|(p,s)| *p >= 1 && *p <= decimal::MAX_PRECISION && s.map_or(false,|s| s <= *p))
+    .map(|(p,s)| ValueType::DECIMAL(p,s.unwrap()))
+}
+
 fn ExprTableName<'a>() -> impl Parser<'a,String>
 {
     tuple3(
@@ -31,19 +62,70 @@ fn ExprTableName<'a>() -> impl Parser<'a,String>
         ).map(|(_,_,name)| name)
 }
 
-fn ExprColumn<'a>() -> impl Parser<'a,(String,ValueType)>
+enum ColumnConstraint {
+    Nullable(bool),
+    Default(String),
+    AutoIncrement,
+}
+
+// null | not null
+fn ExprNullable<'a>() -> impl Parser<'a,bool>
 {
-    tuple3(
+    either(
+        right(match_literal("not"),right(space1(),match_literal("null"))).map(|_| false),
+        match_literal("null").map(|_| true),
+    )
+}
+
+// default <expr>
+fn ExprDefault<'a>() -> impl Parser<'a,String>
+{
+    right(match_literal("default"),right(space1(),ExprSource))
+}
+
+fn ExprColumn<'a>() -> impl Parser<'a,(String,ValueType,Vec<ColumnConstraint>)>
+{
+    tuple4(
         remove_lead_space_and_newline(id_string()),
         remove_lead_space_and_newline(ExprValueType()),
+        zero_or_more(remove_lead_space_and_newline(
+            either3(
+                ExprNullable().map(|v| ColumnConstraint::Nullable(v)),
+                ExprDefault().map(|v| ColumnConstraint::Default(v)),
+                match_literal("autoincrement").map(|_| ColumnConstraint::AutoIncrement),
+            ))).pred(|constraints| {
+                // each kind is given once
+                let count = |f:fn(&ColumnConstraint)->bool| constraints.iter().filter(|c| f(c)).count();
+                count(|c| matches!(c,ColumnConstraint::Nullable(_))) <= 1
+                    && count(|c| matches!(c,ColumnConstraint::Default(_))) <= 1
+                    && count(|c| matches!(c,ColumnConstraint::AutoIncrement)) <= 1
+            }),
         remove_lead_space_and_newline(match_literal(","))                      
-    ).map(|(id,colType,_)| (id,colType))
+    ).map(|(id,colType,constraints,_)| (id,colType,constraints))
+}
+
+// check (<expr>)
+fn ExprCheck<'a>() -> impl Parser<'a,String>
+{
+    tuple4(
+        remove_lead_space_and_newline(match_literal("check")),
+        remove_lead_space_and_newline(match_literal("(")),
+        remove_lead_space_and_newline(ExprSource),
+        pair(
+            remove_lead_space_and_newline(match_literal(")")),
+            remove_lead_space_and_newline(match_literal(","))
+        )
+    ).map(|(_,_,expr,_)| expr)
 }
 
-fn ExprIndex<'a>() -> impl Parser<'a,Vec<String>>
+// [unique] index (<cols>)
+fn ExprIndex<'a>() -> impl Parser<'a,(bool,Vec<String>)>
 {
     tuple3(
-        remove_lead_space_and_newline(match_literal("index")),
+        remove_lead_space_and_newline(either(
+            right(match_literal("unique"),right(space1(),match_literal("index"))).map(|_| true),
+            match_literal("index").map(|_| false),
+        )),
         remove_lead_space_and_newline(match_literal("(")),
         tuple4(
             remove_lead_space_and_newline(id_string()),
@@ -55,7 +137,27 @@ fn ExprIndex<'a>() -> impl Parser<'a,Vec<String>>
             remove_lead_space_and_newline(match_literal(")")),
             remove_lead_space_and_newline(match_literal(","))
         )     
-    ).map( |(_,_,(first,mut tail,_,_))|
+    ).map( |(unique,_,(first,mut tail,_,_))|
+        {
+            tail.insert(0,first);
+            (unique,tail)
+        }
+    )
+}
+
+// (<col>, <col>)
+fn ExprColumnList<'a>() -> impl Parser<'a,Vec<String>>
+{
+    tuple4(
+        remove_lead_space_and_newline(match_literal("(")),
+        remove_lead_space_and_newline(id_string()),
+        zero_or_more(
+            right(
+               remove_lead_space_and_newline(match_literal(",")),
+               remove_lead_space_and_newline(id_string())
+            )),
+        remove_lead_space_and_newline(match_literal(")")),
+    ).map(|(_,first,mut tail,_)|
         {
             tail.insert(0,first);
             tail
@@ -63,6 +165,43 @@ fn ExprIndex<'a>() -> impl Parser<'a,Vec<String>>
     )
 }
 
+// foreign key (<cols>) references <table> (<cols>) [on delete cascade | restrict],
+fn ExprForeignKey<'a>() -> impl Parser<'a,ForeignKey>
+{
+    tuple4(
+        right(
+            remove_lead_space_and_newline(match_literal("foreign")),
+            remove_lead_space_and_newline(match_literal("key")),
+        ),
+        ExprColumnList(),
+        tuple3(
+            remove_lead_space_and_newline(match_literal("references")),
+            remove_lead_space_and_newline(id_string()),
+            ExprColumnList(),
+        ),
+        pair(
+            zero_or_more(right(
+                remove_lead_space_and_newline(match_literal("on")),
+                right(
+                    remove_lead_space_and_newline(match_literal("delete")),
+                    remove_lead_space_and_newline(either(
+                        match_literal("cascade").map(|_| true),
+                        match_literal("restrict").map(|_| false),
+                    ))
+                )
+            )),
+            remove_lead_space_and_newline(match_literal(","))
+        )
+    ).map(|(_,cols,(_,table,refCols),(cascade,_))|
+        ForeignKey{
+            Cols: cols.iter().map(|c| c.as_bytes().to_vec()).collect(),
+            Table: table.as_bytes().to_vec(),
+            RefCols: refCols.iter().map(|c| c.as_bytes().to_vec()).collect(),
+            Cascade: cascade.last() == Some(&true),
+        }
+    )
+}
+
 fn ExprPrimaryKey<'a>() -> impl Parser<'a,Vec<String>>
 {
     tuple4(
@@ -88,14 +227,18 @@ fn ExprPrimaryKey<'a>() -> impl Parser<'a,Vec<String>>
     
 }
 
-fn ExprColumns<'a>() -> impl Parser<'a,(Vec<(String,ValueType)>,Vec<String>,Vec<Vec<String>>)>
+fn ExprColumns<'a>() -> impl Parser<'a,(Vec<(String,ValueType,Vec<ColumnConstraint>)>,Vec<String>,(Vec<(bool,Vec<String>)>,Vec<ForeignKey>),Vec<String>)>
 {
     tuple4(
         remove_lead_space_and_newline(match_literal("(")), 
-        tuple3(
+        tuple4(
             one_or_more(remove_lead_space_and_newline(ExprColumn())),
             remove_lead_space_and_newline(ExprPrimaryKey()),                    
-            one_or_more(remove_lead_space_and_newline(ExprIndex())),  
+            pair(
+                one_or_more(remove_lead_space_and_newline(ExprIndex())),
+                zero_or_more(remove_lead_space_and_newline(ExprForeignKey())),
+            ),
+            zero_or_more(remove_lead_space_and_newline(ExprCheck())),  
            ),
            remove_lead_space_and_newline(match_literal(")")),
            remove_lead_space_and_newline(match_literal(";")),
@@ -109,14 +252,28 @@ pub fn ExprCreateTable<'a>() -> impl Parser<'a,TableDef>
         ExprTableName(),
         ExprColumns(),
     ).map (|(name,
-        (cols,primaryKey,indexes))|
+        (cols,primaryKey,(indexes,foreignKeys),checks))|
     {
         let mut def = TableDef::create(name);
-        for c in cols
+        // columns are not null unless declared null, so their rows keep the format without the NULL marker
+        for (name,colType,constraints) in cols
         {
-            def.Cols.push(c.0.as_bytes().to_vec());
-            def.Types.push(c.1);
+            def.Cols.push(name.as_bytes().to_vec());
+            def.Types.push(colType);
+            def.Nullable.push(false);
+            def.Defaults.push(None);
+            def.AutoIncrement.push(false);
+            for c in constraints
+            {
+                match c {
+                    ColumnConstraint::Nullable(v) => *def.Nullable.last_mut().unwrap() = v,
+                    ColumnConstraint::Default(v) => *def.Defaults.last_mut().unwrap() = Some(v),
+                    ColumnConstraint::AutoIncrement => *def.AutoIncrement.last_mut().unwrap() = true,
+                }
+            }
         }
+        def.Checks = checks;
+        def.ForeignKeys = foreignKeys;
 
         let mut pkey = 0;
         for i in 0..primaryKey.len(){
@@ -129,7 +286,7 @@ pub fn ExprCreateTable<'a>() -> impl Parser<'a,TableDef>
 
         def.PKeys = pkey as u16;
 
-        for i in indexes
+        for (unique,i) in indexes
         {
             let mut index = Vec::new();
             for c in i
@@ -137,6 +294,7 @@ pub fn ExprCreateTable<'a>() -> impl Parser<'a,TableDef>
                 index.push(c.as_bytes().to_vec());
             }
             def.Indexes.push(index);
+            def.Unique.push(unique);
         }
         def
     }
@@ -162,4 +320,118 @@ fn test_statement_expr() {
    let ret = ExprCreateTable().parse(exp).unwrap();
    println!("\n{} Next:{}\n Table:{}",exp,ret.0,ret.1);
     
+}
+
+#[test]
+fn test_nullable_expr() {
+
+    let exp = r#"
+    create table table1
+    ( 
+        a int64,
+        b vchar not null,
+        c int16 null,
+        d bool,
+        primary key (a),
+        index (c),
+    );
+   "#;
+   let ret = ExprCreateTable().parse(exp).unwrap();
+   assert_eq!(vec![false,false,true,false],ret.1.Nullable);
+   assert_eq!(false,ret.1.isNullable(0));
+   assert_eq!(false,ret.1.isNullable(1));
+   assert_eq!(true,ret.1.isNullable(2));
+
+   //a constraint is given once
+   assert_eq!(true,ExprCreateTable().parse("create table t ( a int64, b vchar not null null, primary key (a), index (b), );").is_err());
+   assert_eq!(true,ExprCreateTable().parse("create table t ( a int64, b vchar null null, primary key (a), index (b), );").is_err());
+   assert_eq!(true,ExprCreateTable().parse("create table t ( a int64, b int64 default 1 default 2, primary key (a), index (b), );").is_err());
+}
+
+#[test]
+fn test_constraint_expr() {
+
+    let exp = r#"
+    create table account
+    ( 
+        id int64,
+        owner vchar not null default 'nobody',
+        balance int64 default 10 * 10 not null,
+        rate double null,
+        primary key (id),
+        index (owner),
+        check (balance >= 0),
+        check (rate is null or rate < 1),
+    );
+   "#;
+   let ret = ExprCreateTable().parse(exp).unwrap();
+   assert_eq!(vec![false,false,false,true],ret.1.Nullable);
+   assert_eq!(vec![None,Some("'nobody'".to_string()),Some("10 * 10".to_string()),None],ret.1.Defaults);
+   assert_eq!(vec!["balance >= 0".to_string(),"rate is null or rate < 1".to_string()],ret.1.Checks);
+
+   let def = TableDef::new(&ret.1.Marshal());
+   assert_eq!(ret.1.Defaults,def.Defaults);
+   assert_eq!(ret.1.Checks,def.Checks);
+   assert_eq!(true,def.isNotNull(2));
+}
+
+#[test]
+fn test_unique_index_expr() {
+
+    let exp = r#"
+    create table users
+    ( 
+        id int64,
+        email vchar,
+        name vchar,
+        primary key (id),
+        unique index (email),
+        index (name),
+    );
+   "#;
+   let mut def = ExprCreateTable().parse(exp).unwrap().1;
+   assert_eq!(vec![true,false],def.Unique);
+   def.FixIndexes();
+   assert_eq!("users(email)",def.indexName(0));
+   assert_eq!(false,TableDef::new(&def.Marshal()).isUniqueIndex(1));
+}
+
+#[test]
+fn test_foreign_key_expr() {
+
+    let exp = r#"
+    create table lines
+    ( 
+        orderid int64,
+        line int32,
+        product int64,
+        primary key (orderid,line),
+        index (product),
+        foreign key (orderid) references orders (id) on delete cascade,
+        foreign key ( product ) references products(id),
+        check (line > 0),
+    );
+   "#;
+   let def = ExprCreateTable().parse(exp).unwrap().1;
+   assert_eq!(2,def.ForeignKeys.len());
+   assert_eq!("orders".as_bytes().to_vec(),def.ForeignKeys[0].Table);
+   assert_eq!(true,def.ForeignKeys[0].Cascade);
+   assert_eq!(false,def.ForeignKeys[1].Cascade);
+   assert_eq!("lines(product) references products(id)",def.foreignKeyName(1));
+   assert_eq!(def.ForeignKeys,TableDef::new(&def.Marshal()).ForeignKeys);
+}
+
+#[test]
+fn test_decimal_expr() {
+    assert_eq!(Ok((",",ValueType::DECIMAL(10,2))),ExprValueType().parse("decimal( 10, 2),"));
+    assert_eq!(Ok(("",ValueType::DECIMAL(38,0))),ExprValueType().parse("decimal(38)"));
+    assert!(ExprValueType().parse("decimal(39,2)").is_err());
+    assert!(ExprValueType().parse("decimal(4,5)").is_err());
+}
+
+#[test]
+fn test_text_blob_expr() {
+    assert_eq!(Ok((",",ValueType::TEXT)),ExprValueType().parse("text,"));
+    assert_eq!(Ok((",",ValueType::BLOB)),ExprValueType().parse("blob,"));
+    assert_eq!(Ok((",",ValueType::TIMESTAMP)),ExprValueType().parse("timestamp,"));
 }
\ No newline at end of file
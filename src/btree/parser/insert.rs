@@ -5,7 +5,7 @@ use super::{expr::{id, Constant, Expr}, lib::*, sqlerror::SqlError, statement::*
 pub struct InsertExpr{
     pub TableName:Vec<u8>,
     pub Name:Vec<Vec<u8>>,
-    pub Values:Vec<Vec<Value>>,
+    pub Values:Vec<Vec<Expr>>,
 }
 
 impl InsertExpr{
@@ -18,24 +18,55 @@ impl InsertExpr{
         }
     }
 
-    pub fn createQuest<'a>(&'a self,tdef:&'a TableDef) -> Result<Vec<Record>,BTreeError>
+    // nextval allocates from a sequence, by its name
+    pub fn createQuest<'a>(&'a self,tdef:&'a TableDef,nextval:&mut dyn FnMut(&[u8])->Result<Value,BTreeError>) -> Result<Vec<Record>,BTreeError>
     {
+        let defaults = tdef.defaultExprs()?;
         let mut list = Vec::with_capacity(self.Values.len());
         for row in &self.Values
         {
+            if row.len() != self.Name.len()
+            {
+                return Err(BTreeError::ColumnValueMissing);
+            }
             let mut r: Record = Record::new(&tdef);
             for i in 0..self.Name.len()
             {
-                if let Err(err) = r.Set(&self.Name[i], row[i].clone())
+                let v = row[i].resolveNextVal(nextval)?.eval(tdef, &r.Vals)?;
+                if let Err(err) = r.Set(&self.Name[i], v)
                 {
                     return Err(err);
                 }
             }
+            Self::setDefaults(&mut r, tdef, &defaults, nextval)?;
             list.push(r);
         }
 
         Ok(list)
     }
+
+    // the omitted columns that have a DEFAULT or are autoincrement
+    fn setDefaults(r:&mut Record, tdef:&TableDef, defaults:&[Option<Expr>], nextval:&mut dyn FnMut(&[u8])->Result<Value,BTreeError>) -> Result<(),BTreeError>
+    {
+        for i in 0..tdef.Cols.len()
+        {
+            if r.Vals[i] != Value::None
+            {
+                continue;
+            }
+            if tdef.isAutoIncrement(i)
+            {
+                let v = nextval(&tdef.sequenceName(i))?;
+                r.Set(&tdef.Cols[i], v)?;
+            }
+            else if let Some(Some(expr)) = defaults.get(i)
+            {
+                let v = expr.resolveNextVal(nextval)?.eval(tdef, &r.Vals)?;
+                r.Set(&tdef.Cols[i], v)?;
+            }
+        }
+        Ok(())
+    }
     
 }
 
@@ -61,16 +92,16 @@ impl fmt::Display for InsertExpr {
 }
 
 
-fn Values<'a>() -> impl Parser<'a,Vec<Value>>
+fn Values<'a>() -> impl Parser<'a,Vec<Expr>>
 {
     right(
         match_literal("("),
         tuple3(
-            remove_lead_space(Constant()),
+            remove_lead_space(Expr()),
             zero_or_more(
                     right(
                         remove_lead_space(match_literal(",")),
-                        remove_lead_space(Constant())
+                        remove_lead_space(Expr())
                     )
                 ),
                 remove_lead_space(match_literal(")"))
@@ -84,7 +115,7 @@ fn Values<'a>() -> impl Parser<'a,Vec<Value>>
     )
 }
 
-fn Rows<'a>()-> impl Parser<'a,Vec<Vec<Value>>>
+fn Rows<'a>()-> impl Parser<'a,Vec<Vec<Expr>>>
 {
     tuple3(
         remove_lead_space_and_newline(Values()),
@@ -6,6 +6,8 @@ pub mod delete;
 pub mod update;
 pub mod insert;
 pub mod createtable;
+pub mod savepoint;
+pub mod sequence;
 
 pub mod requesthandler;
 pub mod sqlerror;
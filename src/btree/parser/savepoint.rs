@@ -0,0 +1,59 @@
+use super::lib::*;
+
+//stmt savepoint name;
+pub fn ExprSavePoint<'a>() -> impl Parser<'a,String> 
+{
+    left(
+        right(
+            remove_lead_space_and_newline(match_literal("savepoint")),
+            remove_lead_space_and_newline(id_string()),
+        ),
+        remove_lead_space_and_newline(match_literal(";"))
+    )
+}
+
+//stmt rollback to name;
+pub fn ExprRollbackTo<'a>() -> impl Parser<'a,String> 
+{
+    left(
+        right(
+            pair(
+                remove_lead_space_and_newline(match_literal("rollback")),
+                remove_lead_space_and_newline(match_literal("to")),
+            ),
+            remove_lead_space_and_newline(id_string()),
+        ),
+        remove_lead_space_and_newline(match_literal(";"))
+    )
+}
+
+//stmt release name;
+pub fn ExprRelease<'a>() -> impl Parser<'a,String> 
+{
+    left(
+        right(
+            remove_lead_space_and_newline(match_literal("release")),
+            remove_lead_space_and_newline(id_string()),
+        ),
+        remove_lead_space_and_newline(match_literal(";"))
+    )
+}
+
+#[test]
+fn test_savepoint_expr() {
+
+    let exp = "savepoint sp1;";
+    let ret = ExprSavePoint().parse(exp).unwrap();
+    assert_eq!("sp1",ret.1);
+
+    let exp = "rollback to sp1;";
+    let ret = ExprRollbackTo().parse(exp).unwrap();
+    assert_eq!("sp1",ret.1);
+
+    let exp = "release sp1;";
+    let ret = ExprRelease().parse(exp).unwrap();
+    assert_eq!("sp1",ret.1);
+
+    let exp = "rollback sp1;";
+    assert_eq!(true,ExprRollbackTo().parse(exp).is_err());
+}
@@ -5,6 +5,8 @@ pub struct SelectExpr{
     pub Scan:ScanExpr,
     pub Name:Vec<Vec<u8>>,
     pub Ouput:Vec<Expr>,
+    // AS OF SNAPSHOT name
+    pub AsOf:Option<String>,
 }
 impl fmt::Display for SelectExpr {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
@@ -25,6 +27,7 @@ impl SelectExpr{
             Scan:expr,
             Name:Vec::new(),
             Ouput:Vec::new(),
+            AsOf:None,
         }
     }
 }
@@ -70,18 +73,34 @@ fn ExprSelectItems<'a>() -> impl Parser<'a,Vec<(Expr,Vec<u8>)>>
     )
 }
 
+fn ExprAsOf<'a>() -> impl Parser<'a,String> 
+{
+    //as of snapshot monthend
+    right(
+        match_literal("as"),
+        right(
+            remove_lead_space(match_literal("of")),
+            right(
+                remove_lead_space(match_literal("snapshot")),
+                remove_lead_space(id_string())
+            )
+        )
+    )
+}
+
 //stmt select
 pub fn ExprSelect<'a>() -> impl Parser<'a,SelectExpr> 
 {
     left(
-        pair(
+        tuple3(
             remove_lead_space(ExprSelectItems()),
             remove_lead_space(ExprFrom()),
+            zero_or_more(remove_lead_space(ExprAsOf())),
         ),
         remove_lead_space(match_literal(";")
             )
     )
-        .map(|(v1,v2)|
+        .map(|(v1,v2,mut v3)|
         {
             let mut select = SelectExpr::new(v2);
             for item in v1
@@ -89,6 +108,7 @@ pub fn ExprSelect<'a>() -> impl Parser<'a,SelectExpr>
                 select.Name.push(item.1);
                 select.Ouput.push(item.0);
             }
+            select.AsOf = v3.pop();
             select
         }
     )   
@@ -115,6 +135,18 @@ fn test_selectitem_expr() {
 
 }
 
+#[test]
+fn test_select_asof_expr() {
+
+    let exp = "select id,name from tableA index by id >= 20 as of snapshot monthend;";
+    let ret = ExprSelect().parse(exp).unwrap();
+    assert_eq!(Some("monthend".to_string()),ret.1.AsOf);
+
+    let exp = "select id,name from tableA index by id >= 20;";
+    let ret = ExprSelect().parse(exp).unwrap();
+    assert_eq!(None,ret.1.AsOf);
+}
+
 #[test]
 fn test_scan_expr() {
 
@@ -173,7 +205,7 @@ mod tests {
             Cols : vec!["id".as_bytes().to_vec() , "name".as_bytes().to_vec(),"address".as_bytes().to_vec(),"age".as_bytes().to_vec(),"married".as_bytes().to_vec() ] ,
             PKeys : 0,
             Indexes : vec![vec!["address".as_bytes().to_vec() , "married".as_bytes().to_vec()],vec!["name".as_bytes().to_vec()]],
-            IndexPrefixes : vec![],
+            ..Default::default()
         };
 
         let ret = tx.AddTable(&mut table);
@@ -0,0 +1,27 @@
+use super::lib::*;
+
+//stmt create sequence name;
+pub fn ExprCreateSequence<'a>() -> impl Parser<'a,String> 
+{
+    left(
+        right(
+            pair(
+                remove_lead_space_and_newline(match_literal("create")),
+                remove_lead_space_and_newline(match_literal("sequence")),
+            ),
+            remove_lead_space_and_newline(id_string()),
+        ),
+        remove_lead_space_and_newline(match_literal(";"))
+    )
+}
+
+#[test]
+fn test_sequence_expr() {
+
+    let exp = "create sequence orders;";
+    let ret = ExprCreateSequence().parse(exp).unwrap();
+    assert_eq!("orders",ret.1);
+
+    let exp = "create sequence;";
+    assert_eq!(true,ExprCreateSequence().parse(exp).is_err());
+}
@@ -12,6 +12,8 @@ use super::createtable::ExprCreateTable;
 use super::delete::{DeleteExpr, ExprDelete};
 use super::expr::{id, number_i64};
 use super::insert::{ExprInsert, InsertExpr};
+use super::savepoint::{ExprRelease, ExprRollbackTo, ExprSavePoint};
+use super::sequence::ExprCreateSequence;
 use super::select::{ExprSelect, SelectExpr};
 use super::update::{ExprUpdate, UpdateExpr};
 use super::{expr::Expr};
@@ -23,6 +25,10 @@ pub enum SQLExpr{
     Delete(DeleteExpr),
     Insert(InsertExpr),
     CreatTable(TableDef),
+    SavePoint(String),
+    RollbackTo(String),
+    Release(String),
+    CreateSequence(String),
 }
 
 impl fmt::Display for SQLExpr {
@@ -33,6 +39,10 @@ impl fmt::Display for SQLExpr {
             SQLExpr::Delete(v)  => write!(f,"Delete:{}",v),
             SQLExpr::Insert(v)  => write!(f,"Insert:{}",v),
             SQLExpr::CreatTable(v)  => write!(f,"Create Table:{}",v),
+            SQLExpr::SavePoint(v)  => write!(f,"Savepoint:{}",v),
+            SQLExpr::RollbackTo(v)  => write!(f,"Rollback To:{}",v),
+            SQLExpr::Release(v)  => write!(f,"Release:{}",v),
+            SQLExpr::CreateSequence(v)  => write!(f,"Create Sequence:{}",v),
         }
     }
 }
@@ -61,6 +71,24 @@ impl ScanExpr{
         }
     }
 
+    // a literal, or a negative number
+    fn ConstValue(n:&Expr)->Option<Value>
+    {
+        if n.op == ExpressionType::UnOP
+        {
+            return match &n.left.as_ref()?.val {
+                Some(Value::INT64(v)) => Some(Value::INT64(-v)),
+                Some(Value::INT32(v)) => Some(Value::INT32(-v)),
+                Some(Value::INT16(v)) => Some(Value::INT16(-v)),
+                Some(Value::INT8(v)) => Some(Value::INT8(-v)),
+                Some(Value::FLOAT64(v)) => Some(Value::FLOAT64(-v)),
+                Some(Value::DECIMAL(v,s)) => Some(Value::DECIMAL(-v,*s)),
+                _Other => None,
+            };
+        }
+        n.val.clone()
+    }
+
     fn ExtractConditionItem(n:&Expr)->Option<(ExpressionType,Vec<u8>,Value)>
     {
         if n.op == ExpressionType::EQ || n.op == ExpressionType::LE || n.op == ExpressionType::LT
@@ -68,9 +96,9 @@ impl ScanExpr{
         {
             if let Some(Value::ID(keyName)) = &n.left.as_ref().unwrap().val
             {
-                if let Some(val) =  &n.right.as_ref().unwrap().val
+                if let Some(val) =  Self::ConstValue(n.right.as_ref().unwrap())
                 {
-                    return Some((n.op.clone(),keyName.to_vec(),val.clone()));
+                    return Some((n.op.clone(),keyName.to_vec(),val));
                 }
                 else {
                     return None;
@@ -340,14 +368,20 @@ pub fn ExprFrom<'a>() -> impl Parser<'a,ScanExpr>
 
 pub fn ExprSQL<'a>() -> impl Parser<'a,SQLExpr> 
 {
-    either(
+    either3(
     either4(
         ExprSelect().map(|v| SQLExpr::Select(v)), 
         ExprInsert().map(|v| SQLExpr::Insert(v)), 
         ExprUpdate().map(|v| SQLExpr::Update(v)), 
         ExprDelete().map(|v| SQLExpr::Delete(v)), 
     ),
-    ExprCreateTable().map(|v| SQLExpr::CreatTable(v)), 
+    either4(
+        ExprCreateTable().map(|v| SQLExpr::CreatTable(v)), 
+        ExprSavePoint().map(|v| SQLExpr::SavePoint(v)), 
+        ExprRollbackTo().map(|v| SQLExpr::RollbackTo(v)), 
+        ExprRelease().map(|v| SQLExpr::Release(v)), 
+    ),
+    ExprCreateSequence().map(|v| SQLExpr::CreateSequence(v)), 
     )
 }
 
@@ -372,7 +406,7 @@ fn test_createScan(){
         Cols : vec!["id".as_bytes().to_vec() , "name".as_bytes().to_vec(),"address".as_bytes().to_vec(),"age".as_bytes().to_vec(),"married".as_bytes().to_vec() ] ,
         PKeys : 0,
         Indexes : vec![vec!["address".as_bytes().to_vec() , "married".as_bytes().to_vec()],vec!["age".as_bytes().to_vec()]],
-        IndexPrefixes : vec![],
+        ..Default::default()
     };
 
 
@@ -0,0 +1,235 @@
+use crate::btree::BTreeError;
+
+// dates are days since 1970-01-01, timestamps are microseconds since 1970-01-01 00:00:00 UTC
+pub const MICROS_PER_SECOND: i64 = 1_000_000;
+pub const MICROS_PER_DAY: i64 = 86_400 * MICROS_PER_SECOND;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum DatePart {
+    Year,
+    Month,
+    Day,
+    Hour,
+    Minute,
+    Second,
+}
+
+// proleptic gregorian calendar, see http://howardhinnant.github.io/date_algorithms.html
+pub fn daysFromCivil(y: i64, m: u32, d: u32) -> i64 {
+    let y = if m <= 2 { y - 1 } else { y };
+    let era = (if y >= 0 { y } else { y - 399 }) / 400;
+    let yoe = y - era * 400;
+    let mp = (m as i64 + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + d as i64 - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146097 + doe - 719468
+}
+
+pub fn civilFromDays(days: i64) -> (i64, u32, u32) {
+    let z = days + 719468;
+    let era = (if z >= 0 { z } else { z - 146096 }) / 146097;
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let d = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let m = (if mp < 10 { mp + 3 } else { mp - 9 }) as u32;
+    let y = yoe + era * 400 + if m <= 2 { 1 } else { 0 };
+    (y, m, d)
+}
+
+fn isLeap(y: i64) -> bool {
+    (y % 4 == 0 && y % 100 != 0) || y % 400 == 0
+}
+
+fn daysInMonth(y: i64, m: u32) -> u32 {
+    match m {
+        1 | 3 | 5 | 7 | 8 | 10 | 12 => 31,
+        4 | 6 | 9 | 11 => 30,
+        _Other => if isLeap(y) { 29 } else { 28 },
+    }
+}
+
+// the day is clamped to the end of the month, 01-31 plus a month is 02-28
+pub fn addMonths(days: i64, months: i32) -> i64 {
+    if months == 0 {
+        return days;
+    }
+    let (y, m, d) = civilFromDays(days);
+    let total = y * 12 + (m as i64 - 1) + months as i64;
+    let y = total.div_euclid(12);
+    let m = (total.rem_euclid(12) + 1) as u32;
+    daysFromCivil(y, m, d.min(daysInMonth(y, m)))
+}
+
+fn parseNumber(s: &str, len: usize) -> Option<i64> {
+    if s.len() != len || s.chars().all(|c| c.is_ascii_digit()) == false {
+        return None;
+    }
+    s.parse::<i64>().ok()
+}
+
+// YYYY-MM-DD
+pub fn parseDate(s: &str) -> Option<i32> {
+    let parts: Vec<&str> = s.split('-').collect();
+    if parts.len() != 3 {
+        return None;
+    }
+    let y = parseNumber(parts[0], 4)?;
+    let m = parseNumber(parts[1], 2)? as u32;
+    let d = parseNumber(parts[2], 2)? as u32;
+    if m < 1 || m > 12 || d < 1 || d > daysInMonth(y, m) {
+        return None;
+    }
+    Some(daysFromCivil(y, m, d) as i32)
+}
+
+// YYYY-MM-DD[( |T)HH:MM[:SS[.ffffff]]][Z]
+pub fn parseTimestamp(s: &str) -> Option<i64> {
+    let s = s.strip_suffix('Z').unwrap_or(s);
+    if s.len() == 10 {
+        return Some(parseDate(s)? as i64 * MICROS_PER_DAY);
+    }
+    if s.len() < 16 || (&s[10..11] != " " && &s[10..11] != "T") {
+        return None;
+    }
+    let days = parseDate(&s[0..10])? as i64;
+
+    let (time, frac) = match s[11..].split_once('.') {
+        Some((time, frac)) => (time, Some(frac)),
+        None => (&s[11..], None),
+    };
+    let parts: Vec<&str> = time.split(':').collect();
+    if parts.len() < 2 || parts.len() > 3 || (frac.is_some() && parts.len() != 3) {
+        return None;
+    }
+    let h = parseNumber(parts[0], 2)?;
+    let mi = parseNumber(parts[1], 2)?;
+    let sec = if parts.len() == 3 { parseNumber(parts[2], 2)? } else { 0 };
+    if h > 23 || mi > 59 || sec > 59 {
+        return None;
+    }
+
+    let mut micros = 0;
+    if let Some(frac) = frac {
+        if frac.len() == 0 || frac.len() > 6 || frac.chars().all(|c| c.is_ascii_digit()) == false {
+            return None;
+        }
+        micros = frac.parse::<i64>().ok()? * 10_i64.pow(6 - frac.len() as u32);
+    }
+    Some(days * MICROS_PER_DAY + ((h * 60 + mi) * 60 + sec) * MICROS_PER_SECOND + micros)
+}
+
+// '1 year 2 months 3 days 4 hours', returns months, days and microseconds
+pub fn parseInterval(s: &str) -> Result<(i32, i32, i64), BTreeError> {
+    let words: Vec<&str> = s.split_whitespace().collect();
+    if words.len() == 0 || words.len() % 2 != 0 {
+        return Err(BTreeError::ValueTypeWrong(s.to_string()));
+    }
+
+    let overflow = || BTreeError::NumericOverflow(s.to_string());
+    let (mut months, mut days, mut micros) = (0_i32, 0_i32, 0_i64);
+    for pair in words.chunks(2) {
+        let n = pair[0].parse::<i64>().map_err(|_| BTreeError::ValueTypeWrong(s.to_string()))?;
+        let field = |n: i64, scale: i64| n.checked_mul(scale).and_then(|v| i32::try_from(v).ok());
+        match pair[1].trim_end_matches('s') {
+            "year" => months = field(n, 12).and_then(|v| months.checked_add(v)).ok_or_else(overflow)?,
+            "month" => months = field(n, 1).and_then(|v| months.checked_add(v)).ok_or_else(overflow)?,
+            "week" => days = field(n, 7).and_then(|v| days.checked_add(v)).ok_or_else(overflow)?,
+            "day" => days = field(n, 1).and_then(|v| days.checked_add(v)).ok_or_else(overflow)?,
+            "hour" => micros = n.checked_mul(3600 * MICROS_PER_SECOND).and_then(|v| micros.checked_add(v)).ok_or_else(overflow)?,
+            "minute" => micros = n.checked_mul(60 * MICROS_PER_SECOND).and_then(|v| micros.checked_add(v)).ok_or_else(overflow)?,
+            "second" => micros = n.checked_mul(MICROS_PER_SECOND).and_then(|v| micros.checked_add(v)).ok_or_else(overflow)?,
+            _Other => return Err(BTreeError::ValueTypeWrong(s.to_string())),
+        }
+    }
+    normalizeInterval(months, days, micros).ok_or_else(overflow)
+}
+
+// whole days of the microseconds are carried into the days, both get the sign of their sum.
+// so the fields compare in the order of the durations, months have no fixed length and come first
+pub fn normalizeInterval(months: i32, days: i32, micros: i64) -> Option<(i32, i32, i64)> {
+    let total = days as i128 * MICROS_PER_DAY as i128 + micros as i128;
+    let days = i32::try_from(total / MICROS_PER_DAY as i128).ok()?;
+    Some((months, days, (total % MICROS_PER_DAY as i128) as i64))
+}
+
+pub fn formatDate(days: i32) -> String {
+    let (y, m, d) = civilFromDays(days as i64);
+    format!("{:04}-{:02}-{:02}", y, m, d)
+}
+
+pub fn formatTimestamp(ts: i64) -> String {
+    let days = ts.div_euclid(MICROS_PER_DAY);
+    let time = ts.rem_euclid(MICROS_PER_DAY);
+    let secs = time / MICROS_PER_SECOND;
+    let micros = time % MICROS_PER_SECOND;
+    let mut s = format!("{} {:02}:{:02}:{:02}", formatDate(days as i32), secs / 3600, secs / 60 % 60, secs % 60);
+    if micros != 0 {
+        s += &format!(".{:06}", micros);
+    }
+    s
+}
+
+pub fn formatInterval(months: i32, days: i32, micros: i64) -> String {
+    let mut parts = Vec::new();
+    if months != 0 {
+        parts.push(format!("{} months", months));
+    }
+    if days != 0 {
+        parts.push(format!("{} days", days));
+    }
+    if micros != 0 || parts.len() == 0 {
+        let secs = micros as f64 / MICROS_PER_SECOND as f64;
+        parts.push(format!("{} seconds", secs));
+    }
+    parts.join(" ")
+}
+
+pub fn extract(ts: i64, part: &DatePart) -> i64 {
+    let (y, m, d) = civilFromDays(ts.div_euclid(MICROS_PER_DAY));
+    let secs = ts.rem_euclid(MICROS_PER_DAY) / MICROS_PER_SECOND;
+    match part {
+        DatePart::Year => y,
+        DatePart::Month => m as i64,
+        DatePart::Day => d as i64,
+        DatePart::Hour => secs / 3600,
+        DatePart::Minute => secs / 60 % 60,
+        DatePart::Second => secs % 60,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_format()
+    {
+        assert_eq!(Some(0), parseDate("1970-01-01"));
+        assert_eq!(Some(-1), parseDate("1969-12-31"));
+        assert_eq!(Some(19782), parseDate("2024-02-29"));
+        assert_eq!(None, parseDate("2023-02-29"));
+        assert_eq!(None, parseDate("2024-1-01"));
+        assert_eq!("2024-02-29", formatDate(19782));
+
+        let ts = parseTimestamp("2024-02-29T10:20:30.5Z").unwrap();
+        assert_eq!("2024-02-29 10:20:30.500000", formatTimestamp(ts));
+        assert_eq!(Some(19782 * MICROS_PER_DAY), parseTimestamp("2024-02-29"));
+        assert_eq!("1969-12-31 23:59:59", formatTimestamp(-MICROS_PER_SECOND));
+        assert_eq!(None, parseTimestamp("2024-02-29 24:00:00"));
+
+        assert_eq!((14, 3, 2 * 3600 * MICROS_PER_SECOND), parseInterval("1 year 2 months 3 days 2 hours").unwrap());
+        assert!(matches!(parseInterval("1 fortnight"), Err(BTreeError::ValueTypeWrong(_))));
+        assert_eq!((0, 1, 23 * 3600 * MICROS_PER_SECOND), parseInterval("3 days -25 hours").unwrap());
+        assert_eq!((0, 0, -3600 * MICROS_PER_SECOND), parseInterval("1 day -25 hours").unwrap());
+        assert!(matches!(parseInterval("200000000 years"), Err(BTreeError::NumericOverflow(_))));
+        assert!(matches!(parseInterval("9000000000000 hours"), Err(BTreeError::NumericOverflow(_))));
+        assert!(matches!(parseInterval("2147483647 days 1 day"), Err(BTreeError::NumericOverflow(_))));
+
+        assert_eq!(parseDate("2024-02-29").unwrap() as i64, addMonths(parseDate("2024-01-31").unwrap() as i64, 1));
+        assert_eq!(parseDate("2023-11-30").unwrap() as i64, addMonths(parseDate("2024-01-30").unwrap() as i64, -2));
+        assert_eq!(10, extract(ts, &DatePart::Hour));
+        assert_eq!(2, extract(ts, &DatePart::Month));
+    }
+}
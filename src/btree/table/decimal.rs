@@ -0,0 +1,101 @@
+// decimals are an unscaled i128 and a scale, 12.34 is (1234, 2)
+pub const MAX_PRECISION: u8 = 38;
+
+// a quotient keeps this many more digits than its operands
+pub const DIV_EXTRA_SCALE: u8 = 6;
+
+pub fn pow10(n: u8) -> Option<i128> {
+    10_i128.checked_pow(n as u32)
+}
+
+// the number of digits, 0 has one
+pub fn digits(v: i128) -> u8 {
+    let mut v = v.unsigned_abs();
+    let mut n = 1;
+    while v >= 10 {
+        v /= 10;
+        n += 1;
+    }
+    n
+}
+
+// a / b, rounded half away from zero
+pub fn divRound(a: i128, b: i128) -> Option<i128> {
+    let q = a.checked_div(b)?;
+    let r = a % b;
+    if r.unsigned_abs() * 2 >= b.unsigned_abs() {
+        return if (a < 0) == (b < 0) { q.checked_add(1) } else { q.checked_sub(1) };
+    }
+    Some(q)
+}
+
+// changes the scale of v, rounding when digits are dropped
+pub fn rescale(v: i128, from: u8, to: u8) -> Option<i128> {
+    if to >= from {
+        v.checked_mul(pow10(to - from)?)
+    } else {
+        divRound(v, pow10(from - to)?)
+    }
+}
+
+// 12.34, -0.5, 7
+pub fn parseDecimal(s: &str) -> Option<(i128, u8)> {
+    let (neg, s) = match s.strip_prefix('-') {
+        Some(s) => (true, s),
+        None => (false, s.strip_prefix('+').unwrap_or(s)),
+    };
+    let (int, frac) = s.split_once('.').unwrap_or((s, ""));
+    if int.len() == 0 || (int.len() + frac.len()) > MAX_PRECISION as usize
+        || int.chars().chain(frac.chars()).all(|c| c.is_ascii_digit()) == false {
+        return None;
+    }
+    let v = format!("{}{}", int, frac).parse::<i128>().ok()?;
+    Some((if neg { -v } else { v }, frac.len() as u8))
+}
+
+pub fn formatDecimal(v: i128, scale: u8) -> String {
+    let digits = v.unsigned_abs().to_string();
+    let sign = if v < 0 { "-" } else { "" };
+    if scale == 0 {
+        return format!("{}{}", sign, digits);
+    }
+    let digits = format!("{:0>width$}", digits, width = scale as usize + 1);
+    let (int, frac) = digits.split_at(digits.len() - scale as usize);
+    format!("{}{}.{}", sign, int, frac)
+}
+
+// the float as it prints, 0.1 is 0.1 rather than its binary expansion
+pub fn fromF64(v: f64) -> Option<(i128, u8)> {
+    if v.is_finite() == false {
+        return None;
+    }
+    parseDecimal(&format!("{}", v))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_round()
+    {
+        assert_eq!(Some((1234, 2)), parseDecimal("12.34"));
+        assert_eq!(Some((-5, 1)), parseDecimal("-0.5"));
+        assert_eq!(Some((7, 0)), parseDecimal("7"));
+        assert_eq!(None, parseDecimal("1.2.3"));
+        assert_eq!(None, parseDecimal(".5"));
+        assert_eq!("-0.05", formatDecimal(-5, 2));
+        assert_eq!("12.340", formatDecimal(12340, 3));
+        assert_eq!("42", formatDecimal(42, 0));
+
+        assert_eq!(Some(123), rescale(12345, 3, 1));
+        assert_eq!(Some(-124), rescale(-12350, 3, 1));
+        assert_eq!(Some(12), rescale(1249, 2, 0));
+        assert_eq!(Some(125000), rescale(125, 0, 3));
+        assert_eq!(None, rescale(i128::MAX, 0, 1));
+        assert_eq!(Some(1), divRound(2, 3));
+        assert_eq!(Some(-1), divRound(-1, 2));
+        assert_eq!(3, digits(-100));
+        assert_eq!(Some((1, 1)), fromF64(0.1));
+    }
+}
@@ -1,3 +1,5 @@
 pub mod value;
 pub mod table;
-pub mod record;
\ No newline at end of file
+pub mod record;
+pub mod datetime;
+pub mod decimal;
\ No newline at end of file
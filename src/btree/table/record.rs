@@ -2,6 +2,8 @@ use crate::btree::scan::comp::OP_CMP;
 use crate::btree::table::table::TableDef;
 use crate::btree::table::value::Value;
 use crate::btree::table::value::ValueType;
+use crate::btree::table::datetime;
+use crate::btree::table::decimal;
 use std::fmt;
 use std::panic;
 use crate::btree::BTreeError;
@@ -46,9 +48,20 @@ impl<'a> Record<'a> {
         let idx = self.GetColumnIndex(key);
         if let Some(i) = idx
         {
+            if val.isNull()
+            {
+                if self.def.isNullable(i) == false
+                {
+                    return Err(BTreeError::NullNotAllowed(std::str::from_utf8(key).unwrap().to_string()));
+                }
+                self.Vals[i] = val;
+                return Ok(());
+            }
+
             match (&self.def.Types[i],val)
             {
                 (ValueType::BYTES, val@Value::BYTES(_)) => self.Vals[i] = val,
+                (ValueType::BYTES, Value::BLOB(v)|Value::TEXT(v)) => self.Vals[i] = Value::BYTES(v),
                 (ValueType::BYTES, val@Value::None) =>  self.Vals[i] = val,
                 (ValueType::INT64, val@Value::INT64(_)) =>  self.Vals[i] = val,
                 (ValueType::INT64, Value::INT32(v)) => self.Vals[i] = Value::INT64(v as i64),
@@ -74,6 +87,47 @@ impl<'a> Record<'a> {
                 (ValueType::ID, val@Value::None) =>  self.Vals[i] = val,
                 (ValueType::BOOL, val@Value::BOOL(_)) =>  self.Vals[i] = val,
                 (ValueType::BOOL, val@Value::None) =>  self.Vals[i] = val,
+                (ValueType::FLOAT64, Value::FLOAT64(v)) =>  self.Vals[i] = Self::float(key, v)?,
+                (ValueType::FLOAT64, val@(Value::INT64(_)|Value::INT32(_)|Value::INT16(_)|Value::INT8(_)|Value::DECIMAL(_,_))) => self.Vals[i] = Self::float(key, val.toF64().unwrap())?,
+                (ValueType::FLOAT64, val@Value::None) =>  self.Vals[i] = val,
+                (ValueType::DATE, val@Value::DATE(_)) =>  self.Vals[i] = val,
+                (ValueType::DATE, Value::BYTES(v)) => self.Vals[i] = Value::DATE(Self::parseText(key, &v, datetime::parseDate)?),
+                (ValueType::DATE, val@Value::None) =>  self.Vals[i] = val,
+                (ValueType::TIMESTAMP, val@Value::TIMESTAMP(_)) =>  self.Vals[i] = val,
+                (ValueType::TIMESTAMP, val@Value::DATE(_)) =>  self.Vals[i] = Value::TIMESTAMP(val.toTimestamp().unwrap()),
+                (ValueType::TIMESTAMP, Value::BYTES(v)) => self.Vals[i] = Value::TIMESTAMP(Self::parseText(key, &v, datetime::parseTimestamp)?),
+                (ValueType::TIMESTAMP, val@Value::None) =>  self.Vals[i] = val,
+                (ValueType::INTERVAL, Value::INTERVAL(m,d,u)) => {
+                    let (m,d,u) = datetime::normalizeInterval(m, d, u)
+                        .ok_or(BTreeError::NumericOverflow(std::str::from_utf8(key).unwrap().to_string()))?;
+                    self.Vals[i] = Value::INTERVAL(m,d,u);
+                },
+                (ValueType::INTERVAL, Value::BYTES(v)) => {
+                    let name = std::str::from_utf8(key).unwrap().to_string();
+                    let text = std::str::from_utf8(&v).map_err(|_| BTreeError::ValueTypeWrong(name.clone()))?;
+                    let (m,d,u) = datetime::parseInterval(text).map_err(|err| match err {
+                        BTreeError::NumericOverflow(_) => BTreeError::NumericOverflow(name.clone()),
+                        _Other => BTreeError::ValueTypeWrong(name.clone()),
+                    })?;
+                    self.Vals[i] = Value::INTERVAL(m,d,u);
+                },
+                (ValueType::INTERVAL, val@Value::None) =>  self.Vals[i] = val,
+                (ValueType::DECIMAL(p,s), val@(Value::DECIMAL(_,_)|Value::FLOAT64(_)|Value::INT64(_)|Value::INT32(_)|Value::INT16(_)|Value::INT8(_))) =>
+                    self.Vals[i] = Self::round(key, &val, *p, *s)?,
+                (ValueType::DECIMAL(p,s), Value::BYTES(v)) => {
+                    let (v,scale) = Self::parseText(key, &v, decimal::parseDecimal)?;
+                    self.Vals[i] = Self::round(key, &Value::DECIMAL(v,scale), *p, *s)?;
+                },
+                (ValueType::DECIMAL(_,_), val@Value::None) =>  self.Vals[i] = val,
+                (ValueType::TEXT, Value::BYTES(v)|Value::BLOB(v)) => {
+                    std::str::from_utf8(&v).map_err(|_| BTreeError::ValueTypeWrong(std::str::from_utf8(key).unwrap().to_string()))?;
+                    self.Vals[i] = Value::TEXT(v);
+                },
+                (ValueType::TEXT, val@Value::TEXT(_)) =>  self.Vals[i] = val,
+                (ValueType::TEXT, val@Value::None) =>  self.Vals[i] = val,
+                (ValueType::BLOB, val@Value::BLOB(_)) =>  self.Vals[i] = val,
+                (ValueType::BLOB, Value::BYTES(v)|Value::TEXT(v)) =>  self.Vals[i] = Value::BLOB(v),
+                (ValueType::BLOB, val@Value::None) =>  self.Vals[i] = val,
                 _Other => return Err(BTreeError::ValueTypeWrong(std::str::from_utf8(key).unwrap().to_string()))     
             };
             Ok(())
@@ -84,6 +138,27 @@ impl<'a> Record<'a> {
     }
 
 
+    // NaN has no place in the key order and -0.0 is stored as 0.0, they are equal
+    fn float(key: &[u8], v: f64) -> Result<Value,BTreeError> {
+        if v.is_nan()
+        {
+            return Err(BTreeError::NotANumber(std::str::from_utf8(key).unwrap().to_string()));
+        }
+        Ok(Value::FLOAT64(if v == 0.0 { 0.0 } else { v }))
+    }
+
+    // a string given for a date, timestamp or interval column
+    fn parseText<T>(key: &[u8], v: &[u8], parse: fn(&str) -> Option<T>) -> Result<T,BTreeError> {
+        std::str::from_utf8(v).ok().and_then(parse)
+            .ok_or(BTreeError::ValueTypeWrong(std::str::from_utf8(key).unwrap().to_string()))
+    }
+
+    // a decimal column keeps its values at its own scale
+    fn round(key: &[u8], v: &Value, precision: u8, scale: u8) -> Result<Value,BTreeError> {
+        v.Round(precision, scale)
+            .map_err(|_| BTreeError::NumericOverflow(std::str::from_utf8(key).unwrap().to_string()))
+    }
+
     pub fn Get(&self, key: &[u8])-> Option<Value> {
         let idx = self.GetColumnIndex(key);
         match idx 
@@ -100,6 +175,17 @@ impl<'a> Record<'a> {
         self.def.findIndexWithRecord(&self.Vals)
     }
 
+    // the nullable columns that are not set are NULL
+    pub fn setNulls(&mut self) {
+        for i in 0..self.def.Cols.len()
+        {
+            if self.Vals[i] == Value::None && self.def.isNullable(i)
+            {
+                self.Vals[i] = Value::NULL;
+            }
+        }
+    }
+
     // check primaykey
     pub fn checkPrimaryKey(&self) -> bool {
 
@@ -185,6 +271,21 @@ impl<'a> Record<'a> {
         }
     }
 
+    // a primary key row stored before the integers were order-preserving
+    pub fn decodeLegacy(&mut self, key: &[u8], val: &[u8]) {
+        let mut pos: usize = 4;
+        for idx in 0..self.def.PKeys as usize + 1
+        {
+            pos = self.decodeValEx(key, idx, pos, true);
+        }
+
+        let mut pos: usize = 0;
+        for idx in self.def.PKeys as usize + 1..self.def.Cols.len()
+        {
+            pos = self.decodeValEx(val, idx, pos, true);
+        }
+    }
+
     // order-preserving encoding
     fn encodeKeys(&self, list:&mut Vec<u8>) {
 
@@ -227,6 +328,17 @@ impl<'a> Record<'a> {
         }
     }
 
+    // the entries of a unique index that start with this are duplicates
+    pub fn encodeUniqueKey(&self, index: usize, list: &mut Vec<u8>) {
+
+        list.extend_from_slice(&self.def.IndexPrefixes[index].to_le_bytes());
+        for c in self.def.indexColumns(index)
+        {
+            let idx = self.GetColumnIndex(c);
+            self.encodeVal(idx.unwrap(), list);
+        }
+    }
+
     // The range key can be a prefix of the index key,
     // we may have to encode missing columns to make the comparison work.
     pub fn encodeKeyPartial(&self,idx:usize, list: &mut Vec<u8>,cmp:&OP_CMP) 
@@ -252,7 +364,12 @@ impl<'a> Record<'a> {
                             ValueType::INT16 => {list.extend(&[0xff;2])},
                             ValueType::INT32 => {list.extend(&[0xff;4])},
                             ValueType::INT64 => {list.extend(&[0xff;8])},
-                            ValueType::BYTES => { list.push(0xff)},
+                            ValueType::FLOAT64 => {list.extend(&[0xff;8])},
+                            ValueType::DATE => {list.extend(&[0xff;4])},
+                            ValueType::TIMESTAMP => {list.extend(&[0xff;8])},
+                            ValueType::INTERVAL => {list.extend(&[0xff;16])},
+                            ValueType::DECIMAL(_,_) => {list.extend(&[0xff;16])},
+                            ValueType::BYTES | ValueType::TEXT | ValueType::BLOB => { list.push(0xff)},
                             Other=> {panic!()}
                         }
                     }
@@ -287,12 +404,41 @@ impl<'a> Record<'a> {
 
     fn encodeVal(&self, idx: usize, list:&mut Vec<u8>) {
 
+        // nullable columns start with a marker, NULL sorts first
+        if self.def.isNullable(idx)
+        {
+            match &self.Vals[idx]
+            {
+                Value::NULL | Value::None => {
+                    list.push(0);
+                    return;
+                },
+                _Other => list.push(1),
+            }
+        }
+
         match &self.Vals[idx]
          {
-            Value::INT8(v) => list.extend_from_slice(&v.to_le_bytes()),
-            Value::INT16(v) => list.extend_from_slice(&v.to_le_bytes()),
-            Value::INT32(v) => list.extend_from_slice(&v.to_le_bytes()),
-            Value::INT64(v) => list.extend_from_slice(&v.to_le_bytes()),
+            // big-endian with the sign bit flipped, so the bytes compare like the numbers
+            Value::INT8(v) => list.extend_from_slice(&((*v as u8) ^ 0x80).to_be_bytes()),
+            Value::INT16(v) => list.extend_from_slice(&((*v as u16) ^ 0x8000).to_be_bytes()),
+            Value::INT32(v) => list.extend_from_slice(&((*v as u32) ^ 0x8000_0000).to_be_bytes()),
+            Value::INT64(v) => list.extend_from_slice(&((*v as u64) ^ 0x8000_0000_0000_0000).to_be_bytes()),
+            Value::DATE(v) => list.extend_from_slice(&((*v as u32) ^ 0x8000_0000).to_be_bytes()),
+            Value::TIMESTAMP(v) => list.extend_from_slice(&((*v as u64) ^ 0x8000_0000_0000_0000).to_be_bytes()),
+            Value::INTERVAL(m,d,u) => {
+                list.extend_from_slice(&((*m as u32) ^ 0x8000_0000).to_be_bytes());
+                list.extend_from_slice(&((*d as u32) ^ 0x8000_0000).to_be_bytes());
+                list.extend_from_slice(&((*u as u64) ^ 0x8000_0000_0000_0000).to_be_bytes());
+            },
+            // all the values of a column have its scale, so the unscaled values order them
+            Value::DECIMAL(v,_) => list.extend_from_slice(&((*v as u128) ^ (1 << 127)).to_be_bytes()),
+            // negative floats have all the bits flipped, the others only the sign bit
+            Value::FLOAT64(v) => {
+                let bits = if *v == 0.0 { 0.0f64.to_bits() } else { v.to_bits() };
+                let bits = if bits >> 63 == 1 { !bits } else { bits ^ 0x8000_0000_0000_0000 };
+                list.extend_from_slice(&bits.to_be_bytes());
+            },
             Value::BOOL(v) => {
                 if *v == true {
                     list.extend_from_slice(&[1;1]);
@@ -300,7 +446,7 @@ impl<'a> Record<'a> {
                     list.extend_from_slice(&[0;1]);
                 }
             },
-            Value::BYTES(v) => {
+            Value::BYTES(v) | Value::BLOB(v) | Value::TEXT(v) => {
                 crate::btree::util::escapeString(v, list);
                 //list.extend_from_slice(v);
                 list.push(0);
@@ -313,23 +459,81 @@ impl<'a> Record<'a> {
     }
 
     fn decodeVal(&mut self, val:&[u8], idx: usize, pos: usize) -> usize {
+        self.decodeValEx(val, idx, pos, false)
+    }
+
+    // legacy files store the integers little-endian
+    fn decodeValEx(&mut self, val:&[u8], idx: usize, pos: usize, legacy: bool) -> usize {
+        let mut pos = pos;
+        if self.def.isNullable(idx)
+        {
+            if val[pos] == 0
+            {
+                self.Vals[idx] = Value::NULL;
+                return pos + 1;
+            }
+            pos += 1;
+        }
+
         match (self.def.Types[idx]) {
-            ValueType::INT8 => {
+            ValueType::INT8 if legacy => {
                 self.Vals[idx] = Value::INT8(i8::from_le_bytes([val[pos];1]));
                 return pos + 1;
             },
-            ValueType::INT16 => {
+            ValueType::INT16 if legacy => {
                 self.Vals[idx] = Value::INT16(i16::from_le_bytes( val[pos..pos+2].try_into().unwrap() ));
                 return pos + 2;
             },
-            ValueType::INT32 => {
+            ValueType::INT32 if legacy => {
                 self.Vals[idx] = Value::INT32(i32::from_le_bytes( val[pos..pos+4].try_into().unwrap() ));
                 return pos + 4;
             },
-            ValueType::INT64 => {
+            ValueType::INT64 if legacy => {
                 self.Vals[idx] = Value::INT64(i64::from_le_bytes( val[pos..pos+8].try_into().unwrap() ));
                 return pos + 8;
             },
+            ValueType::INT8 => {
+                self.Vals[idx] = Value::INT8((val[pos] ^ 0x80) as i8);
+                return pos + 1;
+            },
+            ValueType::INT16 => {
+                self.Vals[idx] = Value::INT16((u16::from_be_bytes( val[pos..pos+2].try_into().unwrap() ) ^ 0x8000) as i16);
+                return pos + 2;
+            },
+            ValueType::INT32 => {
+                self.Vals[idx] = Value::INT32((u32::from_be_bytes( val[pos..pos+4].try_into().unwrap() ) ^ 0x8000_0000) as i32);
+                return pos + 4;
+            },
+            ValueType::INT64 => {
+                self.Vals[idx] = Value::INT64((u64::from_be_bytes( val[pos..pos+8].try_into().unwrap() ) ^ 0x8000_0000_0000_0000) as i64);
+                return pos + 8;
+            },
+            ValueType::DATE => {
+                self.Vals[idx] = Value::DATE((u32::from_be_bytes( val[pos..pos+4].try_into().unwrap() ) ^ 0x8000_0000) as i32);
+                return pos + 4;
+            },
+            ValueType::TIMESTAMP => {
+                self.Vals[idx] = Value::TIMESTAMP((u64::from_be_bytes( val[pos..pos+8].try_into().unwrap() ) ^ 0x8000_0000_0000_0000) as i64);
+                return pos + 8;
+            },
+            ValueType::INTERVAL => {
+                let m = (u32::from_be_bytes( val[pos..pos+4].try_into().unwrap() ) ^ 0x8000_0000) as i32;
+                let d = (u32::from_be_bytes( val[pos+4..pos+8].try_into().unwrap() ) ^ 0x8000_0000) as i32;
+                let u = (u64::from_be_bytes( val[pos+8..pos+16].try_into().unwrap() ) ^ 0x8000_0000_0000_0000) as i64;
+                self.Vals[idx] = Value::INTERVAL(m,d,u);
+                return pos + 16;
+            },
+            ValueType::DECIMAL(_,scale) => {
+                let v = (u128::from_be_bytes( val[pos..pos+16].try_into().unwrap() ) ^ (1 << 127)) as i128;
+                self.Vals[idx] = Value::DECIMAL(v,scale);
+                return pos + 16;
+            },
+            ValueType::FLOAT64 => {
+                let bits = u64::from_be_bytes( val[pos..pos+8].try_into().unwrap() );
+                let bits = if bits >> 63 == 1 { bits ^ 0x8000_0000_0000_0000 } else { !bits };
+                self.Vals[idx] = Value::FLOAT64(f64::from_bits(bits));
+                return pos + 8;
+            },
             ValueType::BOOL => {
                 if val[pos] == 1 {
                     self.Vals[idx] = Value::BOOL(true);
@@ -338,7 +542,7 @@ impl<'a> Record<'a> {
                 }
                 return pos + 1;
             },            
-            ValueType::BYTES => {
+            ValueType::BYTES | ValueType::TEXT | ValueType::BLOB => {
                 let mut end = pos;
                 while val[end] != 0
                 {
@@ -353,7 +557,12 @@ impl<'a> Record<'a> {
                     },
                     Other => {}
                 }
-                self.Vals[idx] = Value::BYTES(ret);                        
+                match &self.def.Types[idx]
+                {
+                    ValueType::BLOB => self.Vals[idx] = Value::BLOB(ret),
+                    ValueType::TEXT => self.Vals[idx] = Value::TEXT(ret),
+                    _ => self.Vals[idx] = Value::BYTES(ret),
+                }
                 return end + 1;
             },
             _=>{
@@ -377,7 +586,7 @@ mod tests {
             Cols : vec!["id".as_bytes().to_vec() , "name".as_bytes().to_vec(),"address".as_bytes().to_vec(),"age".as_bytes().to_vec(),"married".as_bytes().to_vec() ] ,
             PKeys : 1,
             Indexes : vec![vec!["address".as_bytes().to_vec() , "married".as_bytes().to_vec()],vec!["age".as_bytes().to_vec()]],
-            IndexPrefixes : vec![],
+            ..Default::default()
         };
         table.FixIndexes();
 
@@ -421,7 +630,7 @@ mod tests {
             Cols : vec!["id".as_bytes().to_vec() , "name".as_bytes().to_vec(),"address".as_bytes().to_vec(),"age".as_bytes().to_vec(),"married".as_bytes().to_vec() ] ,
             PKeys : 0,
             Indexes : vec![vec!["address".as_bytes().to_vec() , "age".as_bytes().to_vec()],vec!["name".as_bytes().to_vec(),"age".as_bytes().to_vec()]],
-            IndexPrefixes : vec![],
+            ..Default::default()
         };
         table.FixIndexes();
 
@@ -462,6 +671,298 @@ mod tests {
 
     }
 
+    #[test]
+    fn test_encode_int_order()
+    {
+        let mut table = TableDef{
+            Prefix:3,
+            Name: "account".as_bytes().to_vec(),
+            Types : vec![ValueType::INT64, ValueType::INT16, ValueType::INT8 ] ,
+            Cols : vec!["id".as_bytes().to_vec() , "balance".as_bytes().to_vec(),"level".as_bytes().to_vec() ] ,
+            PKeys : 0,
+            Indexes : vec![vec!["balance".as_bytes().to_vec()]],
+            ..Default::default()
+        };
+        table.FixIndexes();
+
+        //the encoded keys sort like the numbers
+        let nums: [i64;7] = [i64::MIN, -300, -1, 0, 1, 255, i64::MAX];
+        let mut keys = Vec::new();
+        for n in nums
+        {
+            let mut rc = Record::new(&table);
+            rc.Set("id".as_bytes(), Value::INT64(n)).unwrap();
+            rc.Set("balance".as_bytes(), Value::INT16(n as i16)).unwrap();
+            rc.Set("level".as_bytes(), Value::INT8(n as i8)).unwrap();
+
+            let mut key = Vec::new();
+            rc.encodeKey(table.Prefix, &mut key);
+            keys.push(key.clone());
+
+            let mut vals = Vec::new();
+            rc.encodeValues(&mut vals);
+            let mut rc1 = Record::new(&table);
+            rc1.deencodeKey(&key);
+            rc1.decodeValues(&vals);
+            assert_eq!(Value::INT64(n),rc1.Vals[0]);
+            assert_eq!(Value::INT16(n as i16),rc1.Vals[1]);
+            assert_eq!(Value::INT8(n as i8),rc1.Vals[2]);
+        }
+        for i in 1..keys.len()
+        {
+            assert!(keys[i-1] < keys[i]);
+        }
+
+        let mut low = Record::new(&table);
+        low.Set("balance".as_bytes(), Value::INT16(-2)).unwrap();
+        let mut high = Record::new(&table);
+        high.Set("balance".as_bytes(), Value::INT16(1)).unwrap();
+        let mut lowKey = Vec::new();
+        low.encodeKeyPartial(0, &mut lowKey, &OP_CMP::CMP_GT);
+        let mut highKey = Vec::new();
+        high.encodeKeyPartial(0, &mut highKey, &OP_CMP::CMP_LT);
+        assert!(lowKey < highKey);
+    }
+
+    #[test]
+    fn test_encode_float_order()
+    {
+        let mut table = TableDef{
+            Prefix:3,
+            Name: "price".as_bytes().to_vec(),
+            Types : vec![ValueType::FLOAT64, ValueType::FLOAT64 ] ,
+            Cols : vec!["amount".as_bytes().to_vec() , "weight".as_bytes().to_vec() ] ,
+            PKeys : 0,
+            Indexes : vec![],
+            ..Default::default()
+        };
+        table.FixIndexes();
+
+        let nums: [f64;9] = [f64::NEG_INFINITY, f64::MIN, -2.5, -1e-300, 0.0, 1e-300, 0.5, f64::MAX, f64::INFINITY];
+        let mut keys = Vec::new();
+        for n in nums
+        {
+            let mut rc = Record::new(&table);
+            rc.Set("amount".as_bytes(), Value::FLOAT64(n)).unwrap();
+            rc.Set("weight".as_bytes(), Value::INT32(2)).unwrap();
+
+            let mut key = Vec::new();
+            rc.encodeKey(table.Prefix, &mut key);
+            keys.push(key.clone());
+
+            let mut vals = Vec::new();
+            rc.encodeValues(&mut vals);
+            let mut rc1 = Record::new(&table);
+            rc1.deencodeKey(&key);
+            rc1.decodeValues(&vals);
+            assert_eq!(Value::FLOAT64(n),rc1.Vals[0]);
+            assert_eq!(Value::FLOAT64(2.0),rc1.Vals[1]);
+        }
+        for i in 1..keys.len()
+        {
+            assert!(keys[i-1] < keys[i]);
+        }
+
+        //-0.0 is stored as 0.0, NaN is rejected
+        let mut rc = Record::new(&table);
+        rc.Set("amount".as_bytes(), Value::FLOAT64(-0.0)).unwrap();
+        assert_eq!(0.0f64.to_bits(),rc.Get("amount".as_bytes()).unwrap().toF64().unwrap().to_bits());
+        let mut key = Vec::new();
+        rc.encodeKey(table.Prefix, &mut key);
+        assert_eq!(keys[4],key);
+        assert_eq!(true,matches!(rc.Set("amount".as_bytes(), Value::FLOAT64(f64::NAN)),Err(BTreeError::NotANumber(_))));
+    }
+
+    #[test]
+    fn test_encode_decimal_order()
+    {
+        let mut table = TableDef{
+            Prefix:3,
+            Name: "price".as_bytes().to_vec(),
+            Types : vec![ValueType::DECIMAL(10,2), ValueType::DECIMAL(4,1) ] ,
+            Cols : vec!["amount".as_bytes().to_vec() , "rate".as_bytes().to_vec() ] ,
+            PKeys : 0,
+            Indexes : vec![],
+            ..Default::default()
+        };
+        table.FixIndexes();
+
+        let nums = ["-99999999.99", "-2.5", "-0.01", "0", "0.005", "1", "12.34", "99999999.99"];
+        let mut keys = Vec::new();
+        for n in nums
+        {
+            let mut rc = Record::new(&table);
+            rc.Set("amount".as_bytes(), Value::BYTES(n.as_bytes().to_vec())).unwrap();
+            rc.Set("rate".as_bytes(), Value::FLOAT64(0.25)).unwrap();
+
+            let mut key = Vec::new();
+            rc.encodeKey(table.Prefix, &mut key);
+            keys.push(key.clone());
+
+            let mut vals = Vec::new();
+            rc.encodeValues(&mut vals);
+            let mut rc1 = Record::new(&table);
+            rc1.deencodeKey(&key);
+            rc1.decodeValues(&vals);
+            assert_eq!(rc.Vals[0],rc1.Vals[0]);
+            assert_eq!(Value::DECIMAL(3,1),rc1.Vals[1]);
+        }
+        for i in 1..keys.len()
+        {
+            assert!(keys[i-1] < keys[i]);
+        }
+
+        let mut rc = Record::new(&table);
+        assert!(rc.Set("amount".as_bytes(), Value::INT64(123456789)).is_err());
+        //rounded half away from zero to the column's scale
+        rc.Set("amount".as_bytes(), Value::BYTES("19.999".as_bytes().to_vec())).unwrap();
+        assert_eq!(Value::DECIMAL(2000,2),rc.Vals[0]);
+        assert!(rc.Set("amount".as_bytes(), Value::BYTES("1.2.3".as_bytes().to_vec())).is_err());
+        rc.Set("amount".as_bytes(), Value::INT64(-7)).unwrap();
+        assert_eq!(Value::DECIMAL(-700,2),rc.Vals[0]);
+    }
+
+    #[test]
+    fn test_encode_timestamp_order()
+    {
+        let mut table = TableDef{
+            Prefix:3,
+            Name: "event".as_bytes().to_vec(),
+            Types : vec![ValueType::TIMESTAMP, ValueType::DATE ] ,
+            Cols : vec!["at".as_bytes().to_vec() , "day".as_bytes().to_vec() ] ,
+            PKeys : 0,
+            Indexes : vec![],
+            ..Default::default()
+        };
+        table.FixIndexes();
+
+        //the strings are parsed, the keys sort across the epoch
+        let times = [("1969-12-31 23:00:00","1969-12-31"), ("2024-02-29T23:59:59Z","2024-02-29"), ("2024-03-01 08:00:00","2024-03-01"), ("2025-01-01 00:00:00","2025-01-01")];
+        let mut keys = Vec::new();
+        for (at,day) in times
+        {
+            let mut rc = Record::new(&table);
+            rc.Set("at".as_bytes(), Value::BYTES(at.as_bytes().to_vec())).unwrap();
+            rc.Set("day".as_bytes(), Value::BYTES(day.as_bytes().to_vec())).unwrap();
+            assert_eq!(day,rc.Vals[1].to_string());
+
+            let mut key = Vec::new();
+            rc.encodeKey(table.Prefix, &mut key);
+            keys.push(key.clone());
+
+            let mut vals = Vec::new();
+            rc.encodeValues(&mut vals);
+            let mut rc1 = Record::new(&table);
+            rc1.deencodeKey(&key);
+            rc1.decodeValues(&vals);
+            assert_eq!(rc.Vals,rc1.Vals);
+        }
+        for i in 1..keys.len()
+        {
+            assert!(keys[i-1] < keys[i]);
+        }
+
+        let mut rc = Record::new(&table);
+        rc.Set("at".as_bytes(), Value::DATE(datetime::parseDate("2024-03-01").unwrap())).unwrap();
+        assert_eq!("2024-03-01 00:00:00",rc.Vals[0].to_string());
+        assert_eq!(true,matches!(rc.Set("day".as_bytes(), Value::BYTES("2024-02-30".as_bytes().to_vec())),Err(BTreeError::ValueTypeWrong(_))));
+    }
+
+    #[test]
+    fn test_encode_text_blob()
+    {
+        let mut table = TableDef{
+            Prefix:3,
+            Name: "file".as_bytes().to_vec(),
+            Types : vec![ValueType::BLOB, ValueType::TEXT, ValueType::BYTES ] ,
+            Cols : vec!["data".as_bytes().to_vec() , "name".as_bytes().to_vec(),"raw".as_bytes().to_vec() ] ,
+            PKeys : 0,
+            Indexes : vec![],
+            ..Default::default()
+        };
+        table.FixIndexes();
+
+        //the blobs are ordered bytewise
+        let blobs = [vec![], vec![0x00], vec![0x00,0xff,0x01], vec![0x01], vec![0xff]];
+        let mut keys = Vec::new();
+        for b in blobs
+        {
+            let mut rc = Record::new(&table);
+            rc.Set("data".as_bytes(), Value::BLOB(b.clone())).unwrap();
+            rc.Set("name".as_bytes(), Value::BYTES("a.bin".as_bytes().to_vec())).unwrap();
+            rc.Set("raw".as_bytes(), Value::BLOB(vec![0xc3,0x28])).unwrap();
+
+            let mut key = Vec::new();
+            rc.encodeKey(table.Prefix, &mut key);
+            keys.push(key.clone());
+
+            let mut vals = Vec::new();
+            rc.encodeValues(&mut vals);
+            let mut rc1 = Record::new(&table);
+            rc1.deencodeKey(&key);
+            rc1.decodeValues(&vals);
+            assert_eq!(Value::BLOB(b),rc1.Vals[0]);
+            assert_eq!(Value::TEXT("a.bin".as_bytes().to_vec()),rc1.Vals[1]);
+            assert_eq!(Value::BYTES(vec![0xc3,0x28]),rc1.Vals[2]);
+        }
+        for i in 1..keys.len()
+        {
+            assert!(keys[i-1] < keys[i]);
+        }
+
+        //a string is taken as its bytes, a TEXT column needs valid UTF-8
+        let mut rc = Record::new(&table);
+        rc.Set("data".as_bytes(), Value::BYTES("hello".as_bytes().to_vec())).unwrap();
+        assert_eq!("x'68656c6c6f'",rc.Vals[0].to_string());
+        assert_eq!(true,matches!(rc.Set("name".as_bytes(), Value::BLOB(vec![0xff])),Err(BTreeError::ValueTypeWrong(_))));
+        rc.Set("name".as_bytes(), Value::BLOB("b.txt".as_bytes().to_vec())).unwrap();
+        assert_eq!("b.txt",rc.Vals[1].to_string());
+    }
+
+    #[test]
+    fn test_encode_null()
+    {
+        let mut table = TableDef{
+            Prefix:3,
+            Name: "person".as_bytes().to_vec(),
+            Types : vec![ValueType::INT64, ValueType::INT16, ValueType::BYTES ] ,
+            Cols : vec!["id".as_bytes().to_vec() , "age".as_bytes().to_vec(),"name".as_bytes().to_vec() ] ,
+            PKeys : 0,
+            Indexes : vec![vec!["age".as_bytes().to_vec()]],
+            Nullable : vec![true,true,false],
+            ..Default::default()
+        };
+        table.FixIndexes();
+
+        let mut rc = Record::new(&table);
+        assert!(rc.Set("id".as_bytes(), Value::NULL).is_err());
+        assert!(rc.Set("name".as_bytes(), Value::NULL).is_err());
+        rc.Set("id".as_bytes(), Value::INT64(1)).unwrap();
+        rc.Set("name".as_bytes(), Value::BYTES("Bob".as_bytes().to_vec())).unwrap();
+        rc.setNulls();
+        assert_eq!(Value::NULL,rc.Vals[1]);
+
+        let mut vals = Vec::new();
+        rc.encodeValues(&mut vals);
+        let mut rc1 = Record::new(&table);
+        rc1.decodeValues(&vals);
+        assert_eq!(Value::NULL,rc1.Vals[1]);
+        assert_eq!(Value::BYTES("Bob".as_bytes().to_vec()),rc1.Vals[2]);
+
+        //NULL sorts before any value in an index
+        let mut nullKey = Vec::new();
+        rc.encodeIndex(table.IndexPrefixes[0], 0, &mut nullKey);
+        rc.Set("age".as_bytes(), Value::INT16(i16::MIN)).unwrap();
+        let mut minKey = Vec::new();
+        rc.encodeIndex(table.IndexPrefixes[0], 0, &mut minKey);
+        assert!(nullKey < minKey);
+
+        let mut rc1 = Record::new(&table);
+        rc1.decodeKeyPartrial(0, &nullKey);
+        assert_eq!(Value::NULL,rc1.Vals[1]);
+        assert_eq!(Value::INT64(1),rc1.Vals[0]);
+    }
+
     #[test]
     fn test_encode_decode_keyParital()
     {
@@ -472,7 +973,7 @@ mod tests {
             Cols : vec!["id".as_bytes().to_vec() , "name".as_bytes().to_vec(),"address".as_bytes().to_vec(),"age".as_bytes().to_vec(),"married".as_bytes().to_vec() ] ,
             PKeys : 0,
             Indexes : vec![vec!["address".as_bytes().to_vec() , "age".as_bytes().to_vec()],vec!["name".as_bytes().to_vec(),"age".as_bytes().to_vec()]],
-            IndexPrefixes : vec![],
+            ..Default::default()
         };
         table.FixIndexes();
         println!("{}",table);
@@ -1,3 +1,5 @@
+use crate::btree::parser::{expr::Expr, lib::Parser};
+use crate::btree::table::record::Record;
 use crate::btree::table::value::Value;
 use crate::btree::table::value::ValueType;
 use crate::btree::BTreeError;
@@ -5,7 +7,17 @@ use std::fmt;
 use std::str::FromStr;
 use serde::{Serialize, Deserialize};
 
-#[derive(Serialize,Clone,Deserialize, Debug)]
+// foreign key (Cols) references Table(RefCols), RefCols are the primary key of Table
+#[derive(Serialize,Clone,Deserialize, Debug, PartialEq)]
+pub struct ForeignKey{
+    pub Cols: Vec<Vec<u8>>,
+    pub Table: Vec<u8>,
+    pub RefCols: Vec<Vec<u8>>,
+    // on delete cascade, otherwise restrict
+    pub Cascade: bool,
+}
+
+#[derive(Serialize,Clone,Deserialize, Debug, Default)]
 pub struct TableDef{
     pub Name:Vec<u8>,
     pub Types: Vec<ValueType>,
@@ -14,6 +26,32 @@ pub struct TableDef{
     pub Prefix: u32,
     pub Indexes: Vec<Vec<Vec<u8>>>,
     pub IndexPrefixes: Vec<u32>,
+    // by column, tables created before NULL was supported have none
+    #[serde(default)]
+    pub Nullable: Vec<bool>,
+    // by column, the source of the DEFAULT expression
+    #[serde(default)]
+    pub Defaults: Vec<Option<String>>,
+    // the sources of the table's CHECK expressions
+    #[serde(default)]
+    pub Checks: Vec<String>,
+    // by column, filled from the column's own sequence when omitted
+    #[serde(default)]
+    pub AutoIncrement: Vec<bool>,
+    // by index, no two rows share the values of a unique index's own columns
+    #[serde(default)]
+    pub Unique: Vec<bool>,
+    #[serde(default)]
+    pub ForeignKeys: Vec<ForeignKey>,
+}
+
+// the whole source is one expression
+fn parseExpr(src:&str) -> Result<Expr,BTreeError>
+{
+    match Expr().parse(src) {
+        Ok((rest,expr)) if rest.trim().is_empty() => Ok(expr),
+        _Other => Err(BTreeError::BadExpression(src.to_string())),
+    }
 }
 
 impl TableDef{
@@ -21,13 +59,7 @@ impl TableDef{
     pub fn create(content:String) -> Self{
         TableDef{
             Name:content.as_bytes().to_vec(),
-            Types:Vec::new(),
-            Cols:Vec::new(),
-            Indexes:Vec::new(),
-            PKeys:0,
-            
-            IndexPrefixes:Vec::new(),
-            Prefix:0,
+            ..Default::default()
         }
     }
 
@@ -48,6 +80,98 @@ impl TableDef{
         return None;
     }
 
+    // primary key columns are never nullable
+    pub fn isNullable(&self, idx: usize) -> bool
+    {
+        idx > self.PKeys as usize && self.Nullable.get(idx) == Some(&true)
+    }
+
+    // declared not null, rather than a primary key or a column of an old table
+    pub fn isNotNull(&self, idx: usize) -> bool
+    {
+        idx > self.PKeys as usize && self.Nullable.get(idx) == Some(&false)
+    }
+
+    pub fn isAutoIncrement(&self, idx: usize) -> bool
+    {
+        self.AutoIncrement.get(idx) == Some(&true)
+    }
+
+    pub fn isUniqueIndex(&self, index: usize) -> bool
+    {
+        self.Unique.get(index) == Some(&true)
+    }
+
+    // the columns of an index as declared, FixIndexes appends the primary key to them
+    pub fn indexColumns(&self, index: usize) -> &[Vec<u8>]
+    {
+        let n = self.Indexes[index].len() - (self.PKeys as usize + 1);
+        &self.Indexes[index][0..n]
+    }
+
+    // table(col1,col2)
+    pub fn indexName(&self, index: usize) -> String
+    {
+        let cols: Vec<String> = self.indexColumns(index).iter().map(|c| String::from_utf8_lossy(c).to_string()).collect();
+        format!("{}({})", String::from_utf8_lossy(&self.Name), cols.join(","))
+    }
+
+    // table(col1) references other(col2)
+    pub fn foreignKeyName(&self, idx: usize) -> String
+    {
+        let fk = &self.ForeignKeys[idx];
+        let cols = |list:&Vec<Vec<u8>>| list.iter().map(|c| String::from_utf8_lossy(c).to_string()).collect::<Vec<String>>().join(",");
+        format!("{}({}) references {}({})", String::from_utf8_lossy(&self.Name), cols(&fk.Cols), String::from_utf8_lossy(&fk.Table), cols(&fk.RefCols))
+    }
+
+    // the sequence of an autoincrement column, user sequences can't have a dot in the name
+    pub fn sequenceName(&self, idx: usize) -> Vec<u8>
+    {
+        let mut name = self.Name.clone();
+        name.push(b'.');
+        name.extend_from_slice(&self.Cols[idx]);
+        name
+    }
+
+    // the CHECK expressions, a statement parses them once for all its rows
+    pub fn checkExprs(&self) -> Result<Vec<Expr>,BTreeError>
+    {
+        self.Checks.iter().map(|src| parseExpr(src)).collect()
+    }
+
+    // by column, the DEFAULT expressions
+    pub fn defaultExprs(&self) -> Result<Vec<Option<Expr>>,BTreeError>
+    {
+        self.Defaults.iter().map(|src| src.as_ref().map(|src| parseExpr(src)).transpose()).collect()
+    }
+
+    // a CHECK is boolean and a DEFAULT fits its column, checked when the table is created
+    pub fn checkExprTypes(&self) -> Result<(),BTreeError>
+    {
+        for (src,expr) in self.Checks.iter().zip(self.checkExprs()?)
+        {
+            match expr.sample(self) {
+                Ok(Value::BOOL(_)) | Ok(Value::NULL) => {},
+                _Other => return Err(BTreeError::BadExpression(src.clone())),
+            }
+        }
+
+        let defaults = self.defaultExprs()?;
+        for i in 0..defaults.len()
+        {
+            if let (Some(expr),Some(Some(src))) = (&defaults[i],self.Defaults.get(i))
+            {
+                let mut r = Record::new(self);
+                let v = expr.sample(self).map_err(|_| BTreeError::BadExpression(src.clone()))?;
+                if !v.isNull() && i < self.Cols.len()
+                {
+                    r.Set(&self.Cols[i], v).map_err(|_| BTreeError::BadExpression(src.clone()))?;
+                }
+            }
+        }
+        Ok(())
+    }
+
     pub fn FixIndexes(&mut self)
     {
         //Add Primary Key To Indexes
@@ -89,6 +213,12 @@ impl TableDef{
             return Ok(-1);
         }
 
+        self.findSecondaryIndex(keys)
+    }
+
+    // the shortest secondary index the keys are a prefix of
+    pub fn findSecondaryIndex(&self,keys:&Vec<&Vec<u8>>) -> Result<i16,BTreeError>
+    {
         let mut winner:i16 = -2;
         for i in 0..self.Indexes.len()
         {
@@ -182,7 +312,7 @@ mod tests {
             Cols : vec!["id".as_bytes().to_vec() , "name".as_bytes().to_vec(),"address".as_bytes().to_vec(),"age".as_bytes().to_vec(),"married".as_bytes().to_vec() ] ,
             PKeys : 1,
             Indexes : vec![vec!["address".as_bytes().to_vec() , "married".as_bytes().to_vec()],vec!["age".as_bytes().to_vec()]],
-            IndexPrefixes : vec![],
+            ..Default::default()
         };
 
         table.FixIndexes();
@@ -193,4 +323,31 @@ mod tests {
         println!("{}", t);
     }
 
+    #[test]
+    fn test_expr_types()
+    {
+        let table = |checks:Vec<&str>,defaults:Vec<Option<&str>>| TableDef{
+            Name: "account".as_bytes().to_vec(),
+            Types : vec!["INT64".into(), "BYTES".into(), "INT32".into(), "DATE".into()] ,
+            Cols : vec!["id".as_bytes().to_vec(), "owner".as_bytes().to_vec(), "balance".as_bytes().to_vec(), "opened".as_bytes().to_vec()] ,
+            PKeys : 0,
+            Checks : checks.iter().map(|c| c.to_string()).collect(),
+            Defaults : defaults.iter().map(|d| d.map(|d| d.to_string())).collect(),
+            ..Default::default()
+        };
+
+        assert!(table(vec!["balance >= 0 and owner is not null","10 / balance > 0","opened + interval '1 day' > opened"],vec![None,Some("'nobody'"),Some("10 * 10"),None]).checkExprTypes().is_ok());
+        assert_eq!(2,table(vec!["balance >= 0","balance < 10"],vec![]).checkExprs().unwrap().len());
+
+        let bad = |t:TableDef| matches!(t.checkExprTypes(),Err(BTreeError::BadExpression(_)));
+        assert!(bad(table(vec!["balance >"],vec![])));
+        assert!(bad(table(vec!["balance > 0 )"],vec![])));
+        assert!(bad(table(vec!["balance + 1"],vec![])));
+        assert!(bad(table(vec!["owner > 1"],vec![])));
+        assert!(bad(table(vec!["nothing > 1"],vec![])));
+        assert!(bad(table(vec![],vec![None,None,Some("'abc'")])));
+        assert!(bad(table(vec![],vec![None,None,Some("1 +")])));
+        assert!(bad(table(vec![],vec![None,None,None,Some("true")])));
+    }
+
 }
\ No newline at end of file
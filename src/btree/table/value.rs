@@ -2,7 +2,10 @@ use core::panic;
 use std::{cmp::Ordering, fmt, ops::{Add, Div, Mul, Rem, Sub}};
 use serde::{Serialize, Deserialize};
 
-use crate::btree::{scan::comp::OP_CMP, BTreeError};
+use crate::btree::{scan::comp::OP_CMP, util, BTreeError};
+
+use super::datetime::{self, DatePart, MICROS_PER_DAY};
+use super::decimal;
 
 pub enum ValueError{
     OperationNotSupported(String),
@@ -17,7 +20,14 @@ pub enum ValueType {
     INT16,
     INT8,
     ID,
-    BOOL
+    BOOL,
+    FLOAT64,
+    DATE,
+    TIMESTAMP,
+    INTERVAL,
+    DECIMAL(u8,u8),     // precision and scale
+    TEXT,               // BYTES that are valid UTF-8
+    BLOB,
 }
 
 impl From<&str> for ValueType {
@@ -30,6 +40,17 @@ impl From<&str> for ValueType {
             "INT8" => ValueType::INT8,
             "ID" => ValueType::ID,
             "BOOL" => ValueType::BOOL,
+            "FLOAT64" => ValueType::FLOAT64,
+            "DATE" => ValueType::DATE,
+            "TIMESTAMP" => ValueType::TIMESTAMP,
+            "INTERVAL" => ValueType::INTERVAL,
+            "TEXT" => ValueType::TEXT,
+            "BLOB" => ValueType::BLOB,
+            // DECIMAL(10,2)
+            _Other if item.starts_with("DECIMAL(") && item.ends_with(")") => {
+                let (p,s) = item["DECIMAL(".len()..item.len()-1].split_once(',').expect("Not Support!");
+                ValueType::DECIMAL(p.trim().parse().expect("Not Support!"),s.trim().parse().expect("Not Support!"))
+            },
             _Other => panic!("Not Support!"),
         }
     }
@@ -46,6 +67,13 @@ impl fmt::Display for ValueType {
             ValueType::INT64  => write!(f,"INT64"),
             ValueType::BYTES  => write!(f,"BYTES"),
             ValueType::ID  => write!(f,"ID"),
+            ValueType::FLOAT64  => write!(f,"FLOAT64"),
+            ValueType::DATE  => write!(f,"DATE"),
+            ValueType::TIMESTAMP  => write!(f,"TIMESTAMP"),
+            ValueType::INTERVAL  => write!(f,"INTERVAL"),
+            ValueType::DECIMAL(p,s)  => write!(f,"DECIMAL({},{})",p,s),
+            ValueType::TEXT  => write!(f,"TEXT"),
+            ValueType::BLOB  => write!(f,"BLOB"),
         }
     }
 }
@@ -59,6 +87,14 @@ pub enum Value{
     INT8(i8),
     BOOL(bool),
     ID(Vec<u8>),
+    FLOAT64(f64),
+    DATE(i32),              // days since 1970-01-01
+    TIMESTAMP(i64),         // microseconds since 1970-01-01 00:00:00 UTC
+    INTERVAL(i32,i32,i64),  // months, days and microseconds
+    DECIMAL(i128,u8),       // unscaled value and scale, 12.34 is (1234,2)
+    BLOB(Vec<u8>),
+    TEXT(Vec<u8>),          // valid UTF-8
+    NULL,   // SQL NULL, None is a column that is not set
     None,
 }
 
@@ -75,7 +111,227 @@ impl Value{
             Value::INT8(_) => ValueType::INT8,
             Value::BOOL(_) => ValueType::BOOL,
             Value::ID(_) => ValueType::ID,
-            Value::None => {panic!()},
+            Value::FLOAT64(_) => ValueType::FLOAT64,
+            Value::DATE(_) => ValueType::DATE,
+            Value::TIMESTAMP(_) => ValueType::TIMESTAMP,
+            Value::INTERVAL(_,_,_) => ValueType::INTERVAL,
+            Value::DECIMAL(_,s) => ValueType::DECIMAL(decimal::MAX_PRECISION,*s),
+            Value::BLOB(_) => ValueType::BLOB,
+            Value::TEXT(_) => ValueType::TEXT,
+            Value::NULL | Value::None => {panic!()},
+        }
+    }
+
+    // a value of the type that is not zero, for checking the types of an expression
+    pub fn sample(t:&ValueType)->Value
+    {
+        match t {
+            ValueType::INT64 => Value::INT64(1),
+            ValueType::INT32 => Value::INT32(1),
+            ValueType::INT16 => Value::INT16(1),
+            ValueType::INT8 => Value::INT8(1),
+            ValueType::BOOL => Value::BOOL(true),
+            ValueType::FLOAT64 => Value::FLOAT64(1.0),
+            ValueType::DATE => Value::DATE(0),
+            ValueType::TIMESTAMP => Value::TIMESTAMP(0),
+            ValueType::INTERVAL => Value::INTERVAL(0,1,0),
+            ValueType::DECIMAL(_,s) => Value::DECIMAL(decimal::pow10(*s).unwrap_or(1),*s),
+            ValueType::TEXT => Value::TEXT("a".as_bytes().to_vec()),
+            ValueType::BLOB => Value::BLOB(vec![1]),
+            ValueType::BYTES | ValueType::ID => Value::BYTES("a".as_bytes().to_vec()),
+        }
+    }
+
+    pub fn isNull(&self)->bool
+    {
+        *self == Value::NULL
+    }
+
+    // three-valued logic, NULL is unknown
+    pub fn And(&self,v:&Value)->Result<Value,BTreeError>
+    {
+        match (self,v) {
+            (Value::BOOL(false), Value::BOOL(_)|Value::NULL) | (Value::NULL, Value::BOOL(false)) => Ok(Value::BOOL(false)),
+            (Value::BOOL(true), Value::BOOL(v1)) => Ok(Value::BOOL(*v1)),
+            (Value::BOOL(true)|Value::NULL, Value::NULL) | (Value::NULL, Value::BOOL(true)) => Ok(Value::NULL),
+            _Other => Err(BTreeError::OperationNotSupported(String::from("And"))),
+        }
+    }
+
+    pub fn Or(&self,v:&Value)->Result<Value,BTreeError>
+    {
+        match (self,v) {
+            (Value::BOOL(true), Value::BOOL(_)|Value::NULL) | (Value::NULL, Value::BOOL(true)) => Ok(Value::BOOL(true)),
+            (Value::BOOL(false), Value::BOOL(v1)) => Ok(Value::BOOL(*v1)),
+            (Value::BOOL(false)|Value::NULL, Value::NULL) | (Value::NULL, Value::BOOL(false)) => Ok(Value::NULL),
+            _Other => Err(BTreeError::OperationNotSupported(String::from("Or"))),
+        }
+    }
+
+    pub fn toF64(&self)->Option<f64>
+    {
+        match  self {
+            Value::INT64(v) => Some(*v as f64),
+            Value::INT32(v) => Some(*v as f64),
+            Value::INT16(v) => Some(*v as f64),
+            Value::INT8(v) => Some(*v as f64),
+            Value::FLOAT64(v) => Some(*v),
+            Value::DECIMAL(v,s) => Some(*v as f64 / 10_f64.powi(*s as i32)),
+            _Other => None,
+        }
+    }
+
+    pub fn toI64(&self)->Option<i64>
+    {
+        match  self {
+            Value::INT64(v) => Some(*v),
+            Value::INT32(v) => Some(*v as i64),
+            Value::INT16(v) => Some(*v as i64),
+            Value::INT8(v) => Some(*v as i64),
+            _Other => None,
+        }
+    }
+
+    // an integer with a decimal is a decimal of scale 0
+    fn toDecimal(&self)->Option<(i128,u8)>
+    {
+        match  self {
+            Value::INT64(v) => Some((*v as i128,0)),
+            Value::INT32(v) => Some((*v as i128,0)),
+            Value::INT16(v) => Some((*v as i128,0)),
+            Value::INT8(v) => Some((*v as i128,0)),
+            Value::DECIMAL(v,s) => Some((*v,*s)),
+            _Other => None,
+        }
+    }
+
+    // rounds half away from zero to the scale, the digits must fit the precision
+    pub fn Round(&self,precision:u8,scale:u8)->Result<Value,BTreeError>
+    {
+        let overflow = || BTreeError::NumericOverflow(format!("DECIMAL({},{})",precision,scale));
+        let (v,s) = match self {
+            Value::FLOAT64(v) => decimal::fromF64(*v).ok_or_else(overflow)?,
+            _Other => self.toDecimal().ok_or(BTreeError::OperationNotSupported(String::from("Round")))?,
+        };
+        let v = decimal::rescale(v, s, scale).ok_or_else(overflow)?;
+        if decimal::digits(v) > precision
+        {
+            return Err(overflow());
+        }
+        Ok(Value::DECIMAL(v,scale))
+    }
+
+    // both operands at the larger scale, when one is a decimal and the other a decimal or an integer
+    fn decimalPair(&self,other:&Value)->Option<(i128,i128,u8)>
+    {
+        match (self,other) {
+            (Value::DECIMAL(_,_), _) | (_, Value::DECIMAL(_,_)) => {
+                let ((v,s),(v1,s1)) = (self.toDecimal()?,other.toDecimal()?);
+                let scale = s.max(s1);
+                Some((decimal::rescale(v, s, scale)?,decimal::rescale(v1, s1, scale)?,scale))
+            },
+            _Other => None,
+        }
+    }
+
+    // exact decimal arithmetic, None when neither side is a decimal
+    fn decimalOp(&self,other:&Value,op:&str)->Option<Result<Value,BTreeError>>
+    {
+        match (self,other) {
+            (Value::DECIMAL(_,_), _) | (_, Value::DECIMAL(_,_)) if self.toDecimal().is_some() && other.toDecimal().is_some() => {},
+            _Other => return None,
+        }
+        // a product has the digits of both fractions
+        if op == "Multiply"
+        {
+            let ((v,s),(v1,s1)) = (self.toDecimal()?,other.toDecimal()?);
+            let to = (s + s1).min(decimal::MAX_PRECISION);
+            let r = v.checked_mul(v1).and_then(|r| decimal::rescale(r, s + s1, to));
+            return Some(r.map(|r| Value::DECIMAL(r,to)).ok_or(BTreeError::NumericOverflow(op.to_string())));
+        }
+        let (v,v1,scale) = match self.decimalPair(other) {
+            Some(pair) => pair,
+            None => return Some(Err(BTreeError::NumericOverflow(op.to_string()))),
+        };
+        let r = match op {
+            "Add" => v.checked_add(v1).map(|r| (r,scale)),
+            "Subtract" => v.checked_sub(v1).map(|r| (r,scale)),
+            "Divide" | "Modulo" if v1 == 0 => return Some(Err(BTreeError::OperationNotSupported(op.to_string()))),
+            "Divide" => {
+                let to = (scale + decimal::DIV_EXTRA_SCALE).min(decimal::MAX_PRECISION);
+                decimal::pow10(to).and_then(|p| v.checked_mul(p)).and_then(|n| decimal::divRound(n, v1)).map(|r| (r,to))
+            },
+            "Modulo" => Some((v % v1,scale)),
+            _Other => return Some(Err(BTreeError::OperationNotSupported(op.to_string()))),
+        };
+        Some(r.map(|(r,s)| Value::DECIMAL(r,s)).ok_or(BTreeError::NumericOverflow(op.to_string())))
+    }
+
+    // a date is the midnight at its start
+    pub fn toTimestamp(&self)->Option<i64>
+    {
+        match  self {
+            Value::DATE(v) => Some(*v as i64 * MICROS_PER_DAY),
+            Value::TIMESTAMP(v) => Some(*v),
+            _Other => None,
+        }
+    }
+
+    pub fn Extract(&self,part:&DatePart)->Result<Value,BTreeError>
+    {
+        match self.toTimestamp() {
+            Some(ts) => Ok(Value::INT64(datetime::extract(ts, part))),
+            None => Err(BTreeError::OperationNotSupported(format!("{:?}",part))),
+        }
+    }
+
+    // date and time arithmetic, sign is -1 for subtracting. None when it's not a date operation
+    fn dateOp(&self,other:&Value,sign:i32)->Option<Result<Value,BTreeError>>
+    {
+        let op = if sign == 1 { "Add" } else { "Subtract" };
+        let r = match (self,other) {
+            (Value::DATE(d), Value::INTERVAL(m,days,micros)) if *micros == 0 =>
+                sign.checked_mul(*m).map(|m| datetime::addMonths(*d as i64, m))
+                    .and_then(|day| day.checked_add(sign as i64 * *days as i64))
+                    .and_then(|day| i32::try_from(day).ok())
+                    .map(|day| Value::DATE(day)),
+            (Value::DATE(_)|Value::TIMESTAMP(_), Value::INTERVAL(m,days,micros)) => {
+                let ts = self.toTimestamp()?;
+                sign.checked_mul(*m).map(|m| datetime::addMonths(ts.div_euclid(MICROS_PER_DAY), m))
+                    .and_then(|day| day.checked_add(sign as i64 * *days as i64))
+                    .and_then(|day| day.checked_mul(MICROS_PER_DAY))
+                    .and_then(|v| v.checked_add(ts.rem_euclid(MICROS_PER_DAY)))
+                    .and_then(|v| v.checked_add(sign as i64 * micros))
+                    .map(|v| Value::TIMESTAMP(v))
+            },
+            (Value::INTERVAL(_,_,_), Value::DATE(_)|Value::TIMESTAMP(_)) if sign == 1 => return other.dateOp(self, sign),
+            (Value::INTERVAL(m,d,u), Value::INTERVAL(m1,d1,u1)) =>
+                sign.checked_mul(*m1).and_then(|m1| m.checked_add(m1)).zip(
+                    sign.checked_mul(*d1).and_then(|d1| d.checked_add(d1))).zip(
+                    u.checked_add(sign as i64 * u1))
+                    .and_then(|((m,d),u)| datetime::normalizeInterval(m, d, u))
+                    .map(|(m,d,u)| Value::INTERVAL(m,d,u)),
+            (Value::DATE(d), Value::INT64(_)|Value::INT32(_)|Value::INT16(_)|Value::INT8(_)) =>
+                (sign as i64).checked_mul(other.toF64()? as i64).and_then(|n| n.checked_add(*d as i64))
+                    .and_then(|day| i32::try_from(day).ok())
+                    .map(|day| Value::DATE(day)),
+            (Value::DATE(d), Value::DATE(d1)) if sign == -1 => Some(Value::INT64(*d as i64 - *d1 as i64)),
+            // the whole days of the difference are in the day field
+            (Value::DATE(_)|Value::TIMESTAMP(_), Value::DATE(_)|Value::TIMESTAMP(_)) if sign == -1 =>
+                self.toTimestamp()?.checked_sub(other.toTimestamp()?)
+                    .and_then(|u| datetime::normalizeInterval(0, 0, u))
+                    .map(|(m,d,u)| Value::INTERVAL(m,d,u)),
+            _Other => return None,
+        };
+        Some(r.ok_or(BTreeError::NumericOverflow(op.to_string())))
+    }
+
+    // an integer with a float is promoted to float
+    fn floatPair(&self,other:&Value)->Option<(f64,f64)>
+    {
+        match (self,other) {
+            (Value::FLOAT64(_), _) | (_, Value::FLOAT64(_)) => Some((self.toF64()?,other.toF64()?)),
+            _Other => None,
         }
     }
 
@@ -96,7 +352,19 @@ impl fmt::Display for Value {
             Value::INT16 (val) => write!(f,"{}",*val),
             Value::INT32 (val) => write!(f,"{}",*val),
             Value::INT64 (val) => write!(f,"{}",*val),
-            Value::BYTES (val) => write!(f,"{}",String::from_utf8(val.to_vec()).unwrap()),
+            Value::FLOAT64 (val) => write!(f,"{}",*val),
+            Value::DATE (val) => write!(f,"{}",datetime::formatDate(*val)),
+            Value::TIMESTAMP (val) => write!(f,"{}",datetime::formatTimestamp(*val)),
+            Value::INTERVAL (m,d,u) => write!(f,"{}",datetime::formatInterval(*m,*d,*u)),
+            Value::DECIMAL (v,s) => write!(f,"{}",decimal::formatDecimal(*v,*s)),
+            Value::NULL => write!(f,"NULL"),
+            // bytes that aren't text are shown like a blob literal
+            Value::BYTES (val) => match std::str::from_utf8(val) {
+                Ok(s) => write!(f,"{}",s),
+                Err(_) => write!(f,"x'{}'",util::toHex(val)),
+            },
+            Value::BLOB (val) => write!(f,"x'{}'",util::toHex(val)),
+            Value::TEXT (val) => write!(f,"{}",String::from_utf8_lossy(val)),
             Value::ID (val) => write!(f,"{}",String::from_utf8(val.to_vec()).unwrap()),
             Value::None => write!(f,"None"),
         }
@@ -107,8 +375,26 @@ impl Add for Value{
     type Output = Result<Value,BTreeError>;
 
     fn add(self, other: Value) ->Result<Value,BTreeError> {
+        if self.isNull() || other.isNull()
+        {
+            return Ok(Value::NULL);
+        }
+        if let Some((v,v1)) = self.floatPair(&other)
+        {
+            return Ok(Value::FLOAT64(v + v1));
+        }
+        if let Some(v) = self.decimalOp(&other, "Add")
+        {
+            return v;
+        }
+        if let Some(v) = self.dateOp(&other, 1)
+        {
+            return v;
+        }
         match (self,other) {
             (Value::BYTES(v), Value::BYTES(v1)) => { let mut r = v.clone(); r.extend(&v1); Ok(Value::BYTES(r))},
+            (Value::BLOB(v), Value::BLOB(v1)) => { let mut r = v.clone(); r.extend(&v1); Ok(Value::BLOB(r))},
+            (Value::TEXT(v), Value::TEXT(v1)|Value::BYTES(v1)) | (Value::BYTES(v), Value::TEXT(v1)) => { let mut r = v.clone(); r.extend(&v1); Ok(Value::TEXT(r))},
             (Value::INT64(v), Value::INT64(v1)) => Ok(Value::INT64(v + v1)),
             (Value::INT64(v), Value::INT32(v1)) => Ok(Value::INT64(v + v1 as i64 )),
             (Value::INT64(v), Value::INT16(v1)) => Ok(Value::INT64(v + v1 as i64 )),
@@ -135,6 +421,22 @@ impl Sub for Value
     type Output = Result<Value,BTreeError>;
 
     fn sub(self, rhs: Self) -> Self::Output {
+        if self.isNull() || rhs.isNull()
+        {
+            return Ok(Value::NULL);
+        }
+        if let Some((v,v1)) = self.floatPair(&rhs)
+        {
+            return Ok(Value::FLOAT64(v - v1));
+        }
+        if let Some(v) = self.decimalOp(&rhs, "Subtract")
+        {
+            return v;
+        }
+        if let Some(v) = self.dateOp(&rhs, -1)
+        {
+            return v;
+        }
         match (self,rhs) {
             (Value::INT64(v), Value::INT64(v1)) => Ok(Value::INT64(v - v1)),
             (Value::INT64(v), Value::INT32(v1)) => Ok(Value::INT64(v - v1 as i64 )),
@@ -162,6 +464,18 @@ impl Mul for Value
     type Output = Result<Value,BTreeError>;
 
     fn mul(self, rhs: Self) -> Self::Output {
+        if self.isNull() || rhs.isNull()
+        {
+            return Ok(Value::NULL);
+        }
+        if let Some((v,v1)) = self.floatPair(&rhs)
+        {
+            return Ok(Value::FLOAT64(v * v1));
+        }
+        if let Some(v) = self.decimalOp(&rhs, "Multiply")
+        {
+            return v;
+        }
         match (self,rhs) {
             (Value::INT64(v), Value::INT64(v1)) => Ok(Value::INT64(v * v1)),
             (Value::INT64(v), Value::INT32(v1)) => Ok(Value::INT64(v * v1 as i64 )),
@@ -189,6 +503,18 @@ impl Div for Value
     type Output = Result<Value,BTreeError>;
 
     fn div(self, rhs: Self) -> Self::Output {
+        if self.isNull() || rhs.isNull()
+        {
+            return Ok(Value::NULL);
+        }
+        if let Some((v,v1)) = self.floatPair(&rhs)
+        {
+            return Ok(Value::FLOAT64(v / v1));
+        }
+        if let Some(v) = self.decimalOp(&rhs, "Divide")
+        {
+            return v;
+        }
         match (self,rhs) {
             (Value::INT64(v), Value::INT64(v1)) => Ok(Value::INT64(v / v1)),
             (Value::INT64(v), Value::INT32(v1)) => Ok(Value::INT64(v / v1 as i64 )),
@@ -216,6 +542,18 @@ impl Rem for Value
     type Output = Result<Value,BTreeError>;
 
     fn rem(self, rhs: Self) -> Self::Output {
+        if self.isNull() || rhs.isNull()
+        {
+            return Ok(Value::NULL);
+        }
+        if let Some((v,v1)) = self.floatPair(&rhs)
+        {
+            return Ok(Value::FLOAT64(v % v1));
+        }
+        if let Some(v) = self.decimalOp(&rhs, "Modulo")
+        {
+            return v;
+        }
         match (self,rhs) {
             (Value::INT64(v), Value::INT64(v1)) => Ok(Value::INT64(v % v1)),
             (Value::INT64(v), Value::INT32(v1)) => Ok(Value::INT64(v % v1 as i64 )),
@@ -240,6 +578,18 @@ impl Rem for Value
 
 impl PartialOrd for Value {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        if let Some((v,v1)) = self.floatPair(other)
+        {
+            return v.partial_cmp(&v1);
+        }
+        if let Some((v,v1,_)) = self.decimalPair(other)
+        {
+            return v.partial_cmp(&v1);
+        }
+        if let (Some(v),Some(v1)) = (self.toTimestamp(),other.toTimestamp())
+        {
+            return v.partial_cmp(&v1);
+        }
         match (self,other) {
             (Value::INT64(v), Value::INT64(v1)) => v.partial_cmp(&v1) ,
             (Value::INT64(v), Value::INT32(v1)) => v.partial_cmp(&(*v1 as i64)),
@@ -257,8 +607,14 @@ impl PartialOrd for Value {
             (Value::INT8(v), Value::INT32(v1)) => v.partial_cmp(&(*v1 as i8)),
             (Value::INT8(v), Value::INT16(v1)) =>  v.partial_cmp(&(*v1 as i8)),
             (Value::INT8(v), Value::INT8(v1)) =>  v.partial_cmp(&(*v1 as i8)),
-            (Value::BYTES(v), Value::BYTES(v1)) => v.partial_cmp(&v1) ,
+            // the byte strings compare bytewise, like their keys
+            (Value::BYTES(v)|Value::TEXT(v)|Value::BLOB(v), Value::BYTES(v1)|Value::TEXT(v1)|Value::BLOB(v1)) => v.partial_cmp(&v1) ,
             (Value::BOOL(v), Value::BOOL(v1)) =>  v.partial_cmp(&v1) ,
+            (Value::INTERVAL(m,d,u), Value::INTERVAL(m1,d1,u1)) => {
+                let v = datetime::normalizeInterval(*m, *d, *u).unwrap_or((*m,*d,*u));
+                let v1 = datetime::normalizeInterval(*m1, *d1, *u1).unwrap_or((*m1,*d1,*u1));
+                v.partial_cmp(&v1)
+            },
             _Other => None,
         }
     }
@@ -281,4 +637,115 @@ mod tests {
 
         println!("Format Result: {}|{}|{}|{}|{}|{}",v1,v2,v3,v4,v5,v6);
     }
+
+    #[test]
+    fn test_float_promotion()
+    {
+        assert_eq!(Value::FLOAT64(3.5),(Value::INT32(2) + Value::FLOAT64(1.5)).unwrap());
+        assert_eq!(Value::FLOAT64(-0.5),(Value::FLOAT64(1.5) - Value::INT64(2)).unwrap());
+        assert_eq!(Value::FLOAT64(0.25),(Value::INT8(1) / Value::FLOAT64(4.0)).unwrap());
+        assert_eq!(Value::INT64(0),(Value::INT64(1) / Value::INT64(4)).unwrap());
+
+        assert!(Value::FLOAT64(2.5) > Value::INT16(2));
+        assert!(Value::INT64(3) > Value::FLOAT64(2.5));
+        assert!(Value::FLOAT64(-1.0) < Value::FLOAT64(0.0));
+        assert_eq!(None,Value::FLOAT64(1.0).partial_cmp(&Value::BOOL(true)));
+    }
+
+    #[test]
+    fn test_date_arithmetic()
+    {
+        let d = Value::DATE(datetime::parseDate("2024-01-31").unwrap());
+        let month = Value::INTERVAL(1,0,0);
+        assert_eq!("2024-02-29",(d.clone() + month.clone()).unwrap().to_string());
+        assert_eq!("2023-12-31",(d.clone() - month.clone()).unwrap().to_string());
+        assert_eq!("2024-02-01",(d.clone() + Value::INT64(1)).unwrap().to_string());
+        assert_eq!("2024-01-31 02:00:00",(d.clone() + Value::INTERVAL(0,0,7200 * 1_000_000)).unwrap().to_string());
+
+        let ts = Value::TIMESTAMP(datetime::parseTimestamp("2024-03-01 10:00:00").unwrap());
+        assert_eq!("2024-02-29 09:30:00",(ts.clone() - Value::INTERVAL(0,1,1800 * 1_000_000)).unwrap().to_string());
+        assert_eq!(Value::INT64(30),(Value::DATE(30) - Value::DATE(0)).unwrap());
+        assert_eq!(Value::INTERVAL(0,0,36000 * 1_000_000),(ts.clone() - Value::DATE(datetime::parseDate("2024-03-01").unwrap())).unwrap());
+
+        assert!(ts > d);
+        assert!(Value::DATE(1) > Value::DATE(-1));
+        assert_eq!(Value::INT64(2024),ts.Extract(&DatePart::Year).unwrap());
+        assert_eq!(Value::INT64(31),d.Extract(&DatePart::Day).unwrap());
+        assert!(Value::INT64(1).Extract(&DatePart::Day).is_err());
+
+        //a difference of timestamps compares with the intervals of days
+        let later = Value::TIMESTAMP(datetime::parseTimestamp("2024-03-03 11:00:00").unwrap());
+        let diff = (later - ts.clone()).unwrap();
+        assert_eq!(Value::INTERVAL(0,2,3600 * 1_000_000),diff);
+        assert!(diff > Value::INTERVAL(0,2,0));
+        assert!(diff < Value::INTERVAL(0,1,48 * 3600 * 1_000_000));
+        assert_eq!(Some(Ordering::Equal),Value::INTERVAL(0,0,24 * 3600 * 1_000_000).partial_cmp(&Value::INTERVAL(0,1,0)));
+        assert!(Value::INTERVAL(1,0,0) > Value::INTERVAL(0,40,0));
+
+        //out of range results are errors
+        assert!(matches!(Value::DATE(i32::MAX) + Value::INTERVAL(0,1,0),Err(BTreeError::NumericOverflow(_))));
+        assert!(matches!(Value::DATE(0) - Value::INTERVAL(i32::MIN,0,0),Err(BTreeError::NumericOverflow(_))));
+        assert!(matches!(Value::TIMESTAMP(i64::MAX - 10) + Value::INTERVAL(0,0,100),Err(BTreeError::NumericOverflow(_))));
+        assert!(matches!(Value::INTERVAL(0,i32::MAX,0) + Value::INTERVAL(0,1,0),Err(BTreeError::NumericOverflow(_))));
+        assert!(matches!(Value::DATE(i32::MAX) + Value::INT64(1),Err(BTreeError::NumericOverflow(_))));
+    }
+
+    #[test]
+    fn test_decimal_arithmetic()
+    {
+        assert_eq!(Value::DECIMAL(3,1),(Value::DECIMAL(1,1) + Value::DECIMAL(2,1)).unwrap());
+        assert_eq!(Value::DECIMAL(1105,2),(Value::DECIMAL(1005,2) + Value::INT64(1)).unwrap());
+        assert_eq!(Value::DECIMAL(-95,2),(Value::INT8(1) - Value::DECIMAL(195,2)).unwrap());
+        assert_eq!("3.375",(Value::DECIMAL(15,1) * Value::DECIMAL(225,2)).unwrap().to_string());
+        assert_eq!("0.33333333",(Value::DECIMAL(100,2) / Value::INT32(3)).unwrap().to_string());
+        assert_eq!("-0.666667",(Value::DECIMAL(-2,0) / Value::INT32(3)).unwrap().to_string());
+        assert_eq!(Value::DECIMAL(5,1),(Value::DECIMAL(55,1) % Value::INT64(1)).unwrap());
+        assert!((Value::DECIMAL(1,0) / Value::DECIMAL(0,2)).is_err());
+        assert!((Value::DECIMAL(i128::MAX,0) + Value::INT64(1)).is_err());
+        assert_eq!(Value::FLOAT64(1.75),(Value::DECIMAL(125,2) + Value::FLOAT64(0.5)).unwrap());
+
+        assert_eq!(Value::DECIMAL(1235,2),Value::DECIMAL(12345,3).Round(10,2).unwrap());
+        assert_eq!(Value::DECIMAL(-1235,2),Value::DECIMAL(-12345,3).Round(10,2).unwrap());
+        assert_eq!(Value::DECIMAL(1000,2),Value::INT16(10).Round(4,2).unwrap());
+        assert!(Value::DECIMAL(12345,2).Round(4,2).is_err());
+
+        assert_eq!(Some(Ordering::Equal),Value::DECIMAL(15,1).partial_cmp(&Value::DECIMAL(150,2)));
+        assert!(Value::DECIMAL(150,2) > Value::INT64(1));
+        assert!(Value::DECIMAL(-1,3) < Value::DECIMAL(0,0));
+    }
+
+    #[test]
+    fn test_blob_display()
+    {
+        assert_eq!("x'00ff10'",Value::BLOB(vec![0,0xff,0x10]).to_string());
+        assert_eq!("x''",Value::BLOB(vec![]).to_string());
+        assert_eq!("héllo",Value::BYTES("héllo".as_bytes().to_vec()).to_string());
+        assert_eq!("x'c328'",Value::BYTES(vec![0xc3,0x28]).to_string());
+        assert_eq!(Value::BLOB(vec![1,2]),(Value::BLOB(vec![1]) + Value::BLOB(vec![2])).unwrap());
+        assert!(Value::BLOB(vec![1,2]) > Value::BLOB(vec![1]));
+        assert_eq!(Some(Ordering::Equal),Value::BLOB(vec![1]).partial_cmp(&Value::BYTES(vec![1])));
+        assert!(Value::BLOB(vec![2]) > Value::TEXT(vec![1,5]));
+        assert!(Value::TEXT("ab".as_bytes().to_vec()) < Value::BYTES("b".as_bytes().to_vec()));
+        assert_eq!("héllo",Value::TEXT("héllo".as_bytes().to_vec()).to_string());
+        assert_eq!(Value::TEXT("ab".as_bytes().to_vec()),(Value::TEXT("a".as_bytes().to_vec()) + Value::BYTES("b".as_bytes().to_vec())).unwrap());
+    }
+
+    #[test]
+    fn test_null_logic()
+    {
+        assert_eq!(Value::NULL,(Value::INT32(2) + Value::NULL).unwrap());
+        assert_eq!(None,Value::NULL.partial_cmp(&Value::NULL));
+
+        let t = Value::BOOL(true);
+        let f = Value::BOOL(false);
+        let n = Value::NULL;
+        assert_eq!(f,n.And(&f).unwrap());
+        assert_eq!(n,n.And(&t).unwrap());
+        assert_eq!(n,n.And(&n).unwrap());
+        assert_eq!(t,t.And(&t).unwrap());
+        assert_eq!(t,n.Or(&t).unwrap());
+        assert_eq!(n,f.Or(&n).unwrap());
+        assert_eq!(f,f.Or(&f).unwrap());
+        assert!(t.And(&Value::INT8(1)).is_err());
+    }
 }
\ No newline at end of file
@@ -0,0 +1,55 @@
+use std::{collections::HashMap, sync::RwLock};
+
+use crate::btree::{db::{TDEF_META, TDEF_TABLE}, table::table::TableDef};
+
+// table definitions shared by the transactions. each one is kept with the oldest version
+// it's known to exist at, older snapshots look it up in their own @table.
+// definitions created by a transaction are published when it commits.
+pub struct Catalog{
+    tables:RwLock<HashMap<Vec<u8>,(u64,TableDef)>>,
+}
+
+impl Catalog{
+    pub fn new()->Self
+    {
+        let mut tables = HashMap::new();
+        tables.insert("@meta".as_bytes().to_vec(),(0,TDEF_META.clone()));
+        tables.insert("@table".as_bytes().to_vec(),(0,TDEF_TABLE.clone()));
+        Catalog{
+            tables:RwLock::new(tables),
+        }
+    }
+
+    // the definition visible to a snapshot of the version
+    pub fn get(&self,name:&[u8],version:u64)->Option<TableDef>
+    {
+        match self.tables.read().unwrap().get(name)
+        {
+            Some((ver,def)) if *ver <= version => Some(def.clone()),
+            _ => None,
+        }
+    }
+
+    // a definition known to exist at the version, read from a snapshot or committed
+    pub fn insert(&self,name:&[u8],version:u64,def:&TableDef)
+    {
+        let mut tables = self.tables.write().unwrap();
+        match tables.get_mut(name)
+        {
+            Some(entry) => {
+                if entry.0 > version
+                {
+                    entry.0 = version;
+                }
+            },
+            None => {
+                tables.insert(name.to_vec(),(version,def.clone()));
+            }
+        }
+    }
+
+    pub fn contains(&self,name:&[u8])->bool
+    {
+        self.tables.read().unwrap().contains_key(name)
+    }
+}
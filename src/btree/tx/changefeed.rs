@@ -0,0 +1,32 @@
+use std::fmt;
+use crate::btree::table::value::Value;
+
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub enum ChangeOp{
+    Insert,
+    Update,
+    Delete,
+}
+
+// a row changed by a transaction
+#[derive(Clone,Debug)]
+pub struct RowChange{
+    pub Table:Vec<u8>,
+    pub Op:ChangeOp,
+    pub Key:Vec<Value>,         // primary key columns
+    pub Old:Option<Vec<Value>>, // none for insert
+    pub New:Option<Vec<Value>>, // none for delete
+}
+
+// the changes of a committed transaction, in the order they were made
+#[derive(Clone,Debug)]
+pub struct ChangeEvent{
+    pub Version:u64,
+    pub Changes:Vec<RowChange>,
+}
+
+impl fmt::Display for RowChange{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f,"{}:{:?} Key:{:?} Old:{:?} New:{:?}",String::from_utf8(self.Table.to_vec()).unwrap(),self.Op,self.Key,self.Old,self.New)
+    }
+}
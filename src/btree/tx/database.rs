@@ -1,21 +1,49 @@
-use std::{collections::HashMap, hash::Hash, sync::{Arc, MutexGuard, RwLock}};
+use std::{collections::HashMap, hash::Hash, sync::{mpsc::{self, Receiver, Sender}, Arc, MutexGuard, RwLock}, time::{Duration, Instant}};
 
 use scopeguard::defer;
+use serde::{Deserialize, Serialize};
 
-use crate::btree::{db::{TDEF_META, TDEF_TABLE}, kv::{node::BNode, ContextError}, table::table::TableDef, tx::txwriter::txwriter};
+use crate::btree::{btree::request::{DeleteRequest, InsertReqest}, db::{TDEF_META, TDEF_TABLE}, kv::{node::BNode, ContextError}, scan::comp::OP_CMP, table::{record::Record, table::TableDef, value::Value}, tx::txwriter::txwriter, MODE_INSERT_ONLY, MODE_UPSERT};
 
-use super::{dbcontext::{self, DbContext}, shared::Shared, txinterface::{MmapInterface, TxContent}};
+use super::{changefeed::ChangeEvent, dbcontext::{self, DbContext}, catalog::Catalog, readerslots::ReaderSlots, readers::{ReaderInfo, ReaderTable}, shared::Shared, txinterface::{DBTxInterface, MmapInterface, TxContent, TxInterface, TxReaderInterface}};
 
 pub struct Database{
     context:DbContext,
-    tables: Arc<RwLock<HashMap<Vec<u8>,TableDef>>>,
+    tables: Arc<Catalog>,
     pub writer:Shared<()>,
-    reader:Shared<()>,
-    readers: HashMap<usize,u64>,
+    readers: Arc<ReaderTable>,
+    freedPages: Vec<(u64,usize)>,   // pages freed by the commits newer than the oldest reader, by version
+    history: Vec<(u64,Vec<Vec<u8>>)>, // keys written by the latest commits, by version
+    subscribers: Vec<Sender<ChangeEvent>>,
+    pendingChanges: Vec<ChangeEvent>, // events of the commits not synced yet
+    snapshots: Arc<RwLock<HashMap<String,(u64,u64)>>>, // named snapshots, root and version
+    readonly: bool, // opened by a reader process, another process commits
+    loaded: bool, // the master page was loaded, it's written back on drop
+}
+
+// number of commits kept for validating optimistic transactions
+const MAX_COMMIT_HISTORY: usize = 1024;
+
+// number of keys moved by a commit of the migration of a legacy file, small in tests to cross batches
+const MIGRATE_BATCH: usize = if cfg!(test) { 4 } else { 1024 };
+
+// the @meta row of a migration in progress, it's removed when the migration is done
+const MIGRATE_KEY: &str = "migrate";
+
+// the tables of a legacy file left to migrate, the first one is being moved to the target
+#[derive(Serialize,Deserialize,Clone)]
+struct Migration{
+    Tables: Vec<Vec<u8>>,
+    Target: Option<TableDef>,
 }
 
 impl Drop for Database {
     fn drop(&mut self) {
+        // commits that were not synced yet
+        if self.readonly == false && self.loaded
+        {
+            _ = self.flush();
+        }
     }
 }
 
@@ -28,103 +56,544 @@ impl From<DbContext> for Database {
 
 impl Database{
     pub fn new(context:DbContext) -> Result<Self,ContextError> {
-        let tables = Arc::new(RwLock::new(HashMap::new()));
+        Self::build(context, false)
+    }
+
+    fn build(context:DbContext,readonly:bool) -> Result<Self,ContextError> {
+        let tables = Arc::new(Catalog::new());
+        let snapshots = Arc::new(RwLock::new(HashMap::new()));
+        let readers = Arc::new(ReaderTable::new(context.getMmap(),tables.clone(),snapshots.clone()));
         let mut context = Database {
             context: context,
             tables : tables,
             writer : Shared::new(()),
-            reader : Shared::new(()),
             //lock : None,
-            readers : HashMap::new(),
+            readers : readers,
+            freedPages : Vec::new(),
+            history : Vec::new(),
+            subscribers : Vec::new(),
+            pendingChanges : Vec::new(),
+            snapshots : snapshots,
+            readonly : readonly,
+            loaded : false,
         };
-        context.open();
+        context.open()?;
 
 
         Ok(context)
     }
 
-    fn getMinReadVersion(&self)->u64
+    fn validate(&self,tx:&txwriter)->Result<(),ContextError>
     {
-        let mut minversion:u64 = u64::MAX;
-        for value in self.readers.values() {
-            if minversion > *value
+        let version = tx.version();
+        if let Some((oldest,_)) = self.history.first()
+        {
+            if *oldest > version + 1
+            {
+                // the commits it should be checked against are gone
+                return Err(ContextError::TxConflict);
+            }
+        }
+
+        for (ver,keys) in &self.history
+        {
+            if *ver > version && tx.conflictsWith(keys)
             {
-                minversion = *value
+                return Err(ContextError::TxConflict);
             }
         }
-        minversion
+        Ok(())
     }
 
-}
+    // commit the transaction. without sync the pages are only copied into the mapping,
+    // they become durable with the next flush
+    pub fn commitEx(&mut self, tx:&mut txwriter, sync:bool)->Result<(),ContextError> {
+        if self.readonly
+        {
+            return Err(ContextError::ReadOnly);
+        }
+        // the snapshot it began on is not read anymore, whether it commits or not
+        let _pin = tx.context.pin.take();
+
+        if tx.version() != self.context.version
+        {
+            // others have committed since the transaction began,
+            // check them against what it has read and replay its writes on the latest root
+            self.validate(tx)?;
+
+            let minReader = self.getMinReadVersion();
+            let mut latest = txwriter::new(self.context.createTx(minReader)?,self.tables.clone());
+            latest.fillfactor = tx.fillfactor;
+            for (key,val) in &tx.writes
+            {
+                match val
+                {
+                    Some(v) => {
+                        let mut request = InsertReqest::new(key, v, MODE_UPSERT);
+                        latest.Set(&mut request);
+                    },
+                    None => {
+                        let mut request = DeleteRequest::new(key);
+                        latest.Delete(&mut request);
+                    }
+                }
+            }
+            self.writeTx(&mut latest, sync)?;
+            latest.publishTables(self.context.version);
+        }
+        else {
+            self.writeTx(tx, sync)?;
+        }
+        tx.publishTables(self.context.version);
+        self.publishChanges(tx);
+        if sync
+        {
+            self.deliverChanges();
+        }
 
-impl TxContent for Database
-{
-    fn open(&mut self)->Result<(),crate::btree::kv::ContextError> {
-        self.context.masterload();
-        self.tables.write().unwrap().insert("@meta".as_bytes().to_vec(),TDEF_META.clone());
-        self.tables.write().unwrap().insert("@table".as_bytes().to_vec(),TDEF_TABLE.clone());
         Ok(())
     }
     
-    fn begin(& mut self)->Result<txwriter,ContextError> {
-       
-        let tx =self.context.createTx().unwrap();
-        let lock = self.reader.lock();
-        if self.readers.len() > 0 
+    pub fn subscribe(&mut self)->Receiver<ChangeEvent>
+    {
+        let (sender,receiver) = mpsc::channel();
+        self.subscribers.push(sender);
+        receiver
+    }
+
+    // queue the row changes of a committed transaction, they are delivered once it's synced
+    fn publishChanges(&mut self,tx:&mut txwriter)
+    {
+        let changes = std::mem::take(&mut tx.changes);
+        if self.subscribers.len() == 0 || changes.len() == 0
         {
-            self.context.version = self.getMinReadVersion();
+            return;
         }
-        drop(lock);
-        let mut txwriter: txwriter = txwriter{
-            context:tx,
-            tables:self.tables.clone(),
-        };
-        Ok(txwriter)
+
+        self.pendingChanges.push(ChangeEvent{
+            Version:self.context.version,
+            Changes:changes,
+        });
+    }
+
+    // deliver the events of the synced commits, dropped receivers are removed
+    fn deliverChanges(&mut self)
+    {
+        for event in std::mem::take(&mut self.pendingChanges)
+        {
+            self.subscribers.retain(|s| s.send(event.clone()).is_ok());
+        }
+    }
+
+    // sync the pages written since the last flush, then the master page
+    pub fn flush(&mut self)->Result<(),ContextError>
+    {
+        self.context.syncPages()?;
+        self.context.SaveMaster()?;
+        self.readers.setSynced(self.context.version);
+        self.deliverChanges();
+        Ok(())
     }
-    
-    fn commmit(&mut self, tx:&mut super::txwriter::txwriter)->Result<(),ContextError> {
 
+    fn writeTx(&mut self,tx:&mut txwriter,sync:bool)->Result<(),ContextError>
+    {
+        let freed = tx.context.freelist.freed.len();
+        tx.context.UpdateFreeList()?;
         let nPages: usize = (tx.context.pageflushed + tx.context.nappend as u64) as usize;
-        self.context.writePages(&tx.context.freelist.updates,nPages);
+        if sync {
+            self.context.writePages(&tx.context.freelist.updates,nPages)?;
+        }
+        else {
+            self.context.applyPages(&tx.context.freelist.updates,nPages)?;
+        }
 
         self.context.nappend = tx.context.nappend;
         self.context.freehead = tx.context.freelist.data.head;
 
-        let lock = self.reader.lock();
         self.context.root = tx.context.root;
-        drop(lock);
+        self.context.version += 1;
+
+        if sync {
+            self.context.SaveMaster()?;
+            self.readers.setSynced(self.context.version);
+        }
+        else {
+            self.context.advance();
+        }
+        self.publish();
+
+        let minReader = self.getMinReadVersion();
+        self.freedPages.retain(|(ver,_)| *ver > minReader);
+        if freed > 0
+        {
+            self.freedPages.push((self.context.version,freed));
+        }
 
-        self.context.SaveMaster();
+        let keys = tx.writes.iter().map(|(k,_)| k.clone()).collect();
+        self.history.push((self.context.version,keys));
+        if self.history.len() > MAX_COMMIT_HISTORY
+        {
+            self.history.remove(0);
+        }
+        Ok(())
+    }
 
+    // the commits after the master page on disk are lost by a crash, but the free list
+    // nodes they changed in place may have been written. their items are dropped
+    fn recoverFreeList(&mut self)->Result<(),ContextError>
+    {
+        let mut tx = self.context.createTx(self.context.version)?;
+        if tx.trimFreeList(self.context.version)
+        {
+            self.context.writePages(&tx.freelist.updates, self.context.pageflushed as usize)?;
+            self.context.freehead = tx.freelist.data.head;
+            self.context.SaveMaster()?;
+        }
         Ok(())
     }
-    
-    fn abort(& mut self,tx:&super::txwriter::txwriter) {
 
+    // the oldest version still visible to a reader, a snapshot or the master page on disk
+    fn getMinReadVersion(&self)->u64
+    {
+        self.readers.minReadVersion()
     }
-    
-    fn beginread(&mut self)->Result<super::txreader::TxReader,ContextError> {
-        let lock = self.reader.lock();
-        defer! {
-            drop(lock);
+
+    fn publish(&self)
+    {
+        self.readers.publish(self.context.root,self.context.version,self.context.pageflushed,self.context.freehead,self.context.getContextSize());
+    }
+
+    // open the database of another process, it's never written
+    pub fn openReadOnly(context:DbContext) -> Result<Self,ContextError> {
+        Self::build(context, true)
+    }
+
+    pub fn isReadOnly(&self)->bool
+    {
+        self.readonly
+    }
+
+    // pin the versions read in this process in the shared slots, and honour the slots of the others
+    pub fn attachReaderSlots(&mut self,slots:Arc<ReaderSlots>)
+    {
+        self.readers.setSlots(slots);
+    }
+
+    // pick up the commits of the writer process, returns the latest version
+    pub fn refresh(&mut self)->Result<u64,ContextError>
+    {
+        self.context.reload()?;
+        self.publish();
+        Ok(self.context.version)
+    }
+
+    // readers are registered in it without locking the database
+    pub fn readerTable(&self)->Arc<ReaderTable>
+    {
+        self.readers.clone()
+    }
+
+    // readers older than the age are invalidated, their next read fails with ReaderExpired
+    pub fn setMaxReaderAge(&mut self,age:Option<Duration>)
+    {
+        self.readers.setMaxAge(age);
+    }
+
+    // the minimum fill factor of the pages, percent of a page used by the deletes of later transactions
+    pub fn setFillFactor(&mut self,fillfactor:usize)
+    {
+        self.readers.setFillFactor(fillfactor);
+    }
+
+    pub fn activeReaders(&self)->Vec<ReaderInfo>
+    {
+        self.readers.list()
+    }
+
+    // freed pages that can't be reused yet because a reader may still reach them
+    pub fn blockedPages(&self)->usize
+    {
+        let minReader = self.getMinReadVersion();
+        self.freedPages.iter().filter(|(ver,_)| *ver > minReader).map(|(_,count)| *count).sum()
+    }
+
+    pub fn beginreadAs(&mut self,owner:&str)->Result<super::txreader::TxReader,ContextError> {
+        self.readers.begin(owner)
+    }
+
+    // pin the current root and version under the name.
+    // snapshots are kept in memory only, they are gone once the database is closed
+    pub fn createSnapshot(&mut self,name:&str)->Result<(),ContextError>
+    {
+        let mut snapshots = self.snapshots.write().unwrap();
+        if snapshots.contains_key(name)
+        {
+            return Err(ContextError::SnapshotAlreadyExist);
         }
+        snapshots.insert(name.to_string(),(self.context.root,self.context.version));
+        Ok(())
+    }
 
-        let index = self.readers.len();
-        let reader = self.context.createReader(index,self.tables.clone());
-        if let Ok(r) = reader
+    // unpin the snapshot, its pages can be reclaimed once no reader uses them
+    pub fn releaseSnapshot(&mut self,name:&str)->Result<(),ContextError>
+    {
+        match self.snapshots.write().unwrap().remove(name)
         {
-            self.readers.insert(index,self.context.version);
-            return Ok(r);        
+            Some(_) => Ok(()),
+            None => Err(ContextError::SnapshotNotFound),
         }
-        else {
-            return Err(ContextError::CreateReaderError);
+    }
+
+    pub fn beginreadAt(&mut self,name:&str)->Result<super::txreader::TxReader,ContextError> {
+        self.readers.beginAt(name)
+    }
+
+    // rewrite the rows of a file with little-endian integer keys. every commit moves at most
+    // MIGRATE_BATCH keys and keeps the progress in @meta, an interrupted migration goes on
+    // when the file is opened again. the catalog is all bytes and stays as it is
+    fn migrate(&mut self)->Result<(),ContextError>
+    {
+        loop {
+            let mut tx = self.begin()?;
+            let done = Self::migrateStep(&mut tx)?;
+            self.commitEx(&mut tx, true)?;
+            if done
+            {
+                break;
+            }
+        }
+        self.context.legacy = false;
+        Ok(())
+    }
+
+    // a legacy file migrated only partly, the signature is already the new one
+    fn migrating(&mut self)->Result<bool,ContextError>
+    {
+        let mut tx = self.begin()?;
+        let ret = Self::migration(&tx);
+        self.abort(&mut tx);
+        Ok(ret?.is_some())
+    }
+
+    // the table being migrated gets new prefixes, so its rewritten keys never meet the old ones.
+    // its rows are moved there and its old index keys deleted, then its definition is switched over
+    fn migrateStep(tx:&mut txwriter)->Result<bool,ContextError>
+    {
+        let stored = Self::migration(tx)?;
+        let mut state = match &stored {
+            Some(state) => state.clone(),
+            None => Migration{ Tables:Self::tableNames(tx)?, Target:None },
+        };
+
+        let name = match state.Tables.first() {
+            Some(name) => name.clone(),
+            None => {
+                if stored.is_some()
+                {
+                    let mut rMeta = Record::new(&TDEF_META);
+                    rMeta.Set("key".as_bytes(), Value::BYTES(MIGRATE_KEY.as_bytes().to_vec())).map_err(|_| ContextError::LoadDataException)?;
+                    if tx.DeleteRecord(&rMeta).map_err(|_| ContextError::LoadDataException)? == false
+                    {
+                        return Err(ContextError::LoadDataException);
+                    }
+                }
+                return Ok(true);
+            }
+        };
+
+        let old = Self::storedTableDef(tx, &name)?;
+        match &state.Target {
+            None => state.Target = Some(Self::movedTableDef(tx, &old)?),
+            Some(target) => {
+                if Self::moveKeys(tx, &old, target)? == 0
+                {
+                    let mut rtable = Record::new(&TDEF_TABLE);
+                    rtable.Set("name".as_bytes(), Value::BYTES(name.clone())).map_err(|_| ContextError::LoadDataException)?;
+                    rtable.Set("def".as_bytes(), Value::BYTES(target.Marshal().as_bytes().to_vec())).map_err(|_| ContextError::LoadDataException)?;
+                    tx.UpdateRecord(&mut rtable, MODE_UPSERT).map_err(|_| ContextError::LoadDataException)?;
+                    state.Tables.remove(0);
+                    state.Target = None;
+                }
+            }
+        }
+
+        let mut rMeta = Record::new(&TDEF_META);
+        let val = serde_json::to_vec(&state).map_err(|_| ContextError::LoadDataException)?;
+        rMeta.Set("key".as_bytes(), Value::BYTES(MIGRATE_KEY.as_bytes().to_vec())).map_err(|_| ContextError::LoadDataException)?;
+        rMeta.Set("val".as_bytes(), Value::BYTES(val)).map_err(|_| ContextError::LoadDataException)?;
+        tx.UpdateRecord(&mut rMeta, MODE_UPSERT).map_err(|_| ContextError::LoadDataException)?;
+        Ok(false)
+    }
+
+    fn migration(tx:&txwriter)->Result<Option<Migration>,ContextError>
+    {
+        let mut rMeta = Record::new(&TDEF_META);
+        rMeta.Set("key".as_bytes(), Value::BYTES(MIGRATE_KEY.as_bytes().to_vec())).map_err(|_| ContextError::LoadDataException)?;
+        if let Ok(true) = tx.dbGet(&mut rMeta)
+        {
+            if let Some(Value::BYTES(val)) = rMeta.Get("val".as_bytes())
+            {
+                return serde_json::from_slice(&val).map(Some).map_err(|_| ContextError::LoadDataException);
+            }
+        }
+        Ok(None)
+    }
+
+    fn tableNames(tx:&txwriter)->Result<Vec<Vec<u8>>,ContextError>
+    {
+        let mut names = Vec::new();
+        let mut iter = tx.Seek(&TDEF_TABLE.Prefix.to_le_bytes(), OP_CMP::CMP_GE);
+        while iter.Valid()
+        {
+            let (k,v) = iter.Deref();
+            if k.len() < 4 || u32::from_le_bytes(k[0..4].try_into().unwrap()) != TDEF_TABLE.Prefix
+            {
+                break;
+            }
+            let mut rec = Record::new(&TDEF_TABLE);
+            rec.deencodeKey(k);
+            if let Some(Value::BYTES(name)) = rec.Get("name".as_bytes())
+            {
+                names.push(name);
+            }
+            iter.Next();
+        }
+        Ok(names)
+    }
+
+    fn storedTableDef(tx:&txwriter,name:&[u8])->Result<TableDef,ContextError>
+    {
+        let mut rtable = Record::new(&TDEF_TABLE);
+        rtable.Set("name".as_bytes(), Value::BYTES(name.to_vec())).map_err(|_| ContextError::LoadDataException)?;
+        if let Ok(true) = tx.dbGet(&mut rtable)
+        {
+            if let Some(Value::BYTES(def)) = rtable.Get("def".as_bytes())
+            {
+                return serde_json::from_slice(&def).map_err(|_| ContextError::LoadDataException);
+            }
+        }
+        Err(ContextError::LoadDataException)
+    }
+
+    // the definition with prefixes taken from next_prefix, like a new table
+    fn movedTableDef(tx:&mut txwriter,old:&TableDef)->Result<TableDef,ContextError>
+    {
+        let mut rMeta = Record::new(&TDEF_META);
+        rMeta.Set("key".as_bytes(), Value::BYTES("next_prefix".as_bytes().to_vec())).map_err(|_| ContextError::LoadDataException)?;
+        let mut prefix = crate::btree::TABLE_PREFIX_MIN;
+        if let Ok(true) = tx.dbGet(&mut rMeta)
+        {
+            if let Some(Value::BYTES(v)) = rMeta.Get("val".as_bytes())
+            {
+                prefix = u32::from_le_bytes(v.try_into().map_err(|_| ContextError::LoadDataException)?);
+            }
+        }
+
+        let mut target = old.clone();
+        target.Prefix = prefix + 1;
+        target.IndexPrefixes = (0..old.Indexes.len() as u32).map(|i| target.Prefix + i + 1).collect();
+        let next = target.Prefix + old.Indexes.len() as u32 + 1;
+        rMeta.Set("val".as_bytes(), Value::BYTES(next.to_le_bytes().to_vec())).map_err(|_| ContextError::LoadDataException)?;
+        tx.UpdateRecord(&mut rMeta, MODE_UPSERT).map_err(|_| ContextError::LoadDataException)?;
+        Ok(target)
+    }
+
+    // move a batch of rows to the new prefixes, which rebuilds their index keys, and delete
+    // the old index keys once the rows are gone. returns the number of old keys deleted
+    fn moveKeys(tx:&mut txwriter,old:&TableDef,target:&TableDef)->Result<usize,ContextError>
+    {
+        let mut batch: Vec<(bool,Vec<u8>,Vec<u8>)> = Vec::with_capacity(MIGRATE_BATCH);
+        for prefix in std::iter::once(old.Prefix).chain(old.IndexPrefixes.iter().cloned())
+        {
+            let prefix = prefix.to_le_bytes();
+            let mut iter = tx.Seek(&prefix, OP_CMP::CMP_GE);
+            while iter.Valid() && batch.len() < MIGRATE_BATCH
+            {
+                let (k,v) = iter.Deref();
+                if k.len() < 4 || k[0..4] != prefix
+                {
+                    break;
+                }
+                batch.push((prefix == old.Prefix.to_le_bytes(),k.to_vec(),v.to_vec()));
+                iter.Next();
+            }
+        }
+
+        for (row,k,v) in &batch
+        {
+            if tx.Delete(&mut DeleteRequest::new(k)) == false
+            {
+                return Err(ContextError::LoadDataException);
+            }
+            if *row
+            {
+                let mut rec = Record::new(target);
+                rec.decodeLegacy(k, v);
+                tx.UpdateRecord(&mut rec, MODE_INSERT_ONLY).map_err(|_| ContextError::LoadDataException)?;
+            }
+        }
+        Ok(batch.len())
+    }
+
+}
+
+impl TxContent for Database
+{
+    fn open(&mut self)->Result<(),crate::btree::kv::ContextError> {
+        if self.readonly
+        {
+            // the writer process creates and migrates the file, a reader never writes it
+            if self.context.getContextSize() == 0
+            {
+                return Err(ContextError::NotDataBaseFile);
+            }
+            self.context.masterload()?;
+            self.publish();
+            if self.context.legacy || self.migrating()?
+            {
+                return Err(ContextError::ReadOnly);
+            }
+            return Ok(());
         }
+
+        self.context.masterload()?;
+        self.loaded = true;
+        self.recoverFreeList()?;
+        self.readers.setSynced(self.context.version);
+        self.publish();
+        if self.context.legacy || self.migrating()?
+        {
+            self.migrate()?;
+        }
+        Ok(())
+    }
+    
+    fn begin(& mut self)->Result<txwriter,ContextError> {
+       
+        let tx = self.readers.createTx();
+        let mut txwriter: txwriter = txwriter::new(tx,self.tables.clone());
+        txwriter.fillfactor = self.readers.fillFactor();
+        Ok(txwriter)
+    }
+    
+    fn commmit(&mut self, tx:&mut super::txwriter::txwriter)->Result<(),ContextError> {
+        self.commitEx(tx, true)
+    }
+    
+    fn abort(& mut self,tx:&mut super::txwriter::txwriter) {
+        // nothing is written before commit, just throw the pending pages away
+        tx.rollback();
+        // the file may have grown since the transaction began
+        tx.context.reset(self.context.root, self.context.pageflushed, self.context.getContextSize(), self.context.freehead);
+        tx.context.pin = None;
+    }
+    
+    fn beginread(&mut self)->Result<super::txreader::TxReader,ContextError> {
+        self.beginreadAs("")
     }
     
     fn endread(&mut self, reader:& super::txreader::TxReader) {
-        let lock = self.reader.lock();
-        self.readers.remove(&reader.index);
-        drop(lock);
+        self.readers.end(reader);
     }
 }
 
@@ -133,12 +602,12 @@ impl TxContent for Database
 #[cfg(test)]
 mod tests {
 
-    use std::{fmt::Write, sync::{Arc, Mutex, RwLock}, thread, time::Duration};
+    use std::{fmt::Write, sync::{Arc, Mutex, RwLock}, thread, time::{Duration, Instant}};
     use rand::Rng;
 
-    use crate::btree::{db::{TDEF_META, TDEF_TABLE}, scan::comp::OP_CMP, table::{record::Record, table::TableDef, value::{Value, ValueType}}, tx::{dbinstance::DBInstance, memoryContext::memoryContext, shared::Shared, txinterface::{DBReadInterface, DBTxInterface, TxReadContext}, txwriter::txwriter, winmmap::Mmap}, BTREE_PAGE_SIZE, MODE_UPSERT};
+    use crate::btree::{db::{TDEF_META, TDEF_TABLE}, scan::comp::OP_CMP, table::{record::Record, table::TableDef, value::{Value, ValueType}}, tx::{dbinstance::{DBInstance, ReadGuard}, memoryContext::memoryContext, shared::Shared, txinterface::{DBReadInterface, DBTxInterface, TxReadContext, TxReaderInterface}, txwriter::txwriter, winmmap::Mmap, groupcommit::Durability, changefeed::ChangeOp}, BTREE_PAGE_SIZE, MODE_UPSERT};
     use super::*;
-    use crate::btree::{btree::request::{DeleteRequest, InsertReqest}, db::{scanner::Scanner, INDEX_ADD, INDEX_DEL}};
+    use crate::btree::{btree::request::{DeleteRequest, InsertReqest}, db::{scanner::Scanner, INDEX_ADD, INDEX_DEL}, kv::nodeinterface::{BNodeFreeListInterface, BNodeReadInterface}, BTreeError};
 
     #[test]
     fn test_memorycontext()
@@ -156,7 +625,7 @@ mod tests {
             Cols : vec!["id".as_bytes().to_vec() , "name".as_bytes().to_vec(),"address".as_bytes().to_vec(),"age".as_bytes().to_vec(),"married".as_bytes().to_vec() ] ,
             PKeys : 0,
             Indexes : vec![vec!["address".as_bytes().to_vec() , "married".as_bytes().to_vec()],vec!["name".as_bytes().to_vec()]],
-            IndexPrefixes : vec![],
+            ..Default::default()
         };
 
         let ret = tx.AddTable(&mut table);
@@ -270,6 +739,1290 @@ mod tests {
 
     }
 
+    const PERSON:&str = "create table person ( id vchar, name vchar, primary key (id), index (name), );";
+
+    //an empty database in memory for the end-to-end tests
+    fn testdb()->(Arc<RwLock<memoryContext>>,DBInstance)
+    {
+        let mctx = Arc::new(RwLock::new(memoryContext::new(BTREE_PAGE_SIZE,1000)));
+        let db = DBInstance::new(Database::new(DbContext::new(mctx.clone())).unwrap());
+        (mctx,db)
+    }
+
+    //runs the statements in a transaction of their own and commits it
+    fn execute(db:&DBInstance,sql:&str)
+    {
+        let mut tx = db.beginTx().unwrap();
+        tx.ExecuteSQLStatments(sql.to_string()).unwrap();
+        db.commitTx(&mut tx).unwrap();
+    }
+
+    //a statement that isn't a query, ExecuteSQLStatments doesn't return its error
+    fn exec(tx:&mut txwriter,sql:&str)->Result<usize,BTreeError>
+    {
+        use crate::btree::parser::{lib::Parser, statement::ExprSQLList};
+        let list = ExprSQLList().parse(sql).unwrap().1;
+        tx.ExecuteNoQuery(&list[0])
+    }
+
+    #[test]
+    fn test_abort()
+    {
+        let (_,db) = testdb();
+
+        execute(&db,PERSON);
+
+        //abort
+        let mut tx = db.beginTx().unwrap();
+        tx.ExecuteSQLStatments("insert into person ( id, name ) values ('1','Bob1');".to_string()).unwrap();
+        tx.ExecuteSQLStatments(PERSON.replace("person", "book")).unwrap();
+        assert_eq!(true,tx.getTableDef("book".as_bytes()).is_some());
+        db.abortTx(&mut tx);
+        assert_eq!(false,db.lock().unwrap().tables.contains("book".as_bytes()));
+
+        //drop without commit
+        let mut tx = db.beginTx().unwrap();
+        tx.ExecuteSQLStatments("insert into person ( id, name ) values ('2','Bob2');".to_string()).unwrap();
+        tx.ExecuteSQLStatments(PERSON.replace("person", "book")).unwrap();
+        drop(tx);
+        assert_eq!(false,db.lock().unwrap().tables.contains("book".as_bytes()));
+
+        //aborted after another commit grew the file
+        let mut tx = db.beginTx().unwrap();
+        let mut other = db.beginTx().unwrap();
+        for i in 10..60 {
+            other.ExecuteSQLStatments(format!("insert into person ( id, name ) values ('{}','{}');",i,"Bob".repeat(40))).unwrap();
+        }
+        db.commitTx(&mut other).unwrap();
+        db.abortTx(&mut tx);
+
+        let mut tx = db.beginTx().unwrap();
+        assert_eq!(true,tx.getTableDef("book".as_bytes()).is_none());
+        let tdef = tx.getTableDef("person".as_bytes()).unwrap();
+        for id in ["1","2"] {
+            let mut r = Record::new(&tdef);
+            r.Set("id".as_bytes(), Value::BYTES(id.as_bytes().to_vec()));
+            assert_eq!(false,tx.dbGet(&mut r).unwrap());
+        }
+        db.commitTx(&mut tx).unwrap();
+    }
+
+    #[test]
+    fn test_closure_tx()
+    {
+        let (_,db) = testdb();
+
+        let ret = db.write_tx(|tx| {
+            tx.ExecuteSQLStatments(PERSON.to_string())?;
+            tx.ExecuteSQLStatments("insert into person ( id, name ) values ('1','Bob1');".to_string())
+        });
+        assert_eq!(true,ret.is_ok());
+
+        //aborted on error
+        let ret:Result<(),BTreeError> = db.write_tx(|tx| {
+            tx.ExecuteSQLStatments("insert into person ( id, name ) values ('2','Bob2');".to_string())?;
+            Err(BTreeError::RecordNotFound)
+        });
+        assert_eq!(true,ret.is_err());
+
+        //aborted on panic
+        let db1 = db.clone();
+        let ret = std::panic::catch_unwind(move || {
+            let _:Result<(),BTreeError> = db1.write_tx(|tx| {
+                tx.ExecuteSQLStatments("insert into person ( id, name ) values ('3','Bob3');".to_string())?;
+                panic!("write failed");
+            });
+        });
+        assert_eq!(true,ret.is_err());
+
+        for i in 1..4 {
+            let count = db.read_tx(|reader| {
+                let list = reader.ExecuteSQLStatments(format!("select id,name from person index by id = '{}';",i)).unwrap();
+                list[0].Rows.len()
+            }).unwrap();
+            assert_eq!(if i == 1 {1} else {0},count);
+        }
+        assert_eq!(0,db.active_readers().len());
+    }
+
+    #[test]
+    fn test_optimistic_tx()
+    {
+        let (_,db) = testdb();
+
+        execute(&db,PERSON);
+        execute(&db,"insert into person ( id, name ) values ('1','Bob1');");
+
+        //disjoint writes, both are committed
+        let mut tx1 = db.beginTx().unwrap();
+        let mut tx2 = db.beginTx().unwrap();
+        tx1.ExecuteSQLStatments("insert into person ( id, name ) values ('2','Bob2');".to_string()).unwrap();
+        tx2.ExecuteSQLStatments("insert into person ( id, name ) values ('3','Bob3');".to_string()).unwrap();
+        db.commitTx(&mut tx1).unwrap();
+        db.commitTx(&mut tx2).unwrap();
+
+        //both update the same row, the later one has to retry
+        let mut tx1 = db.beginTx().unwrap();
+        let mut tx2 = db.beginTx().unwrap();
+        tx1.ExecuteSQLStatments("update person set name = 'Alice' index by id = '1';".to_string()).unwrap();
+        tx2.ExecuteSQLStatments("update person set name = 'Tom' index by id = '1';".to_string()).unwrap();
+        db.commitTx(&mut tx1).unwrap();
+        let ret = db.commitTx(&mut tx2);
+        assert_eq!(true,matches!(ret,Err(ContextError::TxConflict)));
+        db.abortTx(&mut tx2);
+
+        let mut tx = db.beginTx().unwrap();
+        let tdef = tx.getTableDef("person".as_bytes()).unwrap();
+        for (id,name) in [("1","Alice"),("2","Bob2"),("3","Bob3")] {
+            let mut r = Record::new(&tdef);
+            r.Set("id".as_bytes(), Value::BYTES(id.as_bytes().to_vec()));
+            assert_eq!(true,tx.dbGet(&mut r).unwrap());
+            assert_eq!(Some(Value::BYTES(name.as_bytes().to_vec())),r.Get("name".as_bytes()));
+        }
+        db.commitTx(&mut tx).unwrap();
+    }
+
+    #[test]
+    fn test_writer_pins_version()
+    {
+        let (_,db) = testdb();
+
+        execute(&db,PERSON);
+        execute(&db,"insert into person ( id, name ) values ('1','Bob1');");
+
+        //the pages replaced by a later commit are still reachable from the open writer
+        let mut tx1 = db.beginTx().unwrap();
+        let mut tx2 = db.beginTx().unwrap();
+        tx2.ExecuteSQLStatments("update person set name = 'Tom' index by id = '1';".to_string()).unwrap();
+        db.commitTx(&mut tx2).unwrap();
+        assert_eq!(tx1.version(),db.lock().unwrap().getMinReadVersion());
+        assert_eq!(true,db.blocked_pages() > 0);
+
+        db.abortTx(&mut tx1);
+        let current = db.lock().unwrap().context.version;
+        assert_eq!(current,db.lock().unwrap().getMinReadVersion());
+        assert_eq!(0,db.blocked_pages());
+
+        //unpinned by the commit, even a failed one, and by dropping the transaction
+        let mut tx1 = db.beginTx().unwrap();
+        let mut tx2 = db.beginTx().unwrap();
+        tx1.ExecuteSQLStatments("update person set name = 'Alice' index by id = '1';".to_string()).unwrap();
+        tx2.ExecuteSQLStatments("update person set name = 'Bob' index by id = '1';".to_string()).unwrap();
+        let tx3 = db.beginTx().unwrap();
+        db.commitTx(&mut tx1).unwrap();
+        assert_eq!(true,matches!(db.commitTx(&mut tx2),Err(ContextError::TxConflict)));
+        assert_eq!(1,db.lock().unwrap().readers.writers());
+        drop(tx3);
+        assert_eq!(0,db.lock().unwrap().readers.writers());
+        let current = db.lock().unwrap().context.version;
+        assert_eq!(current,db.lock().unwrap().getMinReadVersion());
+    }
+
+    #[test]
+    fn test_group_commit()
+    {
+        let (mctx,db) = testdb();
+
+        execute(&db,PERSON);
+
+        db.setCommitWindow(Duration::from_millis(50));
+        let syncs = mctx.read().unwrap().syncCount;
+
+        let mut handles = vec![];
+        for i in 0..8 {
+            let instance =  db.clone();
+            let handle = thread::spawn(move || {
+                let mut writer = instance.getLocker();
+                let lock = writer.lock().unwrap();
+                let mut tx = instance.beginTx().unwrap();
+                tx.ExecuteSQLStatments(format!("insert into person ( id, name ) values ('{}','Bob{}');",i,i)).unwrap();
+                drop(lock);
+                instance.commitTx(&mut tx).unwrap();
+            });
+            handles.push(handle);
+        }
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        //two syncs for each flush
+        let flushes = (mctx.read().unwrap().syncCount - syncs) / 2;
+        assert_eq!(true,flushes >= 1 && flushes < 8);
+
+        let mut tx = db.beginTx().unwrap();
+        let tdef = tx.getTableDef("person".as_bytes()).unwrap();
+        for i in 0..8 {
+            let mut r = Record::new(&tdef);
+            r.Set("id".as_bytes(), Value::BYTES(format!("{}",i).as_bytes().to_vec()));
+            assert_eq!(true,tx.dbGet(&mut r).unwrap());
+        }
+        db.commitTx(&mut tx).unwrap();
+    }
+
+    #[test]
+    fn test_fillfactor()
+    {
+        let (_,db) = testdb();
+        let mut tx = db.beginTx().unwrap();
+        assert_eq!(crate::btree::BTREE_MIN_FILL_FACTOR,tx.fillfactor);
+        db.abortTx(&mut tx);
+
+        //the transactions begun afterwards use it
+        db.set_fillfactor(40);
+        let mut tx = db.beginTx().unwrap();
+        assert_eq!(40,tx.fillfactor);
+        db.abortTx(&mut tx);
+    }
+
+    #[test]
+    fn test_durability()
+    {
+        let (mctx,db) = testdb();
+
+        execute(&db,PERSON);
+        let syncs = mctx.read().unwrap().syncCount;
+        //the background flush runs on its own thread, it's given until a deadline
+        let waitSyncs = |count:usize| {
+            let deadline = Instant::now() + Duration::from_secs(10);
+            while mctx.read().unwrap().syncCount < count && Instant::now() < deadline
+            {
+                thread::sleep(Duration::from_millis(10));
+            }
+            mctx.read().unwrap().syncCount
+        };
+
+        //none, left to the checkpoint
+        let mut tx = db.beginTxWith(Durability::None).unwrap();
+        tx.ExecuteSQLStatments("insert into person ( id, name ) values ('1','Bob1');".to_string()).unwrap();
+        db.commitTx(&mut tx).unwrap();
+        assert_eq!(syncs,mctx.read().unwrap().syncCount);
+
+        //normal, synced when the interval has passed
+        db.setSyncInterval(Duration::from_secs(3600));
+        let mut tx = db.beginTx().unwrap();
+        tx.ExecuteSQLStatments("insert into person ( id, name ) values ('2','Bob2');".to_string()).unwrap();
+        db.commitTxWith(&mut tx,Durability::Normal).unwrap();
+        assert_eq!(syncs,mctx.read().unwrap().syncCount);
+
+        //the background flush follows the shorter interval
+        db.setSyncInterval(Duration::ZERO);
+        assert_eq!(syncs + 2,waitSyncs(syncs + 2));
+
+        let mut tx = db.beginTxWith(Durability::Normal).unwrap();
+        tx.ExecuteSQLStatments("insert into person ( id, name ) values ('3','Bob3');".to_string()).unwrap();
+        db.commitTx(&mut tx).unwrap();
+        assert_eq!(syncs + 4,mctx.read().unwrap().syncCount);
+
+        let mut tx = db.beginTxWith(Durability::None).unwrap();
+        tx.ExecuteSQLStatments("insert into person ( id, name ) values ('4','Bob4');".to_string()).unwrap();
+        db.commitTx(&mut tx).unwrap();
+        db.checkpoint().unwrap();
+        assert_eq!(syncs + 6,mctx.read().unwrap().syncCount);
+
+        let mut reader = db.beginRead().unwrap();
+        for i in 1..5 {
+            let list = reader.ExecuteSQLStatments(format!("select id,name from person index by id = '{}';",i)).unwrap();
+            assert_eq!(1,list[0].Rows.len());
+        }
+        db.endRead(&mut reader);
+
+        //normal commits left unsynced are flushed in the background
+        db.setSyncInterval(Duration::from_secs(3600));
+        let mut tx = db.beginTxWith(Durability::Normal).unwrap();
+        tx.ExecuteSQLStatments("insert into person ( id, name ) values ('5','Bob5');".to_string()).unwrap();
+        db.commitTx(&mut tx).unwrap();
+        assert_eq!(syncs + 6,mctx.read().unwrap().syncCount);
+        db.setSyncInterval(Duration::from_millis(200));
+        assert_eq!(syncs + 8,waitSyncs(syncs + 8));
+    }
+
+    #[test]
+    fn test_durability_crash()
+    {
+        let (mctx,db) = testdb();
+        db.setSyncInterval(Duration::from_secs(3600));
+
+        let mut tx = db.beginTx().unwrap();
+        tx.ExecuteSQLStatments(PERSON.to_string()).unwrap();
+        tx.ExecuteSQLStatments("insert into person ( id, name ) values ('1','Bob1');".to_string()).unwrap();
+        db.commitTx(&mut tx).unwrap();
+
+        let commit = |id:usize,durability:Durability| {
+            let mut tx = db.beginTx().unwrap();
+            tx.ExecuteSQLStatments(format!("insert into person ( id, name ) values ('{}','Bob{}');",id,id)).unwrap();
+            tx.ExecuteSQLStatments(format!("update person set name = 'Tom{}' index by id = '1';",id)).unwrap();
+            db.commitTxWith(&mut tx,durability).unwrap();
+        };
+        //the state on disk after a crash, it opens at the last synced master
+        let crash = || {
+            let image = Arc::new(RwLock::new(mctx.read().unwrap().copy()));
+            let other = DBInstance::new(Database::new(DbContext::new(image)).unwrap());
+            let mut reader = other.beginRead().unwrap();
+            let list = reader.ExecuteSQLStatments("select id,name from person index by id >= '1';".to_string()).unwrap();
+            other.endRead(&mut reader);
+            list[0].Rows.iter().map(|r| r.Vals[1].to_string()).collect::<Vec<String>>()
+        };
+
+        commit(2,Durability::None);
+        commit(3,Durability::Normal);
+        assert_eq!(vec!["Bob1".to_string()],crash());
+
+        commit(4,Durability::Full);
+        commit(5,Durability::None);
+        commit(6,Durability::None);
+        assert_eq!(vec!["Tom4","Bob2","Bob3","Bob4"],crash());
+
+        db.checkpoint().unwrap();
+        assert_eq!(vec!["Tom6","Bob2","Bob3","Bob4","Bob5","Bob6"],crash());
+    }
+
+    #[test]
+    fn test_crash_keeps_synced_pages()
+    {
+        let (mctx,db) = testdb();
+
+        //the rows span several leaves
+        let name = |i:usize| format!("Bob{}{}",i,"x".repeat(60));
+        let mut tx = db.beginTx().unwrap();
+        tx.ExecuteSQLStatments(PERSON.to_string()).unwrap();
+        for i in 0..80 {
+            tx.ExecuteSQLStatments(format!("insert into person ( id, name ) values ('{:02}','{}');",i,name(i))).unwrap();
+        }
+        db.commitTx(&mut tx).unwrap();
+        let synced = db.lock().unwrap().context.pageflushed;
+
+        //the pages of the synced tree freed by these commits are not reused by the next ones
+        for i in 0..5 {
+            let mut tx = db.beginTxWith(Durability::None).unwrap();
+            tx.ExecuteSQLStatments(format!("update person set name = 'Tom{}' index by id = '{:02}';",i,i)).unwrap();
+            tx.ExecuteSQLStatments(format!("delete from person index by id = '{:02}';",79 - i)).unwrap();
+            db.commitTx(&mut tx).unwrap();
+        }
+        assert_eq!(true,db.blocked_pages() > 0);
+
+        //reopen from the master page on disk
+        let crashed = Arc::new(RwLock::new(mctx.read().unwrap().copy()));
+        let other = DBInstance::new(Database::new(DbContext::new(crashed.clone())).unwrap());
+        assert_eq!(synced,other.lock().unwrap().context.pageflushed);
+        //the free list holds only pages freed before the synced master
+        let mut tx = other.beginTx().unwrap();
+        let mut ptr = tx.context.freelist.data.head;
+        while ptr != 0 {
+            let node = tx.context.get(ptr).unwrap();
+            for i in 0..node.flnSize() as usize {
+                assert_eq!(true,node.flnPtrWithVersion(i).1 < tx.version());
+            }
+            ptr = node.flnNext();
+        }
+        other.abortTx(&mut tx);
+        let check = |db:&DBInstance,from:usize| {
+            let mut reader = db.beginRead().unwrap();
+            let list = reader.ExecuteSQLStatments("select id,name from person index by id >= '00';".to_string()).unwrap();
+            assert_eq!(80,list[0].Rows.len());
+            for i in from..80 {
+                assert_eq!(Value::BYTES(format!("{:02}",i).as_bytes().to_vec()),list[0].Rows[i].Vals[0]);
+                assert_eq!(Value::BYTES(name(i).as_bytes().to_vec()),list[0].Rows[i].Vals[1]);
+            }
+            db.endRead(&mut reader);
+        };
+        check(&other,0);
+
+        //nor are the ones the lost commits left in the free list
+        for i in 0..40 {
+            let mut tx = other.beginTx().unwrap();
+            tx.ExecuteSQLStatments(format!("update person set name = 'Ann{}' index by id = '{:02}';",i,i % 5)).unwrap();
+            other.commitTx(&mut tx).unwrap();
+        }
+        check(&other,5);
+
+        //once synced, the freed pages are reused
+        execute(&db,"update person set name = 'Tom' index by id = '00';");
+        assert_eq!(0,db.blocked_pages());
+        let used = db.lock().unwrap().context.pageflushed;
+        for i in 0..10 {
+            let mut tx = db.beginTx().unwrap();
+            tx.ExecuteSQLStatments(format!("update person set name = 'Ann{}' index by id = '01';",i)).unwrap();
+            db.commitTx(&mut tx).unwrap();
+        }
+        assert_eq!(used,db.lock().unwrap().context.pageflushed);
+    }
+
+    #[test]
+    fn test_sync_failure()
+    {
+        let (mctx,db) = testdb();
+
+        execute(&db,PERSON);
+        let rows = || {
+            let mut reader = db.beginRead().unwrap();
+            let list = reader.ExecuteSQLStatments("select id,name from person index by id >= '1';".to_string()).unwrap();
+            db.endRead(&mut reader);
+            list[0].Rows.len()
+        };
+
+        //committed and visible, it must not be retried
+        mctx.write().unwrap().failSync = true;
+        let mut tx = db.beginTx().unwrap();
+        tx.ExecuteSQLStatments("insert into person ( id, name ) values ('1','Bob1');".to_string()).unwrap();
+        assert_eq!(true,matches!(db.commitTx(&mut tx),Err(ContextError::NotDurable)));
+        assert_eq!(1,rows());
+
+        //refused until a sync succeeds
+        let mut tx = db.beginTx().unwrap();
+        tx.ExecuteSQLStatments("insert into person ( id, name ) values ('2','Bob2');".to_string()).unwrap();
+        assert_eq!(true,matches!(db.commitTx(&mut tx),Err(ContextError::FlushFileBUffersError)));
+        db.abortTx(&mut tx);
+        assert_eq!(1,rows());
+
+        mctx.write().unwrap().failSync = false;
+        execute(&db,"insert into person ( id, name ) values ('2','Bob2');");
+        assert_eq!(2,rows());
+        let crashed = Arc::new(RwLock::new(mctx.read().unwrap().copy()));
+        let other = DBInstance::new(Database::new(DbContext::new(crashed)).unwrap());
+        let mut reader = other.beginRead().unwrap();
+        let list = reader.ExecuteSQLStatments("select id,name from person index by id >= '1';".to_string()).unwrap();
+        assert_eq!(2,list[0].Rows.len());
+        other.endRead(&mut reader);
+    }
+
+    #[test]
+    fn test_change_feed()
+    {
+        let (mctx,db) = testdb();
+        let receiver = db.subscribe();
+
+        let sql = r#"
+        create table person
+        ( 
+            id vchar,
+            name vchar,
+            age int16,
+            primary key (id),
+            index (name),
+        );
+        insert into person ( id, name, age ) values ('1','Bob1',20),('2','Bob2',30);
+        update person set age = 21 index by id = '1';
+        delete from person index by id = '2';
+       "#;
+        execute(&db,sql);
+
+        //aborted transactions are not delivered
+        let mut tx = db.beginTx().unwrap();
+        tx.ExecuteSQLStatments("insert into person ( id, name, age ) values ('3','Bob3',40);".to_string()).unwrap();
+        db.abortTx(&mut tx);
+
+        let event = receiver.try_recv().unwrap();
+        assert_eq!(true,receiver.try_recv().is_err());
+        assert_eq!(true,event.Changes[3].to_string().starts_with("person:Delete Key:"));
+
+        let ops:Vec<ChangeOp> = event.Changes.iter().map(|c| c.Op).collect();
+        assert_eq!(vec![ChangeOp::Insert,ChangeOp::Insert,ChangeOp::Update,ChangeOp::Delete],ops);
+        assert_eq!("person".as_bytes().to_vec(),event.Changes[0].Table);
+        assert_eq!(vec![Value::BYTES("1".as_bytes().to_vec())],event.Changes[2].Key);
+        assert_eq!(Value::INT16(20),event.Changes[2].Old.as_ref().unwrap()[2]);
+        assert_eq!(Value::INT16(21),event.Changes[2].New.as_ref().unwrap()[2]);
+        assert_eq!(true,event.Changes[3].New.is_none());
+        assert_eq!(db.lock().unwrap().context.version,event.Version);
+
+        //delivered only after the commit is synced
+        let mut tx = db.beginTxWith(Durability::None).unwrap();
+        tx.ExecuteSQLStatments("insert into person ( id, name, age ) values ('4','Bob4',40);".to_string()).unwrap();
+        db.commitTx(&mut tx).unwrap();
+        assert_eq!(true,receiver.try_recv().is_err());
+
+        //committed but not durable, delivered with the next successful sync
+        mctx.write().unwrap().failSync = true;
+        let mut tx = db.beginTx().unwrap();
+        tx.ExecuteSQLStatments("insert into person ( id, name, age ) values ('5','Bob5',50);".to_string()).unwrap();
+        assert_eq!(true,matches!(db.commitTx(&mut tx),Err(ContextError::NotDurable)));
+        assert_eq!(true,receiver.try_recv().is_err());
+
+        //a failed commit is never delivered
+        let mut tx = db.beginTx().unwrap();
+        tx.ExecuteSQLStatments("insert into person ( id, name, age ) values ('6','Bob6',60);".to_string()).unwrap();
+        assert_eq!(false,matches!(db.commitTx(&mut tx),Ok(_) | Err(ContextError::NotDurable)));
+        db.abortTx(&mut tx);
+        assert_eq!(true,receiver.try_recv().is_err());
+
+        mctx.write().unwrap().failSync = false;
+        db.checkpoint().unwrap();
+        let event = receiver.try_recv().unwrap();
+        assert_eq!(vec![Value::BYTES("4".as_bytes().to_vec())],event.Changes[0].Key);
+        let event = receiver.try_recv().unwrap();
+        assert_eq!(vec![Value::BYTES("5".as_bytes().to_vec())],event.Changes[0].Key);
+        assert_eq!(true,receiver.try_recv().is_err());
+    }
+
+    #[test]
+    fn test_select_filter()
+    {
+        let (_,db) = testdb();
+
+        let sql = r#"
+        create table person
+        ( 
+            id vchar,
+            name vchar,
+            age int16,
+            primary key (id),
+            index (name),
+        );
+        insert into person ( id, name, age ) values ('1','Bob1',20),('2','Bob2',30),('3','Bob3',40),('4','Bob4',50),('5','Bob5',60);
+       "#;
+        execute(&db,sql);
+
+        let mut reader = db.beginRead().unwrap();
+        let list = reader.ExecuteSQLStatments("select id,age from person index by id >= '1' and id <= '5' filter age > 25;".to_string()).unwrap();
+        assert_eq!(4,list[0].Rows.len());
+        assert_eq!(Value::BYTES("2".as_bytes().to_vec()),list[0].Rows[0].Vals[0]);
+
+        let list = reader.ExecuteSQLStatments("select id,age from person index by id >= '1' and id <= '5' filter age > 25 limit 2 offset 1;".to_string()).unwrap();
+        assert_eq!(2,list[0].Rows.len());
+        assert_eq!(Value::BYTES("3".as_bytes().to_vec()),list[0].Rows[0].Vals[0]);
+        assert_eq!(Value::BYTES("4".as_bytes().to_vec()),list[0].Rows[1].Vals[0]);
+        db.endRead(&mut reader);
+    }
+
+    #[test]
+    fn test_open_range_scan()
+    {
+        let (_,db) = testdb();
+
+        let sql = r#"
+        create table person
+        ( 
+            id vchar,
+            name vchar,
+            age int16,
+            primary key (id),
+            index (name),
+        );
+        insert into person ( id, name, age ) values ('1','Bob1',20),('2','Bob2',30),('3','Bob3',40);
+       "#;
+        execute(&db,sql);
+
+        // the name index is stored right after the rows
+        let mut reader = db.beginRead().unwrap();
+        let list = reader.ExecuteSQLStatments("select id,age from person index by id > '1';".to_string()).unwrap();
+        assert_eq!(2,list[0].Rows.len());
+        assert_eq!(Value::BYTES("3".as_bytes().to_vec()),list[0].Rows[1].Vals[0]);
+        let list = reader.ExecuteSQLStatments("select id,name from person index by name >= 'Bob2';".to_string()).unwrap();
+        assert_eq!(2,list[0].Rows.len());
+        db.endRead(&mut reader);
+    }
+
+    #[test]
+    fn test_snapshot()
+    {
+        let (_,db) = testdb();
+
+        let sql = r#"
+        create table person
+        ( 
+            id vchar,
+            name vchar,
+            age int16,
+            primary key (id),
+            index (name),
+        );
+        insert into person ( id, name, age ) values ('1','Bob1',20),('2','Bob2',30);
+       "#;
+        execute(&db,sql);
+
+        db.create_snapshot("monthend").unwrap();
+        assert_eq!(true,db.create_snapshot("monthend").is_err());
+
+        execute(&db,"update person set age = 21 index by id = '1';delete from person index by id = '2';");
+
+        let mut reader = db.beginRead_at("monthend").unwrap();
+        let list = reader.ExecuteSQLStatments("select id,age from person index by id = '1';".to_string()).unwrap();
+        assert_eq!(Value::INT16(20),list[0].Rows[0].Vals[1]);
+        let list = reader.ExecuteSQLStatments("select id,age from person index by id = '2';".to_string()).unwrap();
+        assert_eq!(1,list[0].Rows.len());
+        db.endRead(&mut reader);
+
+        let mut reader = db.beginRead().unwrap();
+        let list = reader.ExecuteSQLStatments("select id,age from person index by id = '1';".to_string()).unwrap();
+        assert_eq!(Value::INT16(21),list[0].Rows[0].Vals[1]);
+        let list = reader.ExecuteSQLStatments("select id,age from person index by id = '1' as of snapshot monthend;".to_string()).unwrap();
+        assert_eq!(Value::INT16(20),list[0].Rows[0].Vals[1]);
+        let list = reader.ExecuteSQLStatments("select id,age from person index by id = '2' as of snapshot monthend;".to_string()).unwrap();
+        assert_eq!(1,list[0].Rows.len());
+        //the reader of the snapshot is registered only during the select
+        assert_eq!(1,db.active_readers().len());
+        db.endRead(&mut reader);
+
+        db.release_snapshot("monthend").unwrap();
+        assert_eq!(true,db.beginRead_at("monthend").is_err());
+        assert_eq!(true,db.release_snapshot("monthend").is_err());
+        let mut reader = db.beginRead().unwrap();
+        let ret = reader.ExecuteSQLStatments("select id,age from person index by id = '1' as of snapshot monthend;".to_string());
+        assert_eq!(true,matches!(ret,Err(BTreeError::SnapshotNotFound(name)) if name == "monthend"));
+        db.endRead(&mut reader);
+    }
+
+    #[test]
+    fn test_reader_tracking()
+    {
+        let (_,db) = testdb();
+
+        let sql = r#"
+        create table person
+        ( 
+            id vchar,
+            name vchar,
+            age int16,
+            primary key (id),
+            index (name),
+        );
+        insert into person ( id, name, age ) values ('1','Bob1',20),('2','Bob2',30);
+       "#;
+        execute(&db,sql);
+
+        let mut reader = db.beginRead_as("monthly report").unwrap();
+        let readers = db.active_readers();
+        assert_eq!(1,readers.len());
+        assert_eq!("monthly report",readers[0].Owner);
+        assert_eq!(db.lock().unwrap().context.version,readers[0].Version);
+        assert_eq!(true,readers[0].to_string().starts_with(&format!("Reader:{} Owner:monthly report Version:{}",readers[0].Index,readers[0].Version)));
+
+        //the pages replaced by the update are still reachable by the reader
+        execute(&db,"update person set age = 21 index by id = '1';");
+        assert_eq!(true,db.blocked_pages() > 0);
+
+        //any age reaches a zero max age, the reader expires without waiting
+        db.set_max_reader_age(Some(Duration::ZERO));
+        assert_eq!(0,db.blocked_pages());
+        let ret = reader.ExecuteSQLStatments("select id,age from person index by id = '1';".to_string());
+        assert_eq!(true,matches!(ret,Err(BTreeError::ReaderExpired)));
+        db.endRead(&mut reader);
+        assert_eq!(0,db.active_readers().len());
+
+        //a new reader is not affected
+        db.set_max_reader_age(Some(Duration::from_secs(3600)));
+        let mut reader = db.beginRead().unwrap();
+        let list = reader.ExecuteSQLStatments("select id,age from person index by id = '1';".to_string()).unwrap();
+        assert_eq!(Value::INT16(21),list[0].Rows[0].Vals[1]);
+        db.endRead(&mut reader);
+    }
+
+    #[test]
+    fn test_expired_reader_scan()
+    {
+        let (_,db) = testdb();
+
+        let mut tx = db.beginTx().unwrap();
+        tx.ExecuteSQLStatments(PERSON.to_string()).unwrap();
+        for i in 0..150 {
+            tx.ExecuteSQLStatments(format!("insert into person ( id, name ) values ('{:03}','{}');",i,"Bob".repeat(40))).unwrap();
+        }
+        db.commitTx(&mut tx).unwrap();
+
+        //an open scan stops at the next page once the reader has expired
+        let mut reader = db.beginRead().unwrap();
+        let tdef = reader.getTableDef("person".as_bytes()).unwrap();
+        let mut key = Record::new(&tdef);
+        key.Set("id".as_bytes(), Value::BYTES("000".as_bytes().to_vec()));
+        let mut scanner = reader.Scan(OP_CMP::CMP_GE, None, &key, None).unwrap();
+        assert_eq!(2,scanner.by_ref().take(2).count());
+
+        db.set_max_reader_age(Some(Duration::ZERO));
+        assert_eq!(true,2 + scanner.count() < 150);
+
+        let ret = reader.ExecuteSQLStatments("select id,name from person index by id >= '000';".to_string());
+        assert_eq!(true,matches!(ret,Err(BTreeError::ReaderExpired)));
+        db.endRead(&mut reader);
+    }
+
+    #[test]
+    fn test_reader_handles()
+    {
+        let (_,db) = testdb();
+
+        execute(&db,PERSON);
+        execute(&db,"insert into person ( id, name ) values ('1','Bob1');");
+
+        //ending a reader must not unregister another one
+        let mut reader1 = db.beginRead().unwrap();
+        let mut reader2 = db.beginRead().unwrap();
+        db.endRead(&mut reader1);
+        let mut reader3 = db.beginRead().unwrap();
+        assert_ne!(reader2.index,reader3.index);
+        db.endRead(&mut reader3);
+        let readers = db.active_readers();
+        assert_eq!(1,readers.len());
+        assert_eq!(reader2.index,readers[0].Index);
+        db.endRead(&mut reader2);
+
+        //readers don't wait for the database lock
+        let lock = db.lock().unwrap();
+        {
+            let mut guard = ReadGuard::new(&db).unwrap();
+            let list = guard.ExecuteSQLStatments("select id,name from person index by id = '1';".to_string()).unwrap();
+            assert_eq!(1,list[0].Rows.len());
+            assert_eq!(1,db.active_readers().len());
+        }
+        drop(lock);
+        assert_eq!(0,db.active_readers().len());
+    }
+
+    #[test]
+    fn test_read_pages_copied()
+    {
+        let (mctx,db) = testdb();
+
+        execute(&db,PERSON);
+
+        //a page rewritten in the mapping doesn't change under the reader or the transaction
+        let reader = db.beginRead().unwrap();
+        let tx = db.beginTx().unwrap();
+        let ptr = reader.get_root();
+        let page = reader.getRef(ptr).unwrap();
+        let txPage = tx.context.getRef(ptr).unwrap();
+        let content = page.data().to_vec();
+        unsafe {
+            let mmap = mctx.read().unwrap().getMmap();
+            std::ptr::write_bytes(mmap.read().unwrap().ptr.add(ptr as usize * BTREE_PAGE_SIZE), 0, BTREE_PAGE_SIZE);
+        }
+        assert_eq!(content,page.data());
+        assert_eq!(content,txPage.data());
+    }
+
+    #[test]
+    fn test_catalog_isolation()
+    {
+        let (_,db) = testdb();
+
+        let mut oldReader = db.beginRead().unwrap();
+        let mut tx = db.beginTx().unwrap();
+        tx.ExecuteSQLStatments(PERSON.to_string()).unwrap();
+        tx.ExecuteSQLStatments("insert into person ( id, name ) values ('1','Bob1');".to_string()).unwrap();
+        assert_eq!(true,tx.getTableDef("person".as_bytes()).is_some());
+
+        //not visible before commit
+        let mut reader = db.beginRead().unwrap();
+        assert_eq!(true,reader.getTableDef("person".as_bytes()).is_none());
+        db.endRead(&mut reader);
+        db.commitTx(&mut tx).unwrap();
+
+        //not visible to a snapshot older than the commit
+        assert_eq!(true,oldReader.getTableDef("person".as_bytes()).is_none());
+        let ret = oldReader.ExecuteSQLStatments("select id,name from person index by id = '1';".to_string());
+        assert_eq!(true,matches!(ret,Err(BTreeError::TableNotFind)));
+        db.endRead(&mut oldReader);
+
+        let mut reader = db.beginRead().unwrap();
+        let list = reader.ExecuteSQLStatments("select id,name from person index by id = '1';".to_string()).unwrap();
+        assert_eq!(1,list[0].Rows.len());
+        db.endRead(&mut reader);
+
+        //a table created after a savepoint is gone with the rollback
+        let mut tx = db.beginTx().unwrap();
+        tx.savepoint("s1");
+        tx.ExecuteSQLStatments(PERSON.replace("person","book")).unwrap();
+        assert_eq!(true,tx.getTableDef("book".as_bytes()).is_some());
+        tx.rollbackTo("s1").unwrap();
+        assert_eq!(true,tx.getTableDef("book".as_bytes()).is_none());
+        db.commitTx(&mut tx).unwrap();
+        assert_eq!(false,db.lock().unwrap().tables.contains("book".as_bytes()));
+    }
+
+    #[test]
+    fn test_begin_without_lock()
+    {
+        let (mctx,db) = testdb();
+
+        execute(&db,PERSON);
+        execute(&db,"insert into person ( id, name ) values ('1','Bob1');");
+
+        //a commit holds the database while the mapping is being synced
+        let lock = db.lock().unwrap();
+        let sync = mctx.write().unwrap();
+
+        let mut reader = db.beginRead().unwrap();
+        let list = reader.ExecuteSQLStatments("select id,name from person index by id = '1';".to_string()).unwrap();
+        assert_eq!(1,list[0].Rows.len());
+        db.endRead(&mut reader);
+
+        let mut tx = db.beginTx().unwrap();
+        tx.ExecuteSQLStatments("insert into person ( id, name ) values ('2','Bob2');".to_string()).unwrap();
+        drop(sync);
+        drop(lock);
+        db.commitTx(&mut tx).unwrap();
+
+        let mut reader = db.beginRead().unwrap();
+        let list = reader.ExecuteSQLStatments("select id,name from person index by id = '2';".to_string()).unwrap();
+        assert_eq!(1,list[0].Rows.len());
+        db.endRead(&mut reader);
+    }
+
+    #[test]
+    fn test_reader_process()
+    {
+        let path = std::env::temp_dir().join(format!("rustdb-{}.readers",std::process::id()));
+        let path = path.to_str().unwrap();
+        _ = std::fs::remove_file(path);
+
+        //two databases on one mapping stand for the writer and the reader process
+        let (mctx,db) = testdb();
+        db.share_readers(path).unwrap();
+        let other = DBInstance::open_reader(DbContext::new(mctx.clone()),path).unwrap();
+        assert_eq!(true,other.beginTx().is_err());
+
+        execute(&db,PERSON);
+        execute(&db,"insert into person ( id, name ) values ('1','Bob1');");
+
+        //the reader process sees the commit and pins its version
+        let mut reader = other.beginRead().unwrap();
+        let list = reader.ExecuteSQLStatments("select id,name from person index by id = '1';".to_string()).unwrap();
+        assert_eq!(1,list[0].Rows.len());
+
+        execute(&db,"insert into person ( id, name ) values ('2','Bob2');");
+        assert_eq!(reader.version,db.lock().unwrap().getMinReadVersion());
+
+        let list = reader.ExecuteSQLStatments("select id,name from person index by id = '2';".to_string()).unwrap();
+        assert_eq!(0,list[0].Rows.len());
+        other.endRead(&mut reader);
+        let current = db.lock().unwrap().context.version;
+        assert_eq!(current,db.lock().unwrap().getMinReadVersion());
+
+        let mut reader = other.beginRead().unwrap();
+        let list = reader.ExecuteSQLStatments("select id,name from person index by id = '2';".to_string()).unwrap();
+        assert_eq!(1,list[0].Rows.len());
+        other.endRead(&mut reader);
+
+        drop(other);
+        drop(db);
+        _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn test_reader_slots_process()
+    {
+        let path = std::env::temp_dir().join(format!("rustdb-{}.slots",std::process::id()));
+        let path = path.to_str().unwrap().to_string();
+        let ready = format!("{}.ready",path);
+        _ = std::fs::remove_file(&path);
+        _ = std::fs::remove_file(&ready);
+
+        let (_,db) = testdb();
+        db.share_readers(&path).unwrap();
+
+        execute(&db,PERSON);
+        let pinned = db.lock().unwrap().context.version;
+        execute(&db,"insert into person ( id, name ) values ('1','Bob1');");
+
+        //another process pins a version the way its readers do
+        let mut child = std::process::Command::new(std::env::current_exe().unwrap())
+            .args(["--exact","btree::tx::database::tests::reader_slots_child","--ignored"])
+            .env("RUSTDB_SLOTS",&path)
+            .env("RUSTDB_VERSION",pinned.to_string())
+            .stdout(std::process::Stdio::null())
+            .spawn().unwrap();
+        for _ in 0..1000 {
+            if std::path::Path::new(&ready).exists() {
+                break;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(pinned,db.lock().unwrap().getMinReadVersion());
+
+        //its slot is reclaimed once it's gone
+        child.kill().unwrap();
+        child.wait().unwrap();
+        let current = db.lock().unwrap().context.version;
+        assert_eq!(current,db.lock().unwrap().getMinReadVersion());
+
+        drop(db);
+        _ = std::fs::remove_file(&path);
+        _ = std::fs::remove_file(&ready);
+    }
+
+    // the reader process of test_reader_slots_process, it holds the slot until it's killed
+    #[test]
+    #[ignore]
+    fn reader_slots_child()
+    {
+        let (Ok(path),Ok(version)) = (std::env::var("RUSTDB_SLOTS"),std::env::var("RUSTDB_VERSION")) else {
+            return;
+        };
+        let slots = ReaderSlots::open(&path).unwrap();
+        slots.acquire(version.parse().unwrap()).unwrap();
+        std::fs::write(format!("{}.ready",path), "").unwrap();
+        thread::sleep(Duration::from_secs(60));
+    }
+
+    #[test]
+    fn test_open_read_only()
+    {
+        //a reader process doesn't create the file
+        let mut mctx = Arc::new(RwLock::new(memoryContext::new(BTREE_PAGE_SIZE,1000)));
+        let ret = Database::openReadOnly(DbContext::new(mctx.clone()));
+        assert_eq!(true,matches!(ret,Err(ContextError::NotDataBaseFile)));
+        assert_eq!(0,mctx.read().unwrap().getContextSize());
+        assert_eq!(0,mctx.read().unwrap().syncCount);
+
+        //nor migrates it
+        let db = Database::new(DbContext::new(mctx.clone())).unwrap();
+        drop(db);
+        let mmap = mctx.read().unwrap().getMmap();
+        unsafe {
+            std::ptr::copy_nonoverlapping(crate::btree::kv::DB_SIG_V1.as_ptr(), mmap.read().unwrap().ptr, 16);
+        }
+        let syncs = mctx.read().unwrap().syncCount;
+        let ret = Database::openReadOnly(DbContext::new(mctx.clone()));
+        assert_eq!(true,matches!(ret,Err(ContextError::ReadOnly)));
+        assert_eq!(syncs,mctx.read().unwrap().syncCount);
+        unsafe {
+            assert_eq!(crate::btree::kv::DB_SIG_V1,std::slice::from_raw_parts(mmap.read().unwrap().ptr, 16));
+        }
+    }
+
+    #[test]
+    fn test_migrate_legacy()
+    {
+        let mut mctx = Arc::new(RwLock::new(memoryContext::new(BTREE_PAGE_SIZE,1000)));
+        let mut db = Database::new(DbContext::new(mctx.clone())).unwrap();
+
+        let sql = r#"
+        create table account
+        ( 
+            id int64,
+            balance int32,
+            primary key (id),
+            index (balance),
+        );
+        create table tag
+        ( 
+            id int16,
+            rank int8,
+            primary key (id),
+            index (rank),
+        );
+       "#;
+        let mut tx = db.begin().unwrap();
+        tx.ExecuteSQLStatments(sql.to_string()).unwrap();
+        db.commmit(&mut tx).unwrap();
+
+        //rows as the old format wrote them, integers little-endian
+        let mut tx = db.begin().unwrap();
+        let account = tx.getTableDef("account".as_bytes()).unwrap();
+        for i in 0..10i64
+        {
+            let (id,balance) = (i * 37 - 100,500 - i as i32 * 90);
+            let mut key = account.Prefix.to_le_bytes().to_vec();
+            key.extend_from_slice(&id.to_le_bytes());
+            tx.Set(&mut InsertReqest::new(&key, &balance.to_le_bytes(), MODE_UPSERT));
+
+            let mut key = account.IndexPrefixes[0].to_le_bytes().to_vec();
+            key.extend_from_slice(&balance.to_le_bytes());
+            key.extend_from_slice(&id.to_le_bytes());
+            tx.Set(&mut InsertReqest::new(&key, &[], MODE_UPSERT));
+        }
+        let tag = tx.getTableDef("tag".as_bytes()).unwrap();
+        for (id,rank) in [(-2i16,3i8),(7,-1),(300,9)]
+        {
+            let mut key = tag.Prefix.to_le_bytes().to_vec();
+            key.extend_from_slice(&id.to_le_bytes());
+            tx.Set(&mut InsertReqest::new(&key, &rank.to_le_bytes(), MODE_UPSERT));
+
+            let mut key = tag.IndexPrefixes[0].to_le_bytes().to_vec();
+            key.extend_from_slice(&rank.to_le_bytes());
+            key.extend_from_slice(&id.to_le_bytes());
+            tx.Set(&mut InsertReqest::new(&key, &[], MODE_UPSERT));
+        }
+        db.commmit(&mut tx).unwrap();
+        drop(db);
+
+        //the master page as the first versions stored it, 16 bytes in without a checksum
+        let mmap = mctx.read().unwrap().getMmap();
+        unsafe {
+            let buffer = mmap.read().unwrap().ptr;
+            std::ptr::copy(buffer.add(16), buffer.add(32), 32);
+            std::ptr::copy_nonoverlapping(crate::btree::kv::DB_SIG_V1.as_ptr(), buffer.add(16), 16);
+            std::ptr::write_bytes(buffer, 0, 16);
+            std::ptr::write_bytes(buffer.add(64), 0, 8);
+        }
+
+        //interrupted after the first commits, the file has the new signature by then
+        let synced = mctx.read().unwrap().syncCount;
+        mctx.write().unwrap().syncLimit = Some(synced + 5);
+        assert_eq!(true,Database::new(DbContext::new(mctx.clone())).is_err());
+        unsafe {
+            assert_eq!(crate::btree::kv::DB_SIG,std::slice::from_raw_parts(mmap.read().unwrap().ptr, 16));
+        }
+        let mut context = DbContext::new(mctx.clone());
+        context.masterload().unwrap();
+        let tx = txwriter::new(context.createTx(context.version).unwrap(),Arc::new(Catalog::new()));
+        let state = Database::migration(&tx).unwrap().unwrap();
+        assert_eq!(vec!["account".as_bytes().to_vec(),"tag".as_bytes().to_vec()],state.Tables);
+        assert_eq!(true,state.Target.is_some());
+        drop(tx);
+
+        mctx.write().unwrap().syncLimit = None;
+        let db = DBInstance::new(Database::new(DbContext::new(mctx.clone())).unwrap());
+        assert_eq!(false,db.lock().unwrap().context.legacy);
+        assert_eq!(false,db.lock().unwrap().migrating().unwrap());
+
+        let mut reader = db.beginRead().unwrap();
+        let list = reader.ExecuteSQLStatments("select id,balance from account index by id >= -200 and id < 1000;".to_string()).unwrap();
+        assert_eq!(10,list[0].Rows.len());
+        let list = reader.ExecuteSQLStatments("select id,balance from account index by id >= -5 and id < 100;".to_string()).unwrap();
+        assert_eq!(3,list[0].Rows.len());
+        let list = reader.ExecuteSQLStatments("select id,balance from account index by balance > -100 and balance < 100;".to_string()).unwrap();
+        assert_eq!(2,list[0].Rows.len());
+        let list = reader.ExecuteSQLStatments("select id,balance from account index by balance = 500;".to_string()).unwrap();
+        assert_eq!(1,list[0].Rows.len());
+        assert_eq!(Value::INT64(-100),list[0].Rows[0].Vals[0]);
+        let list = reader.ExecuteSQLStatments("select id,rank from tag index by id >= -5 and id < 10;".to_string()).unwrap();
+        assert_eq!(2,list[0].Rows.len());
+        let list = reader.ExecuteSQLStatments("select id,rank from tag index by rank = 9;".to_string()).unwrap();
+        assert_eq!(Value::INT16(300),list[0].Rows[0].Vals[0]);
+        db.endRead(&mut reader);
+
+        //no key is left under the old prefixes
+        let mut tx = db.beginTx().unwrap();
+        for prefix in [account.Prefix,account.IndexPrefixes[0],tag.Prefix,tag.IndexPrefixes[0]]
+        {
+            let iter = tx.Seek(&prefix.to_le_bytes(), OP_CMP::CMP_GE);
+            assert_eq!(false,iter.Valid() && iter.Deref().0[0..4] == prefix.to_le_bytes());
+        }
+        db.abortTx(&mut tx);
+    }
+
+    #[test]
+    fn test_column_constraints()
+    {
+        let (_,db) = testdb();
+
+        let sql = r#"
+        create table account
+        ( 
+            id int64,
+            owner vchar not null,
+            balance int64 not null default 100,
+            note vchar null default 'new',
+            primary key (id),
+            index (owner),
+            check (balance >= 0 and balance < 1000),
+        );
+        insert into account ( id, owner ) values (1, 'Bob');
+        insert into account ( id, owner, balance, note ) values (2, 'Ann', 5, null);
+       "#;
+        let mut tx = db.beginTx().unwrap();
+        tx.ExecuteSQLStatments(sql.to_string()).unwrap();
+        assert!(matches!(exec(&mut tx,"insert into account ( id, balance ) values (3, 1);"),Err(BTreeError::NullNotAllowed(_))));
+        assert!(matches!(exec(&mut tx,"insert into account ( id, owner, balance ) values (3, 'Tom', 5000);"),Err(BTreeError::CheckViolation(_))));
+        assert!(matches!(exec(&mut tx,"update account set balance = balance - 10 index by id = 2;"),Err(BTreeError::CheckViolation(_))));
+        assert!(matches!(exec(&mut tx,"update account set owner = null index by id = 2;"),Err(BTreeError::NullNotAllowed(_))));
+        //a statement failing on its second row leaves the first one as it was
+        assert!(matches!(exec(&mut tx,"update account set balance = balance - 50 index by id >= 1;"),Err(BTreeError::CheckViolation(_))));
+        assert!(matches!(exec(&mut tx,"insert into account ( id, owner ) values (3, 'Tom'), (4, null);"),Err(BTreeError::NullNotAllowed(_))));
+        assert_eq!(1,exec(&mut tx,"update account set balance = balance - 10 index by id = 1;").unwrap());
+
+        //a CHECK or DEFAULT that can't be used is rejected with the table
+        for sql in ["create table bad ( id int64, n int64, primary key (id), index (n), check (n + 1), );",
+                    "create table bad ( id int64, n int64, primary key (id), index (n), check (m > 0), );",
+                    "create table bad ( id int64, n int64, primary key (id), index (n), check (n > 'x'), );",
+                    "create table bad ( id int64, n int64 default 'abc', primary key (id), index (n), );"]
+        {
+            assert!(matches!(exec(&mut tx,sql),Err(BTreeError::BadExpression(_))));
+        }
+        db.commitTx(&mut tx).unwrap();
+
+        let mut reader = db.beginRead().unwrap();
+        let list = reader.ExecuteSQLStatments("select id,owner,balance,note from account index by id >= 1;".to_string()).unwrap();
+        assert_eq!(2,list[0].Rows.len());
+        assert_eq!(Value::INT64(90),list[0].Rows[0].Vals[2]);
+        assert_eq!(Value::BYTES("new".as_bytes().to_vec()),list[0].Rows[0].Vals[3]);
+        assert_eq!(Value::INT64(5),list[0].Rows[1].Vals[2]);
+        assert_eq!(Value::NULL,list[0].Rows[1].Vals[3]);
+        db.endRead(&mut reader);
+    }
+
+    #[test]
+    fn test_autoincrement_sequence()
+    {
+        use crate::btree::parser::{lib::Parser, statement::{ExprSQLList, SQLExpr}};
+        let insert = |tx:&mut txwriter,sql:&str| {
+            match &ExprSQLList().parse(sql).unwrap().1[0] {
+                SQLExpr::Insert(cmd) => tx.executeInsert(cmd),
+                _Other => panic!(),
+            }
+        };
+
+        let (_,db) = testdb();
+
+        let sql = r#"
+        create table orders
+        ( 
+            id int64 autoincrement,
+            item vchar,
+            ticket int32 default nextval('tickets'),
+            primary key (id),
+            index (item),
+        );
+        create sequence tickets;
+       "#;
+        let mut tx = db.beginTx().unwrap();
+        tx.ExecuteSQLStatments(sql.to_string()).unwrap();
+        let (count,keys) = insert(&mut tx,"insert into orders ( item ) values ('a'), ('b');").unwrap();
+        assert_eq!(2,count);
+        assert_eq!(vec![Value::INT64(1),Value::INT64(2)],keys);
+        let (_,keys) = insert(&mut tx,"insert into orders ( id, item ) values (10, 'c');").unwrap();
+        assert_eq!(vec![Value::INT64(10)],keys);
+        let (_,keys) = insert(&mut tx,"insert into orders ( item, ticket ) values ('d', nextval('tickets') * 100);").unwrap();
+        assert_eq!(vec![Value::INT64(11)],keys);
+        assert!(matches!(insert(&mut tx,"insert into orders ( item, ticket ) values ('e', nextval('nothing'));"),Err(BTreeError::SequenceNotFound(_))));
+        assert!(matches!(tx.createSequence("tickets"),Err(BTreeError::SequenceAlreadyExist(_))));
+        db.commitTx(&mut tx).unwrap();
+
+        //an aborted transaction gives its values back
+        let mut tx = db.beginTx().unwrap();
+        let (_,keys) = insert(&mut tx,"insert into orders ( item ) values ('x');").unwrap();
+        assert_eq!(vec![Value::INT64(12)],keys);
+        assert_eq!(Value::INT64(6),tx.nextval("tickets".as_bytes()).unwrap());
+        db.abortTx(&mut tx);
+
+        let mut tx = db.beginTx().unwrap();
+        let (_,keys) = insert(&mut tx,"insert into orders ( item ) values ('f');").unwrap();
+        assert_eq!(vec![Value::INT64(12)],keys);
+        db.commitTx(&mut tx).unwrap();
+
+        let mut reader = db.beginRead().unwrap();
+        let list = reader.ExecuteSQLStatments("select id,item,ticket from orders index by id >= 1;".to_string()).unwrap();
+        assert_eq!(5,list[0].Rows.len());
+        assert_eq!(Value::INT32(1),list[0].Rows[0].Vals[2]);
+        assert_eq!(Value::INT64(10),list[0].Rows[2].Vals[0]);
+        assert_eq!(Value::INT32(3),list[0].Rows[2].Vals[2]);
+        assert_eq!(Value::INT32(400),list[0].Rows[3].Vals[2]);
+        assert_eq!(Value::INT64(12),list[0].Rows[4].Vals[0]);
+        assert_eq!(Value::INT32(5),list[0].Rows[4].Vals[2]);
+        db.endRead(&mut reader);
+    }
+
+    #[test]
+    fn test_unique_index()
+    {
+        let (_,db) = testdb();
+
+        let sql = r#"
+        create table users
+        ( 
+            id int64,
+            email vchar null,
+            name vchar null,
+            primary key (id),
+            unique index (email),
+            index (name),
+        );
+        insert into users ( id, email, name ) values (1, 'bob@a.org', 'Bob');
+        insert into users ( id, email, name ) values (2, 'ann@a.org', 'Bob');
+        insert into users ( id, name ) values (3, 'Tom');
+        insert into users ( id, name ) values (4, 'Tim');
+       "#;
+        let mut tx = db.beginTx().unwrap();
+        tx.ExecuteSQLStatments(sql.to_string()).unwrap();
+        match exec(&mut tx,"insert into users ( id, email ) values (5, 'bob@a.org');") {
+            Err(BTreeError::UniqueViolation(name)) => assert_eq!("users(email)",name),
+            _Other => panic!(),
+        }
+        assert!(matches!(exec(&mut tx,"update users set email = 'bob@a.org' index by id = 2;"),Err(BTreeError::UniqueViolation(_))));
+        assert_eq!(1,exec(&mut tx,"update users set name = 'Ann' index by id = 2;").unwrap());
+        assert_eq!(1,exec(&mut tx,"update users set email = 'robert@a.org' index by id = 1;").unwrap());
+        db.commitTx(&mut tx).unwrap();
+
+        let mut tx = db.beginTx().unwrap();
+        assert_eq!(1,exec(&mut tx,"insert into users ( id, email ) values (5, 'bob@a.org');").unwrap());
+        db.commitTx(&mut tx).unwrap();
+
+        let mut reader = db.beginRead().unwrap();
+        let list = reader.ExecuteSQLStatments("select id,email,name from users index by id >= 1;".to_string()).unwrap();
+        assert_eq!(5,list[0].Rows.len());
+        assert_eq!(Value::BYTES("robert@a.org".as_bytes().to_vec()),list[0].Rows[0].Vals[1]);
+        assert_eq!(Value::BYTES("Ann".as_bytes().to_vec()),list[0].Rows[1].Vals[2]);
+        assert_eq!(Value::NULL,list[0].Rows[3].Vals[1]);
+        assert_eq!(Value::BYTES("bob@a.org".as_bytes().to_vec()),list[0].Rows[4].Vals[1]);
+        db.endRead(&mut reader);
+    }
+
+    #[test]
+    fn test_foreign_keys()
+    {
+        let (_,db) = testdb();
+
+        let sql = r#"
+        create table customers
+        ( 
+            id int64,
+            name vchar,
+            primary key (id),
+            index (name),
+        );
+        create table orders
+        ( 
+            id int64,
+            customer int64 null,
+            primary key (id),
+            index (customer),
+            foreign key (customer) references customers(id),
+        );
+        create table lines
+        ( 
+            orderid int64,
+            line int32,
+            product vchar,
+            primary key (orderid,line),
+            index (orderid),
+            foreign key (orderid) references orders(id) on delete cascade,
+        );
+        insert into customers ( id, name ) values (1, 'Bob'), (2, 'Ann');
+        insert into orders ( id, customer ) values (10, 1), (11, 1), (12, null);
+        insert into lines ( orderid, line, product ) values (10, 1, 'pen'), (10, 2, 'ink'), (11, 1, 'cup');
+       "#;
+        execute(&db,sql);
+        let mut tx = db.beginTx().unwrap();
+        match exec(&mut tx,"insert into orders ( id, customer ) values (13, 3);") {
+            Err(BTreeError::ForeignKeyViolation(name)) => assert_eq!("orders(customer) references customers(id)",name),
+            _Other => panic!(),
+        }
+        assert!(matches!(exec(&mut tx,"update orders set customer = 3 index by id = 10;"),Err(BTreeError::ForeignKeyViolation(_))));
+        assert!(matches!(exec(&mut tx,"delete from customers index by id = 1;"),Err(BTreeError::ForeignKeyViolation(_))));
+        assert!(matches!(exec(&mut tx,"create table bad ( id int64, other int64, primary key (id), index (id), foreign key (other) references orders(id), );"),Err(BTreeError::BadForeignKey(_))));
+        assert_eq!(1,exec(&mut tx,"update orders set customer = 2 index by id = 11;").unwrap());
+        assert_eq!(1,exec(&mut tx,"delete from orders index by id = 10;").unwrap());
+        db.commitTx(&mut tx).unwrap();
+
+        let mut reader = db.beginRead().unwrap();
+        let list = reader.ExecuteSQLStatments("select orderid,line,product from lines index by orderid >= 1;select id,customer from orders index by id >= 1;".to_string()).unwrap();
+        assert_eq!(1,list[0].Rows.len());
+        assert_eq!(Value::INT64(11),list[0].Rows[0].Vals[0]);
+        assert_eq!(2,list[1].Rows.len());
+        db.endRead(&mut reader);
+
+        // nothing references Bob anymore
+        let mut tx = db.beginTx().unwrap();
+        assert_eq!(1,exec(&mut tx,"delete from customers index by id = 1;").unwrap());
+        assert!(matches!(exec(&mut tx,"delete from customers index by id = 2;"),Err(BTreeError::ForeignKeyViolation(_))));
+        db.commitTx(&mut tx).unwrap();
+
+        //changing a referenced key is restricted like a delete, a cascade moves the children along
+        let mut tx = db.beginTx().unwrap();
+        assert!(matches!(exec(&mut tx,"update customers set id = 5 index by id = 2;"),Err(BTreeError::ForeignKeyViolation(_))));
+        assert!(matches!(exec(&mut tx,"update orders set id = 12 index by id = 11;"),Err(BTreeError::UniqueViolation(_))));
+        assert_eq!(1,exec(&mut tx,"update orders set id = 21 index by id = 11;").unwrap());
+        db.commitTx(&mut tx).unwrap();
+
+        let mut reader = db.beginRead().unwrap();
+        let list = reader.ExecuteSQLStatments("select orderid,line,product from lines index by orderid >= 1;select id,customer from orders index by id >= 1;".to_string()).unwrap();
+        assert_eq!(1,list[0].Rows.len());
+        assert_eq!(vec![Value::INT64(21),Value::INT32(1),Value::BYTES("cup".as_bytes().to_vec())],list[0].Rows[0].Vals);
+        assert_eq!(2,list[1].Rows.len());
+        assert_eq!(vec![Value::INT64(12),Value::NULL],list[1].Rows[0].Vals);
+        assert_eq!(vec![Value::INT64(21),Value::INT64(2)],list[1].Rows[1].Vals);
+        db.endRead(&mut reader);
+    }
+
     fn write(i:u64,db:DBInstance)
     {
         let mut rng = rand::thread_rng();
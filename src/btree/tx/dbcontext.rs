@@ -1,7 +1,14 @@
 use std::{collections::HashMap, sync::{Arc, Mutex, RwLock}};
 
-use crate::btree::{kv::{memorycontext::MemoryContext, node::BNode, nodeinterface::{BNodeFreeListInterface, BNodeReadInterface, BNodeWriteInterface}, ContextError, DB_SIG}, table::table::TableDef, BTREE_PAGE_SIZE};
-use super::{memoryContext::memoryContext, tx::Tx, txinterface::MmapInterface, txreader::TxReader, windowsfileContext::WinMmap, winmmap::Mmap};
+use crate::btree::{kv::{memorycontext::MemoryContext, node::BNode, nodeinterface::{BNodeFreeListInterface, BNodeReadInterface, BNodeWriteInterface}, ContextError, DB_SIG, DB_SIG_V1}, table::table::TableDef, BTREE_PAGE_SIZE};
+use super::{catalog::Catalog, memoryContext::memoryContext, tx::Tx, txinterface::MmapInterface, txreader::TxReader, windowsfileContext::WinMmap, winmmap::Mmap};
+
+// the master page fields and their checksum
+const MASTER_SIZE: usize = 56;
+// where the first versions stored the master page, they had no checksum
+const LEGACY_MASTER_OFFSET: usize = 16;
+// how often a reader process rereads a master page that is being stored
+const MASTER_RETRIES: usize = 100;
 
 pub struct DbContext{
     //mmapObj:&'a mut dyn MmapInterface,
@@ -12,8 +19,9 @@ pub struct DbContext{
     pub freehead: u64, //head of freeelist
     pub version:u64, //verison of db data
     
-    pageflushed: u64, // database size in number of pages
+    pub pageflushed: u64, // database size in number of pages
     nfreelist: u16, //number of pages taken from the free list
+    pub legacy: bool, // loaded with the old signature, the integer keys need migrating
 }
 
 impl From<memoryContext> for DbContext {
@@ -39,30 +47,50 @@ impl DbContext{
             nappend:0,
             freehead:0,
             version : 0, 
+            legacy : false,
         }
     }
 
-    pub fn createReader(&mut self,index:usize,tables: Arc<RwLock<HashMap<Vec<u8>,TableDef>>>)->Result<TxReader,ContextError>
+    pub fn getMmap(&self)->Arc<RwLock<Mmap>>
+    {
+        self.mmapObj.read().unwrap().getMmap()
+    }
+
+    pub fn getContextSize(&self)->usize
+    {
+        self.mmapObj.read().unwrap().getContextSize()
+    }
+
+    pub fn createReader(&mut self,index:usize,tables: Arc<Catalog>,
+        snapshots: Arc<RwLock<HashMap<String,(u64,u64)>>>)->Result<TxReader,ContextError>
+    {  
+        self.createReaderAt(index, self.root, self.version, tables, snapshots)
+    }
+
+    // reader on an older root, which must be pinned by the caller
+    pub fn createReaderAt(&mut self,index:usize,root:u64,version:u64,tables: Arc<Catalog>,
+        snapshots: Arc<RwLock<HashMap<String,(u64,u64)>>>)->Result<TxReader,ContextError>
     {  
         let reader = TxReader::new(
             self.mmapObj.read().unwrap().getMmap().clone(),
-            self.root,
+            root,
             self.mmapObj.read().unwrap().getContextSize(),
-            self.version,
+            version,
             index,
-            tables
+            tables,
+            snapshots
         );
 
         Ok(reader)
     }
 
-    pub fn createTx(&mut self)->Result<Tx,ContextError>
+    pub fn createTx(&mut self,minReader:u64)->Result<Tx,ContextError>
     {
         let tx = Tx::new(self.mmapObj.read().unwrap().getMmap().clone(),
             self.root,self.pageflushed,            
             self.mmapObj.read().unwrap().getContextSize() as usize, 
             self.freehead,
-            self.version, self.version
+            self.version, minReader
         );
 
         Ok(tx)
@@ -70,8 +98,8 @@ impl DbContext{
 
     // the master page format.
     // it contains the pointer to the root and other important bits.
-    //| sig | btree_root | page_used | free_list | version |
-    //| 16B | 8B | 8B | 8B | 8B |    
+    //| sig | btree_root | page_used | free_list | version | checksum |
+    //| 16B | 8B | 8B | 8B | 8B | 8B |    
     pub fn masterload(&mut self)->Result<(),ContextError>
     {
         //Init Db file
@@ -116,75 +144,87 @@ impl DbContext{
         }
 
         //Load Db File
+        let master = self.readMaster(0);
+        // the first versions stored the master page 16 bytes in, always with the old signature
+        let moved = self.readMaster(LEGACY_MASTER_OFFSET);
+        if master[0..16] != *DB_SIG && master[0..16] != *DB_SIG_V1 && moved[0..16] == *DB_SIG_V1
+        {
+            return self.applyMaster(&moved);
+        }
+        self.applyMaster(&master)
+    }
+
+    fn readMaster(&self,offset:usize)->[u8;MASTER_SIZE]
+    {
+        let mut master:[u8;MASTER_SIZE] = [0;MASTER_SIZE];
         unsafe {
             let mut mmap = self.mmapObj.read().unwrap().getMmap();
             let buffer =  mmap.read().unwrap().ptr;
+            std::ptr::copy_nonoverlapping(buffer.add(offset), master.as_mut_ptr(), MASTER_SIZE);
+        }
+        master
+    }
 
-            //read sig
-            for i in 0..16
-            {
-                if *buffer.add(i) != DB_SIG[i]
-                {
-                    return Err(ContextError::NotDataBaseFile);
-                }
-            }
+    // a zero checksum is left by the versions that didn't store one
+    fn masterChecked(master:&[u8;MASTER_SIZE])->bool
+    {
+        let checksum = u64::from_le_bytes(master[48..56].try_into().unwrap());
+        checksum == 0 || checksum == Self::masterChecksum(&master[16..48])
+    }
 
-            let mut pos: usize = 16;
-            let mut content:[u8;8] = [0;8];
-            
-            //read root
-            for i in 0..8
-            {
-                content[i] = *buffer.add(i+ pos);
-            }
-            let root = u64::from_le_bytes(content[0..8].try_into().unwrap());
+    // FNV-1a over the fields after the signature, never zero
+    fn masterChecksum(data:&[u8])->u64
+    {
+        let mut hash:u64 = 0xcbf29ce484222325;
+        for b in data
+        {
+            hash ^= *b as u64;
+            hash = hash.wrapping_mul(0x100000001b3);
+        }
+        hash.max(1)
+    }
 
-            //read flushed page
-            pos = 24;
-            for i in 0..8
-            {
-                content[i] = *buffer.add(i+ pos);
-            }
-            let used = u64::from_le_bytes(content[0..8].try_into().unwrap());
+    fn applyMaster(&mut self,master:&[u8;MASTER_SIZE])->Result<(),ContextError>
+    {
+        //read sig
+        let sig = &master[0..16];
+        let legacy = sig == DB_SIG_V1;
+        if sig != DB_SIG && legacy == false
+        {
+            return Err(ContextError::NotDataBaseFile);
+        }
+        if Self::masterChecked(master) == false
+        {
+            return Err(ContextError::LoadDataException);
+        }
 
-            //read freelist head
-            pos = 32;
-            for i in 0..8
-            {
-                content[i] = *buffer.add(i+ pos);
-            }
-            let freehead = u64::from_le_bytes(content[0..8].try_into().unwrap());
+        let root = u64::from_le_bytes(master[16..24].try_into().unwrap());
+        let used = u64::from_le_bytes(master[24..32].try_into().unwrap());
+        let freehead = u64::from_le_bytes(master[32..40].try_into().unwrap());
+        let version = u64::from_le_bytes(master[40..48].try_into().unwrap());
 
-            //read version
-            pos = 40;
-            for i in 0..8
-            {
-                content[i] = *buffer.add(i+ pos);
-            }
-            let version = u64::from_le_bytes(content[0..8].try_into().unwrap());
-
-            let mut bad: bool = !(1 <= used && used <= (self.mmapObj.read().unwrap().getContextSize() as u64)/ BTREE_PAGE_SIZE as u64);
-            bad = bad || !(0 <= root && root < used);
-            if (bad == true) {
-                return Err(ContextError::LoadDataException);
-            }
-    
-            self.root = root;
-            self.pageflushed = used;
-            self.nfreelist = 0;
-            self.nappend = 0;    
-            self.freehead = freehead;
-            self.version = version;
+        let mut bad: bool = !(1 <= used && used <= (self.mmapObj.read().unwrap().getContextSize() as u64)/ BTREE_PAGE_SIZE as u64);
+        bad = bad || !(0 <= root && root < used);
+        if (bad == true) {
+            return Err(ContextError::LoadDataException);
         }
 
-       Ok(())
+        self.root = root;
+        self.pageflushed = used;
+        self.nfreelist = 0;
+        self.nappend = 0;    
+        self.freehead = freehead;
+        self.version = version;
+        self.legacy = legacy;
+
+        Ok(())
     }
 
     // update the master page. it must be atomic.
     pub fn masterStore(&mut self) {
         unsafe {
             
-            let mut data: [u8;48] = [0;48];
+            let mut data: [u8;MASTER_SIZE] = [0;MASTER_SIZE];
             for i in 0..16
             {
                 data[i] = DB_SIG[i];
@@ -202,18 +242,49 @@ impl DbContext{
             pos = 40;
             data[pos..pos+8].copy_from_slice(&self.version.to_le_bytes());
 
+            // a reader process rereading the page meanwhile sees a mismatch
+            pos = 48;
+            let checksum = Self::masterChecksum(&data[16..48]);
+            data[pos..pos+8].copy_from_slice(&checksum.to_le_bytes());
+
             let mut mmap = self.mmapObj.read().unwrap().getMmap();
             let buffer =  mmap.read().unwrap().ptr;
-            for i in 0..48
+            // same layout masterload reads: sig, root, used pages, freelist head, version
+            for i in 0..MASTER_SIZE
             {
-                *buffer.add(i + 16) = data[i];
+                *buffer.add(i) = data[i];
             }
         }
     }
 
+    // reread the master page stored by another process,
+    // the mapping is extended first if the file has grown
+    pub fn reload(&mut self)->Result<(),ContextError>
+    {
+        // the writer doesn't lock the page, a torn copy is read again
+        for _ in 0..MASTER_RETRIES
+        {
+            let master = self.readMaster(0);
+            if Self::masterChecked(&master)
+            {
+                let used = u64::from_le_bytes(master[24..32].try_into().unwrap());
+                self.mmapObj.write().unwrap().extendPages(used as usize)?;
+                return self.applyMaster(&master);
+            }
+            std::thread::yield_now();
+        }
+        Err(ContextError::LoadDataException)
+    }
+
     pub fn writePages(&mut self,updates:&HashMap<u64,Option<BNode>>,totalPages:usize)->Result<(),ContextError>{
+        self.applyPages(updates, totalPages)?;
+        self.syncPages()
+    }
+
+    // copy the pages into the mapping without syncing them
+    pub fn applyPages(&mut self,updates:&HashMap<u64,Option<BNode>>,totalPages:usize)->Result<(),ContextError>{
 
-        self.mmapObj.write().unwrap().extendPages(totalPages);
+        self.mmapObj.write().unwrap().extendPages(totalPages)?;
 
         for entry in updates
         {
@@ -232,6 +303,10 @@ impl DbContext{
             }
         }
 
+        Ok(())
+    }
+
+    pub fn syncPages(&mut self)->Result<(),ContextError>{
         let ret = self.mmapObj.write().unwrap().syncContext();
         if let Err(err) = ret
         {
@@ -241,11 +316,17 @@ impl DbContext{
         Ok(())
     }
 
-    pub fn SaveMaster(&mut self)->Result<(),ContextError>
+    // account the appended pages, the master page is not touched
+    pub fn advance(&mut self)
     {
         self.pageflushed += self.nappend as u64;
         self.nfreelist = 0;
         self.nappend = 0;
+    }
+
+    pub fn SaveMaster(&mut self)->Result<(),ContextError>
+    {
+        self.advance();
 
         self.masterStore();
 
@@ -253,7 +334,7 @@ impl DbContext{
         let ret = writer.syncContext(); 
         drop(writer);
 
-        Ok(())
+        ret
     }
 
 }
@@ -266,6 +347,53 @@ mod tests {
     use super::*;
     use crate::btree::{btree::request::{DeleteRequest, InsertReqest}, db::{scanner::Scanner, INDEX_ADD, INDEX_DEL}};
 
+    #[test]
+    fn test_master_roundtrip()
+    {
+        let mctx = Arc::new(RwLock::new(memoryContext::new(BTREE_PAGE_SIZE,1000)));
+        let mut context = DbContext::new(mctx.clone());
+        context.masterload().unwrap();
+
+        context.freehead = 1;
+        context.version = 7;
+        context.masterStore();
+
+        let mut reopened = DbContext::new(mctx.clone());
+        reopened.masterload().unwrap();
+        assert_eq!(reopened.root, 1);
+        assert_eq!(reopened.pageflushed, 2);
+        assert_eq!(reopened.freehead, 1);
+        assert_eq!(reopened.version, 7);
+    }
+
+    #[test]
+    fn test_master_reload()
+    {
+        let mut mctx = Arc::new(RwLock::new(memoryContext::new(BTREE_PAGE_SIZE,1000)));
+        let mut writer = DbContext::new(mctx.clone());
+        writer.masterload().unwrap();
+        writer.root = 1;
+        writer.version = 7;
+        writer.masterStore();
+
+        let mut reader = DbContext::new(mctx.clone());
+        reader.reload().unwrap();
+        assert_eq!(7,reader.version);
+
+        //half of a newer master page, as the writer leaves it while storing
+        let mmap = mctx.read().unwrap().getMmap();
+        unsafe {
+            *mmap.read().unwrap().ptr.add(40) = 8;
+        }
+        assert_eq!(true,matches!(reader.reload(),Err(ContextError::LoadDataException)));
+        assert_eq!(7,reader.version);
+
+        writer.version = 8;
+        writer.masterStore();
+        reader.reload().unwrap();
+        assert_eq!(8,reader.version);
+    }
+
     #[test]
     fn test_memorycontext()
     {
@@ -273,16 +401,11 @@ mod tests {
         let mut context = DbContext::new(mctx.clone());
         context.masterload();
 
-        let tables = Arc::new(RwLock::new(HashMap::new()));
-        tables.write().unwrap().insert("@meta".as_bytes().to_vec(),TDEF_META.clone());
-        tables.write().unwrap().insert("@table".as_bytes().to_vec(),TDEF_TABLE.clone());
+        let tables = Arc::new(Catalog::new());
         
-        let tx = context.createTx().unwrap();
+        let tx = context.createTx(context.version).unwrap();
         
-        let mut dbinstance = txwriter{
-            context:tx,
-            tables:tables.clone(),
-        };
+        let mut dbinstance = txwriter::new(tx,tables.clone());
 
         let mut table = TableDef{
             Prefix:0,
@@ -291,7 +414,7 @@ mod tests {
             Cols : vec!["id".as_bytes().to_vec() , "name".as_bytes().to_vec(),"address".as_bytes().to_vec(),"age".as_bytes().to_vec(),"married".as_bytes().to_vec() ] ,
             PKeys : 0,
             Indexes : vec![vec!["address".as_bytes().to_vec() , "married".as_bytes().to_vec()],vec!["name".as_bytes().to_vec()]],
-            IndexPrefixes : vec![],
+            ..Default::default()
         };
 
         let ret = dbinstance.AddTable(&mut table);
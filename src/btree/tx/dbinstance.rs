@@ -1,10 +1,13 @@
-use std::{ops::{Deref, DerefMut}, sync::{Arc, Mutex}};
+use std::{ops::{Deref, DerefMut}, sync::{mpsc::Receiver, Arc, Mutex}, thread, time::Duration};
 use crate::btree::kv::ContextError;
 
-use super::{database::Database, dbcontext::DbContext, shared::Shared, txinterface::TxContent, txreader::{self, TxReader}, txwriter::txwriter};
+use super::{changefeed::ChangeEvent, readerslots::ReaderSlots, readers::{ReaderInfo, ReaderTable}, database::Database, groupcommit::{Durability, GroupCommit}, dbcontext::DbContext, shared::Shared, txinterface::TxContent, txreader::{self, TxReader}, txwriter::txwriter};
 
 pub struct DBInstance {
     inner: Arc<Mutex<Database>>,
+    group: Arc<GroupCommit>,
+    readers: Arc<ReaderTable>, // readers begin and end without locking the database
+    readonly: bool, // a reader process, the commits come from another process
 }
 
 impl From<DbContext> for DBInstance {
@@ -15,31 +18,188 @@ impl From<DbContext> for DBInstance {
 
 impl DBInstance {
     pub fn new(db: Database) -> Self {
+        let readers = db.readerTable();
+        let readonly = db.isReadOnly();
         DBInstance {
             inner: Arc::new(Mutex::new(db)),
+            group: Arc::new(GroupCommit::new(Duration::ZERO)),
+            readers: readers,
+            readonly: readonly,
+        }
+    }
+
+    // open the database file of a writer process for reading,
+    // the versions read here are pinned in the reader slot file shared with the writer
+    pub fn open_reader(context: DbContext,slotsPath:&str) -> Result<Self,ContextError> {
+        let mut db = Database::openReadOnly(context)?;
+        db.attachReaderSlots(Arc::new(ReaderSlots::open(slotsPath)?));
+        Ok(DBInstance::new(db))
+    }
+
+    // let reader processes pin versions through the slot file
+    pub fn share_readers(&self,slotsPath:&str) -> Result<(),ContextError> {
+        let slots = Arc::new(ReaderSlots::open(slotsPath)?);
+        let mut dbinstance =  self.lock().unwrap();
+        dbinstance.attachReaderSlots(slots);
+        drop(dbinstance);
+        Ok(())
+    }
+
+    fn refresh(&self)->Result<u64,ContextError>
+    {
+        let mut dbinstance =  self.lock().unwrap();
+        let ret = dbinstance.refresh();
+        drop(dbinstance);
+        ret
+    }
+
+    // a reader process starts from the latest master page. the writer may commit again
+    // before the slot is taken, then the reader starts over on the newer version
+    fn beginShared(&self,owner:&str)->Result<TxReader,ContextError>
+    {
+        loop {
+            self.refresh()?;
+            let reader = self.readers.begin(owner)?;
+            if self.refresh()? == reader.version
+            {
+                return Ok(reader);
+            }
+            self.readers.end(&reader);
         }
     }
 
     pub fn clone(&self) -> Self {
         DBInstance {
             inner: Arc::clone(&self.inner),
+            group: Arc::clone(&self.group),
+            readers: Arc::clone(&self.readers),
+            readonly: self.readonly,
         }
     }
 
+    // write transactions can run in parallel without the writer lock,
+    // commitTx returns ContextError::TxConflict when the transaction has to be retried.
+    // it starts from the published state and doesn't wait for a commit in progress
     pub fn beginTx(&self)->Result<txwriter,ContextError>
     {
-        let mut dbinstance =  self.lock().unwrap();
-        let mut tx = dbinstance.begin();
-        drop(dbinstance);
-        return tx;
+        if self.readonly
+        {
+            return Err(ContextError::ReadOnly);
+        }
+        let mut tx = txwriter::new(self.readers.createTx(),self.readers.tables());
+        tx.fillfactor = self.readers.fillFactor();
+        Ok(tx)
+    }
+
+    pub fn beginTxWith(&self,durability:Durability)->Result<txwriter,ContextError>
+    {
+        let mut tx = self.beginTx()?;
+        tx.durability = durability;
+        Ok(tx)
     }
 
+    // the pages are synced together with the other commits finishing in the same window,
+    // a Full commit returns after the shared sync is done.
+    // ContextError::NotDurable means the transaction is committed and visible but its sync failed,
+    // it must not be retried. after a failed sync no transaction is committed until a sync succeeds
     pub fn commitTx(&self,tx:&mut txwriter)->Result<(),ContextError>
     {
+        if self.group.failing()
+        {
+            self.checkpoint()?;
+        }
+
+        let durability = tx.durability;
         let mut dbinstance =  self.lock().unwrap();
-        let mut ret = dbinstance.commmit(tx);
+        dbinstance.commitEx(tx, false)?;
+        let seq = self.group.enqueue();
         drop(dbinstance);
-        return ret;
+
+        match durability
+        {
+            Durability::Full => self.waitCommitted(seq),
+            Durability::Normal => {
+                if self.group.syncDue() {
+                    return self.waitCommitted(seq);
+                }
+                self.startFlusher();
+                Ok(())
+            },
+            Durability::None => Ok(()),
+        }
+    }
+
+    // the commit is already published, a failed sync can't undo it
+    fn waitCommitted(&self,seq:u64)->Result<(),ContextError>
+    {
+        self.waitSynced(seq).map_err(|_| ContextError::NotDurable)
+    }
+
+    // after a crash the commits since the last sync are lost, the database opens at the synced master
+    pub fn commitTxWith(&self,tx:&mut txwriter,durability:Durability)->Result<(),ContextError>
+    {
+        tx.durability = durability;
+        self.commitTx(tx)
+    }
+
+    // make every commit so far durable
+    pub fn checkpoint(&self)->Result<(),ContextError>
+    {
+        let dbinstance =  self.lock().unwrap();
+        let seq = self.group.applied();
+        drop(dbinstance);
+        self.waitSynced(seq)
+    }
+
+    fn waitSynced(&self,seq:u64)->Result<(),ContextError>
+    {
+        syncCommits(&self.inner, &self.group, seq)
+    }
+
+    // sync the deferred Normal commits when the interval has passed. the thread holds the
+    // database only while flushing and stops once everything applied is synced
+    fn startFlusher(&self)
+    {
+        if self.group.startFlusher() == false
+        {
+            return;
+        }
+        let inner = Arc::downgrade(&self.inner);
+        let group = Arc::clone(&self.group);
+        thread::spawn(move || {
+            loop {
+                // woken up regularly to follow a shorter sync interval
+                let delay = group.flushDelay();
+                if delay.is_zero() == false
+                {
+                    thread::sleep(delay.min(Duration::from_millis(100)));
+                    continue;
+                }
+                let db = match inner.upgrade() {
+                    Some(db) => db,
+                    None => break,
+                };
+                let seq = group.applied();
+                let _ = syncCommits(&db, &group, seq);
+                drop(db);
+                if group.stopFlusher()
+                {
+                    break;
+                }
+            }
+        });
+    }
+
+    // how often Normal commits sync
+    pub fn setSyncInterval(&self,interval:Duration)
+    {
+        self.group.setSyncInterval(interval);
+    }
+
+    // how long a commit waits for others to share its sync
+    pub fn setCommitWindow(&self,window:Duration)
+    {
+        self.group.setWindow(window);
     }
 
     pub fn abortTx(&self,tx:&mut txwriter)
@@ -51,17 +211,115 @@ impl DBInstance {
 
     pub fn beginRead(&self)->Result<TxReader,ContextError>
     {
-        let mut dbinstance =  self.lock().unwrap();
-        let mut tx = dbinstance.beginread();
+        self.beginRead_as("")
+    }
+
+    // the owner label shows up in active_readers
+    pub fn beginRead_as(&self,owner:&str)->Result<TxReader,ContextError>
+    {
+        if self.readonly
+        {
+            return self.beginShared(owner);
+        }
+        self.readers.begin(owner)
+    }
+
+    pub fn active_readers(&self)->Vec<ReaderInfo>
+    {
+        self.readers.list()
+    }
+
+    // readers older than the age stop pinning pages, their next read fails
+    pub fn set_max_reader_age(&self,age:Option<Duration>)
+    {
+        self.readers.setMaxAge(age);
+    }
+
+    // a page below the fill factor (percent of a page) is merged with a sibling or borrows from one on delete,
+    // for the transactions begun afterwards
+    pub fn set_fillfactor(&self,fillfactor:usize)
+    {
+        self.readers.setFillFactor(fillfactor);
+    }
+
+    // freed pages kept from reuse by the oldest reader or snapshot
+    pub fn blocked_pages(&self)->usize
+    {
+        let dbinstance =  self.lock().unwrap();
+        let count = dbinstance.blockedPages();
         drop(dbinstance);
-        return tx;
+        count
     }
 
     pub fn endRead(&self,tx:&mut TxReader)
+    {
+        self.readers.end(tx);
+    }
+
+    // run f inside a write transaction. the transaction is committed when f returns Ok,
+    // aborted when f returns Err. a panic in f drops the transaction, which aborts it too.
+    // ContextError::NotDurable is returned for a committed transaction whose sync failed
+    pub fn write_tx<T,E,F>(&self,f:F)->Result<T,E>
+        where F: FnOnce(&mut txwriter)->Result<T,E>, E: From<ContextError>
+    {
+        let writer = self.getLocker();
+        let lock = writer.lock().unwrap_or_else(|e| e.into_inner());
+
+        let mut tx = self.beginTx()?;
+        let ret = f(&mut tx);
+        match ret
+        {
+            Ok(v) => {
+                self.commitTx(&mut tx)?;
+                drop(lock);
+                Ok(v)
+            },
+            Err(err) => {
+                self.abortTx(&mut tx);
+                drop(lock);
+                Err(err)
+            }
+        }
+    }
+
+    // run f with a reader, the reader is released when f returns
+    pub fn read_tx<T,F>(&self,f:F)->Result<T,ContextError>
+        where F: FnOnce(&mut TxReader)->T
+    {
+        let mut guard = ReadGuard::new(self)?;
+        Ok(f(&mut guard))
+    }
+
+    // pin the current version under the name until it is released or the database is closed
+    pub fn create_snapshot(&self,name:&str)->Result<(),ContextError>
+    {
+        let mut dbinstance =  self.lock().unwrap();
+        let ret = dbinstance.createSnapshot(name);
+        drop(dbinstance);
+        ret
+    }
+
+    pub fn release_snapshot(&self,name:&str)->Result<(),ContextError>
+    {
+        let mut dbinstance =  self.lock().unwrap();
+        let ret = dbinstance.releaseSnapshot(name);
+        drop(dbinstance);
+        ret
+    }
+
+    // read the database as it was when the snapshot was created
+    pub fn beginRead_at(&self,name:&str)->Result<TxReader,ContextError>
+    {
+        self.readers.beginAt(name)
+    }
+
+    // receive the row changes of every transaction committed from now on
+    pub fn subscribe(&self)->Receiver<ChangeEvent>
     {
         let mut dbinstance =  self.lock().unwrap();
-        let mut ret = dbinstance.endread(tx);
+        let receiver = dbinstance.subscribe();
         drop(dbinstance);
+        receiver
     }
 
     pub fn getLocker(&self)->Shared<()>
@@ -73,6 +331,18 @@ impl DBInstance {
     }
 }
 
+// sync everything applied so far, together with the commits waiting on the same flush
+fn syncCommits(inner:&Mutex<Database>,group:&GroupCommit,seq:u64)->Result<(),ContextError>
+{
+    group.wait(seq, || {
+        let mut dbinstance =  inner.lock().unwrap();
+        let target = group.applied();
+        let ret = dbinstance.flush();
+        drop(dbinstance);
+        (target,ret)
+    })
+}
+
 impl Deref for DBInstance {
     type Target = Mutex<Database>;
 
@@ -86,3 +356,39 @@ impl DerefMut for DBInstance {
         Arc::get_mut(&mut self.inner).expect("Multiple strong references exist")
     }
 }
+
+// keeps a reader registered until it is dropped
+pub struct ReadGuard<'a> {
+    db: &'a DBInstance,
+    reader: TxReader,
+}
+
+impl<'a> ReadGuard<'a> {
+    pub fn new(db:&'a DBInstance)->Result<Self,ContextError> {
+        let reader = db.beginRead()?;
+        Ok(ReadGuard{
+            db:db,
+            reader:reader,
+        })
+    }
+}
+
+impl<'a> Drop for ReadGuard<'a> {
+    fn drop(&mut self) {
+        self.db.endRead(&mut self.reader);
+    }
+}
+
+impl<'a> Deref for ReadGuard<'a> {
+    type Target = TxReader;
+
+    fn deref(&self) -> &Self::Target {
+        &self.reader
+    }
+}
+
+impl<'a> DerefMut for ReadGuard<'a> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.reader
+    }
+}
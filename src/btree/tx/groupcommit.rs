@@ -0,0 +1,183 @@
+use std::{sync::{Condvar, Mutex}, thread, time::{Duration, Instant}};
+
+use crate::btree::kv::ContextError;
+
+// how a commit is made durable.
+// Full: commitTx returns after the pages and the master page are synced.
+// Normal: the pages and the master page are synced once the sync interval has passed,
+//         by the next commit or by a background flush. a crash loses at most one interval.
+// None: nothing is synced, it's left to a later checkpoint.
+// in every case the master page is only stored after the pages it points at are synced,
+// and the pages reachable from the synced master are not reused before the next sync.
+// after a crash the database opens at the last synced master.
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub enum Durability{
+    Full,
+    Normal,
+    None,
+}
+
+// commits finishing within the window share one data sync and one master page sync.
+// the first waiting commit becomes the leader and flushes for all the others.
+pub struct GroupCommit{
+    state:Mutex<GroupState>,
+    cond:Condvar,
+}
+
+struct GroupState{
+    applied:u64, // commits copied into the mapping
+    synced:u64,  // commits made durable
+    syncing:bool,
+    window:Duration,
+    interval:Duration, // sync interval for Normal commits
+    lastFlush:Instant,
+    flusher:bool, // a background flush is scheduled
+    failed:Option<(u64,ContextError)>, // the last failed flush and the commits it covered, until a flush succeeds
+    failures:u64, // number of failed flushes
+}
+
+impl GroupCommit{
+    pub fn new(window:Duration)->Self
+    {
+        GroupCommit{
+            state:Mutex::new(GroupState{
+                applied:0,
+                synced:0,
+                syncing:false,
+                window:window,
+                interval:Duration::from_secs(1),
+                lastFlush:Instant::now(),
+                flusher:false,
+                failed:None,
+                failures:0,
+            }),
+            cond:Condvar::new(),
+        }
+    }
+
+    pub fn setWindow(&self,window:Duration)
+    {
+        self.state.lock().unwrap().window = window;
+    }
+
+    pub fn setSyncInterval(&self,interval:Duration)
+    {
+        self.state.lock().unwrap().interval = interval;
+    }
+
+    // whether a Normal commit should sync now
+    pub fn syncDue(&self)->bool
+    {
+        let state = self.state.lock().unwrap();
+        state.lastFlush.elapsed() >= state.interval
+    }
+
+    // schedule the background flush of the Normal commits, returns false when it's already running
+    pub fn startFlusher(&self)->bool
+    {
+        let mut state = self.state.lock().unwrap();
+        if state.flusher
+        {
+            return false;
+        }
+        state.flusher = true;
+        true
+    }
+
+    // the flusher stops when every applied commit is synced
+    pub fn stopFlusher(&self)->bool
+    {
+        let mut state = self.state.lock().unwrap();
+        if state.synced < state.applied
+        {
+            return false;
+        }
+        state.flusher = false;
+        true
+    }
+
+    // how long until the next Normal sync is due
+    pub fn flushDelay(&self)->Duration
+    {
+        let state = self.state.lock().unwrap();
+        state.interval.saturating_sub(state.lastFlush.elapsed())
+    }
+
+    // register an applied commit, must be called while the database is still locked
+    pub fn enqueue(&self)->u64
+    {
+        let mut state = self.state.lock().unwrap();
+        state.applied += 1;
+        state.applied
+    }
+
+    // whether the last flush failed, the commits applied so far may not be durable
+    pub fn failing(&self)->bool
+    {
+        self.state.lock().unwrap().failed.is_some()
+    }
+
+    // the number of applied commits, must be called while the database is locked
+    pub fn applied(&self)->u64
+    {
+        self.state.lock().unwrap().applied
+    }
+
+    // wait until the commit is durable. flush syncs everything applied so far and
+    // returns the number of applied commits it covers.
+    pub fn wait<F>(&self,seq:u64,flush:F)->Result<(),ContextError>
+        where F: Fn()->(u64,Result<(),ContextError>)
+    {
+        let mut state = self.state.lock().unwrap();
+        // a flush failed before the wait began is retried
+        let failures = state.failures;
+        loop {
+            if let Some((target,err)) = &state.failed
+            {
+                if state.failures > failures && seq <= *target && seq > state.synced
+                {
+                    return Err(err.clone());
+                }
+            }
+            if state.synced >= seq
+            {
+                return Ok(());
+            }
+
+            if state.syncing == false
+            {
+                state.syncing = true;
+                let window = state.window;
+                drop(state);
+
+                if window.is_zero() == false
+                {
+                    thread::sleep(window);
+                }
+                let (target,ret) = flush();
+
+                state = self.state.lock().unwrap();
+                state.syncing = false;
+                match ret
+                {
+                    Ok(_) => {
+                        state.failed = None;
+                        state.lastFlush = Instant::now();
+                        if state.synced < target
+                        {
+                            state.synced = target;
+                        }
+                    },
+                    Err(err) => {
+                        state.failed = Some((target,err));
+                        state.failures += 1;
+                    }
+                }
+                self.cond.notify_all();
+                continue;
+            }
+
+            state = self.cond.wait(state).unwrap();
+        }
+    }
+}
@@ -13,6 +13,9 @@ pub struct memoryContext{
     dwPageSize:usize,
     maxMemoryPageCount:usize,
     data:Vec<u8>,
+    pub syncCount:usize,
+    pub failSync:bool, // the syncs fail, for testing
+    pub syncLimit:Option<usize>, // the syncs fail once syncCount reaches it, for testing
 }
 
 impl memoryContext{
@@ -31,8 +34,20 @@ impl memoryContext{
             dwPageSize:pagesize,
             maxMemoryPageCount:maxPageCount,
             fileSize:0,
+            syncCount:0,
+            failSync:false,
+            syncLimit:None,
         }
     }
+
+    // the file as a crash leaves it, with every page written to the mapping on disk
+    pub fn copy(&self) -> Self
+    {
+        let mut context = memoryContext::new(self.dwPageSize,self.maxMemoryPageCount);
+        context.data[0..self.fileSize].copy_from_slice(&self.data[0..self.fileSize]);
+        context.fileSize = self.fileSize;
+        context
+    }
 }
 
 impl MmapInterface for memoryContext{
@@ -79,6 +94,11 @@ impl MmapInterface for memoryContext{
     }
 
     fn syncContext(&mut self) -> Result<(),crate::btree::kv::ContextError> {
+        if self.failSync || self.syncLimit.map_or(false, |n| self.syncCount >= n)
+        {
+            return Err(ContextError::FlushFileBUffersError);
+        }
+        self.syncCount += 1;
         Ok(())
     }
 }
\ No newline at end of file
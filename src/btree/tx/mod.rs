@@ -14,4 +14,9 @@ pub mod memoryContext;
 pub mod database;
 pub mod txRecord; 
 pub mod dbinstance;
+pub mod groupcommit;
+pub mod changefeed;
+pub mod readers;
+pub mod catalog;
+pub mod readerslots;
 mod shared;
\ No newline at end of file
@@ -0,0 +1,271 @@
+use std::{collections::HashMap, fmt, sync::{atomic::{AtomicUsize, Ordering}, Arc, Mutex, RwLock}, time::{Duration, Instant}};
+
+use crate::btree::kv::ContextError;
+use super::{catalog::Catalog, readerslots::ReaderSlots, tx::Tx, txreader::TxReader, winmmap::Mmap};
+
+// a registered reader
+#[derive(Clone,Debug)]
+pub struct ReaderInfo{
+    pub Index:usize,
+    pub Version:u64,   // the version it reads, pages freed after it can't be reused
+    pub Owner:String,
+    pub Start:Instant,
+    pub Slot:Option<usize>, // the slot shared with other processes
+}
+
+impl ReaderInfo{
+    pub fn new(index:usize,version:u64,owner:&str)->Self
+    {
+        ReaderInfo{
+            Index:index,
+            Version:version,
+            Owner:owner.to_string(),
+            Start:Instant::now(),
+            Slot:None,
+        }
+    }
+
+    pub fn age(&self)->Duration
+    {
+        self.Start.elapsed()
+    }
+}
+
+impl fmt::Display for ReaderInfo{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f,"Reader:{} Owner:{} Version:{} Age:{:?}",self.Index,self.Owner,self.Version,self.age())
+    }
+}
+
+// the registered readers and the latest committed state. it's shared with DBInstance,
+// so readers and write transactions begin without waiting for a commit.
+pub struct ReaderTable{
+    mmap:Arc<RwLock<Mmap>>, // the mapping never moves, its lock is not held by syncs
+    tables: Arc<Catalog>,
+    snapshots: Arc<RwLock<HashMap<String,(u64,u64)>>>,
+    maxAge: Arc<RwLock<Option<Duration>>>, // older readers are invalid and don't pin pages
+    fillfactor:AtomicUsize, // of the write transactions, a page below it is merged or borrows on delete
+    nextId:AtomicUsize,
+    slots:RwLock<Option<Arc<ReaderSlots>>>, // readers of other processes
+    writers:Arc<Mutex<HashMap<usize,u64>>>, // write transactions in progress, by the version they began on
+    state:Mutex<ReaderState>,
+}
+
+// keeps the version a write transaction began on from reuse, until it's committed, aborted or dropped
+pub struct WriterPin{
+    writers:Arc<Mutex<HashMap<usize,u64>>>,
+    index:usize,
+}
+
+impl Drop for WriterPin{
+    fn drop(&mut self) {
+        self.writers.lock().unwrap().remove(&self.index);
+    }
+}
+
+struct ReaderState{
+    root:u64,
+    version:u64,
+    pageflushed:u64,
+    freehead:u64,
+    len:usize, // size of the mapping
+    synced:u64, // version of the last synced master page, a crash goes back to its pages
+    readers:HashMap<usize,ReaderInfo>,
+}
+
+impl ReaderTable{
+    pub fn new(mmap:Arc<RwLock<Mmap>>,tables: Arc<Catalog>,
+        snapshots: Arc<RwLock<HashMap<String,(u64,u64)>>>)->Self
+    {
+        ReaderTable{
+            mmap:mmap,
+            tables:tables,
+            snapshots:snapshots,
+            maxAge:Arc::new(RwLock::new(None)),
+            fillfactor:AtomicUsize::new(crate::btree::BTREE_MIN_FILL_FACTOR),
+            nextId:AtomicUsize::new(0),
+            slots:RwLock::new(None),
+            writers:Arc::new(Mutex::new(HashMap::new())),
+            state:Mutex::new(ReaderState{
+                root:0,
+                version:0,
+                pageflushed:0,
+                freehead:0,
+                len:0,
+                synced:0,
+                readers:HashMap::new(),
+            }),
+        }
+    }
+
+    // the state new transactions start from, set by every commit once its pages are in the mapping
+    pub fn publish(&self,root:u64,version:u64,pageflushed:u64,freehead:u64,len:usize)
+    {
+        let mut state = self.state.lock().unwrap();
+        state.root = root;
+        state.version = version;
+        state.pageflushed = pageflushed;
+        state.freehead = freehead;
+        state.len = len;
+    }
+
+    // the pages freed since this version are not reused before the next sync
+    pub fn setSynced(&self,version:u64)
+    {
+        self.state.lock().unwrap().synced = version;
+    }
+
+    // share the pinned versions with the other processes opening the database
+    pub fn setSlots(&self,slots:Arc<ReaderSlots>)
+    {
+        *self.slots.write().unwrap() = Some(slots);
+    }
+
+    pub fn tables(&self)->Arc<Catalog>
+    {
+        self.tables.clone()
+    }
+
+    // a write transaction on the latest committed root, its snapshot is pinned like a reader's
+    pub fn createTx(&self)->Tx
+    {
+        let minReader = self.minReadVersion();
+        let state = self.state.lock().unwrap();
+        let index = self.nextId.fetch_add(1, Ordering::Relaxed);
+        self.writers.lock().unwrap().insert(index,state.version);
+        let mut tx = Tx::new(self.mmap.clone(),state.root,state.pageflushed,state.len,state.freehead,state.version,minReader);
+        tx.pin = Some(WriterPin{ writers:self.writers.clone(), index:index });
+        tx
+    }
+
+    pub fn begin(self:&Arc<Self>,owner:&str)->Result<TxReader,ContextError>
+    {
+        let mut state = self.state.lock().unwrap();
+        let (root,version) = (state.root,state.version);
+        self.register(&mut state,root,version,owner)
+    }
+
+    // the snapshot stays pinned by the reader even if it's released meanwhile
+    pub fn beginAt(self:&Arc<Self>,name:&str)->Result<TxReader,ContextError>
+    {
+        let mut state = self.state.lock().unwrap();
+        let snapshot = self.snapshots.read().unwrap().get(name).cloned();
+        match snapshot
+        {
+            Some((root,version)) => self.register(&mut state,root,version,name),
+            None => Err(ContextError::SnapshotNotFound),
+        }
+    }
+
+    fn register(self:&Arc<Self>,state:&mut ReaderState,root:u64,version:u64,owner:&str)->Result<TxReader,ContextError>
+    {
+        let slot = match &*self.slots.read().unwrap()
+        {
+            Some(slots) => Some(slots.acquire(version)?),
+            None => None,
+        };
+
+        let index = self.nextId.fetch_add(1, Ordering::Relaxed);
+        let mut reader = TxReader::new(self.mmap.clone(),root,state.len,version,index,
+            self.tables.clone(),self.snapshots.clone());
+
+        let mut info = ReaderInfo::new(index,version,owner);
+        info.Slot = slot;
+        reader.start = info.Start;
+        reader.maxAge = self.maxAge.clone();
+        reader.registry = Some(self.clone());
+        state.readers.insert(index,info);
+        Ok(reader)
+    }
+
+    pub fn end(&self,reader:&TxReader)
+    {
+        let info = self.state.lock().unwrap().readers.remove(&reader.index);
+        if let Some(ReaderInfo{ Slot:Some(slot), .. }) = info
+        {
+            if let Some(slots) = &*self.slots.read().unwrap()
+            {
+                slots.release(slot);
+            }
+        }
+    }
+
+    pub fn setMaxAge(&self,age:Option<Duration>)
+    {
+        *self.maxAge.write().unwrap() = age;
+    }
+
+    // percent of a page, between 1 and 50
+    pub fn setFillFactor(&self,fillfactor:usize)
+    {
+        assert!(fillfactor > 0 && fillfactor <= 50);
+        self.fillfactor.store(fillfactor, Ordering::Relaxed);
+    }
+
+    pub fn fillFactor(&self)->usize
+    {
+        self.fillfactor.load(Ordering::Relaxed)
+    }
+
+    fn isExpired(&self,reader:&ReaderInfo)->bool
+    {
+        match *self.maxAge.read().unwrap()
+        {
+            Some(age) => reader.age() >= age,
+            None => false,
+        }
+    }
+
+    // the oldest version still visible to a valid reader, a write transaction, a snapshot
+    // or the master page on disk
+    pub fn minReadVersion(&self)->u64
+    {
+        let state = self.state.lock().unwrap();
+        let mut minversion = state.version.min(state.synced);
+        for version in self.writers.lock().unwrap().values() {
+            if minversion > *version
+            {
+                minversion = *version
+            }
+        }
+        for reader in state.readers.values() {
+            if self.isExpired(reader)
+            {
+                continue;
+            }
+            if minversion > reader.Version
+            {
+                minversion = reader.Version
+            }
+        }
+        for (_,version) in self.snapshots.read().unwrap().values() {
+            if minversion > *version
+            {
+                minversion = *version
+            }
+        }
+        if let Some(slots) = &*self.slots.read().unwrap()
+        {
+            minversion = slots.minVersion(minversion, *self.maxAge.read().unwrap());
+        }
+        minversion
+    }
+
+    pub fn list(&self)->Vec<ReaderInfo>
+    {
+        let mut list:Vec<ReaderInfo> = self.state.lock().unwrap().readers.values().cloned().collect();
+        list.sort_by_key(|r| r.Index);
+        list
+    }
+
+    pub fn len(&self)->usize
+    {
+        self.state.lock().unwrap().readers.len()
+    }
+
+    // write transactions that have not committed or aborted yet
+    pub fn writers(&self)->usize
+    {
+        self.writers.lock().unwrap().len()
+    }
+}
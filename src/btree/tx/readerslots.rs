@@ -0,0 +1,174 @@
+use std::{fs::{File, OpenOptions}, io::{Read, Seek, SeekFrom, Write}, sync::Mutex, time::{Duration, SystemTime, UNIX_EPOCH}};
+
+use crate::btree::kv::ContextError;
+
+// reader slots shared by the processes opening the same database file.
+// a slot holds the pid of a reader, the version it pins and when it began,
+// the writer keeps every pinned version from being reclaimed.
+pub const MAX_READER_SLOTS: usize = 126;
+const SLOT_SIZE: usize = 24;
+
+pub struct ReaderSlots{
+    file:Mutex<File>,
+}
+
+struct Slot{
+    pid:u32,
+    version:u64,
+    start:u64, // seconds since the epoch
+}
+
+impl ReaderSlots{
+    // the slot file is created next to the database file, e.g. "data.db.readers"
+    pub fn open(path:&str)->Result<Self,ContextError>
+    {
+        let file = OpenOptions::new().read(true).write(true).create(true).open(path)
+            .map_err(|_| ContextError::ReaderSlotError)?;
+        file.lock().map_err(|_| ContextError::ReaderSlotError)?;
+        let len = file.metadata().map_err(|_| ContextError::ReaderSlotError)?.len();
+        if len < (MAX_READER_SLOTS * SLOT_SIZE) as u64
+        {
+            file.set_len((MAX_READER_SLOTS * SLOT_SIZE) as u64).map_err(|_| ContextError::ReaderSlotError)?;
+        }
+        file.unlock().map_err(|_| ContextError::ReaderSlotError)?;
+
+        Ok(ReaderSlots{
+            file:Mutex::new(file),
+        })
+    }
+
+    // pin the version, returns the slot to release
+    pub fn acquire(&self,version:u64)->Result<usize,ContextError>
+    {
+        let mut file = self.file.lock().unwrap();
+        file.lock().map_err(|_| ContextError::ReaderSlotError)?;
+        let ret = Self::readSlots(&mut file).and_then(|slots| {
+            // the slots of readers whose process is gone are taken over
+            match slots.iter().position(|s| s.pid == 0 || Self::isAlive(s.pid) == false)
+            {
+                Some(idx) => {
+                    let slot = Slot{ pid:std::process::id(), version:version, start:Self::now() };
+                    Self::writeSlot(&mut file, idx, &slot).map(|_| idx)
+                },
+                None => Err(ContextError::ReaderSlotsFull),
+            }
+        });
+        _ = file.unlock();
+        ret
+    }
+
+    pub fn release(&self,idx:usize)
+    {
+        let mut file = self.file.lock().unwrap();
+        if file.lock().is_ok()
+        {
+            _ = Self::writeSlot(&mut file, idx, &Slot{ pid:0, version:0, start:0 });
+            _ = file.unlock();
+        }
+    }
+
+    // the oldest version pinned by any process, slots older than maxAge are left out.
+    // the slots of processes that exited without releasing them are reclaimed
+    pub fn minVersion(&self,current:u64,maxAge:Option<Duration>)->u64
+    {
+        let mut file = self.file.lock().unwrap();
+        if file.lock().is_err()
+        {
+            return current;
+        }
+        let mut slots = Self::readSlots(&mut file).unwrap_or_default();
+        for idx in 0..slots.len()
+        {
+            if slots[idx].pid != 0 && Self::isAlive(slots[idx].pid) == false
+            {
+                slots[idx] = Slot{ pid:0, version:0, start:0 };
+                _ = Self::writeSlot(&mut file, idx, &slots[idx]);
+            }
+        }
+        _ = file.unlock();
+
+        let now = Self::now();
+        let mut minversion = current;
+        for slot in slots
+        {
+            if slot.pid == 0
+            {
+                continue;
+            }
+            if let Some(age) = maxAge
+            {
+                if now.saturating_sub(slot.start) >= age.as_secs()
+                {
+                    continue;
+                }
+            }
+            if minversion > slot.version
+            {
+                minversion = slot.version;
+            }
+        }
+        minversion
+    }
+
+    #[cfg(windows)]
+    fn isAlive(pid:u32)->bool
+    {
+        use winapi::{shared::winerror::ERROR_ACCESS_DENIED, um::{errhandlingapi::GetLastError, handleapi::CloseHandle, minwinbase::STILL_ACTIVE, processthreadsapi::{GetExitCodeProcess, OpenProcess}, winnt::PROCESS_QUERY_LIMITED_INFORMATION}};
+        unsafe {
+            let handle = OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, 0, pid);
+            if handle.is_null()
+            {
+                // running under another user
+                return GetLastError() == ERROR_ACCESS_DENIED;
+            }
+            let mut code: u32 = 0;
+            let ret = GetExitCodeProcess(handle, &mut code);
+            CloseHandle(handle);
+            ret != 0 && code == STILL_ACTIVE
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    fn isAlive(pid:u32)->bool
+    {
+        std::path::Path::new(&format!("/proc/{}",pid)).exists()
+    }
+
+    #[cfg(not(any(windows, target_os = "linux")))]
+    fn isAlive(pid:u32)->bool
+    {
+        true
+    }
+
+    fn now()->u64
+    {
+        SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+    }
+
+    fn readSlots(file:&mut File)->Result<Vec<Slot>,ContextError>
+    {
+        let mut data = vec![0u8; MAX_READER_SLOTS * SLOT_SIZE];
+        file.seek(SeekFrom::Start(0)).and_then(|_| file.read_exact(&mut data)).map_err(|_| ContextError::ReaderSlotError)?;
+
+        let mut slots = Vec::with_capacity(MAX_READER_SLOTS);
+        for i in 0..MAX_READER_SLOTS
+        {
+            let pos = i * SLOT_SIZE;
+            slots.push(Slot{
+                pid:u32::from_le_bytes(data[pos..pos+4].try_into().unwrap()),
+                version:u64::from_le_bytes(data[pos+8..pos+16].try_into().unwrap()),
+                start:u64::from_le_bytes(data[pos+16..pos+24].try_into().unwrap()),
+            });
+        }
+        Ok(slots)
+    }
+
+    fn writeSlot(file:&mut File,idx:usize,slot:&Slot)->Result<(),ContextError>
+    {
+        let mut data = [0u8; SLOT_SIZE];
+        data[0..4].copy_from_slice(&slot.pid.to_le_bytes());
+        data[8..16].copy_from_slice(&slot.version.to_le_bytes());
+        data[16..24].copy_from_slice(&slot.start.to_le_bytes());
+        file.seek(SeekFrom::Start((idx * SLOT_SIZE) as u64)).and_then(|_| file.write_all(&data)).map_err(|_| ContextError::ReaderSlotError)
+    }
+}
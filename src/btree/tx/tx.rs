@@ -1,18 +1,41 @@
 use std::{collections::HashMap, sync::{Arc, RwLock}};
-use crate::btree::{kv::{node::{self, BNode}, nodeinterface::{BNodeFreeListInterface, BNodeReadInterface, BNodeWriteInterface}, ContextError, FREE_LIST_CAP_WITH_VERSION}, BTREE_PAGE_SIZE};
-use super::{txfreelist::TxFreeList, txinterface::TxReadContext, txreader::TxReader, winmmap::Mmap};
+use crate::btree::{kv::{node::{self, BNode, BNodeRef}, nodeinterface::{BNodeFreeListInterface, BNodeReadInterface, BNodeWriteInterface}, ContextError, FREE_LIST_CAP_WITH_VERSION}, BTREE_PAGE_SIZE};
+use super::{readers::WriterPin, txfreelist::{FreeListData, TxFreeList}, txinterface::TxReadContext, txreader::TxReader, winmmap::Mmap};
 
 pub struct Tx{
     pub freelist:TxFreeList,
     pub pageflushed: u64, // database size in number of pages
     pub nappend: u16, //number of pages to be appended
     pub root:u64,
+    pub pin:Option<WriterPin>, // set for the transactions begun on the published state
+    undo:Option<TxUndo>, // set while a statement runs
 
     //pub reader:TxReader,
     len:usize,
     data:Arc<RwLock<Mmap>>,
 }
 
+// the state of a transaction saved by a savepoint
+pub struct TxSnapshot{
+    root:u64,
+    nappend:u16,
+    freelist:FreeListData,
+    updates:HashMap<u64,Option<BNode>>,
+    freed:Vec<u64>,
+}
+
+// what a statement changed, a failed statement is undone without copying the pending pages
+struct TxUndo{
+    root:u64,
+    nappend:u16,
+    head:u64,
+    total:usize,
+    offset:usize,
+    popped:Vec<u64>, // free list nodes taken off the front
+    freed:usize,
+    pages:HashMap<u64,Option<Option<BNode>>>, // the pending value before the first change
+}
+
 impl TxReadContext for Tx{
     fn get_root(&self)->u64{
         return self.root;
@@ -31,10 +54,20 @@ impl TxReadContext for Tx{
             },
             Other=>
             {
-                self.getMapped(key)
+                self.getMapped(key).ok()
             },
         }
     }
+
+    fn getRef(&self,key:u64) -> Option<BNodeRef<'_>>
+    {
+        match self.freelist.updates.get(&key)
+        {
+            Some(Some(x)) => Some(BNodeRef::new(x.data())),
+            Some(None) => None,
+            None => self.getMapped(key).ok().map(BNodeRef::from),
+        }
+    }
 }
 
 impl Tx{
@@ -49,11 +82,102 @@ impl Tx{
             pageflushed:pageflushed,
             nappend:0,
             root:root,
+            pin:None,
+            undo:None,
         };
         tx.loadCache();
         tx
     }
 
+    // drop all pending pages and go back to the given root and free list
+    pub fn reset(&mut self,root:u64,pageflushed:u64,filelen:usize,freehead:u64)
+    {
+        self.len = filelen;
+        self.freelist.updates.clear();
+        self.freelist.freed.clear();
+        self.freelist.data = FreeListData::new(freehead);
+        self.pageflushed = pageflushed;
+        self.nappend = 0;
+        self.root = root;
+        self.undo = None;
+        self.loadCache();
+    }
+
+    pub fn snapshot(&self)->TxSnapshot
+    {
+        TxSnapshot{
+            root:self.root,
+            nappend:self.nappend,
+            freelist:self.freelist.data.clone(),
+            updates:self.freelist.updates.clone(),
+            freed:self.freelist.freed.clone(),
+        }
+    }
+
+    pub fn restore(&mut self,snapshot:&TxSnapshot)
+    {
+        self.root = snapshot.root;
+        self.nappend = snapshot.nappend;
+        self.freelist.data = snapshot.freelist.clone();
+        self.freelist.updates = snapshot.updates.clone();
+        self.freelist.freed = snapshot.freed.clone();
+    }
+
+    // log the changes from now on, returns false when a statement is already logged
+    pub fn beginUndo(&mut self)->bool
+    {
+        if self.undo.is_some()
+        {
+            return false;
+        }
+        self.undo = Some(TxUndo{
+            root:self.root,
+            nappend:self.nappend,
+            head:self.freelist.data.head,
+            total:self.freelist.data.total,
+            offset:self.freelist.data.offset,
+            popped:Vec::new(),
+            freed:self.freelist.freed.len(),
+            pages:HashMap::new(),
+        });
+        true
+    }
+
+    // stop logging, the logged changes are undone when rollback is set
+    pub fn endUndo(&mut self,rollback:bool)
+    {
+        let undo = match self.undo.take() {
+            Some(undo) if rollback => undo,
+            _ => return,
+        };
+        self.root = undo.root;
+        self.nappend = undo.nappend;
+        self.freelist.data.head = undo.head;
+        self.freelist.data.total = undo.total;
+        self.freelist.data.offset = undo.offset;
+        let mut nodes = undo.popped;
+        nodes.append(&mut self.freelist.data.nodes);
+        self.freelist.data.nodes = nodes;
+        self.freelist.freed.truncate(undo.freed);
+        for (ptr,prev) in undo.pages
+        {
+            match prev
+            {
+                Some(node) => self.freelist.updates.insert(ptr, node),
+                None => self.freelist.updates.remove(&ptr),
+            };
+        }
+    }
+
+    fn setPage(&mut self,ptr:u64,node:BNode)
+    {
+        let prev = self.freelist.updates.insert(ptr, Some(node));
+        if let Some(undo) = &mut self.undo
+        {
+            undo.pages.entry(ptr).or_insert(prev);
+        }
+    }
+
     // try to remove an item from the tail. returns 0 on failure.
     // the removed pointer must not be reachable by the minimum version reader.
     pub fn GetFreeNode(&mut self)->u64 {
@@ -71,8 +195,15 @@ impl Tx{
         let mut node = self.get(self.freelist.data.nodes[0]).unwrap();
         if  self.freelist.data.offset == node.flnSize() as usize
         {
+            // the list of an older master page may still start with the node,
+            // it's freed like a tree page instead of being reused right away
             let ptrNode = self.freelist.data.nodes.remove(0);
+            if let Some(undo) = &mut self.undo
+            {
+                undo.popped.push(ptrNode);
+            }
             self.freelist.data.total -= 1;
+            self.freelist.freed.push(ptrNode);
 
             self.freelist.data.offset = 0;
             if self.freelist.data.nodes.len() != 0 
@@ -82,13 +213,13 @@ impl Tx{
             else {
                 self.freelist.data.head = 0;
             }
-            return ptrNode;
+            return self.PopFreeNode();
         }
 
         // remove one item from the tail
         assert!(self.freelist.data.offset < node.flnSize() as usize);
         let (ptr,ver) = node.flnPtrWithVersion(self.freelist.data.offset);
-        if Self::versionbefore(ver,self.freelist.minReader)
+        if Self::versionbefore(ver,self.freelist.minReader) == false
         {
             // cannot use; possibly reachable by the minimum version reader.
             return 0;
@@ -128,7 +259,6 @@ impl Tx{
 
         while i < count
         {
-            let mut ptr = self.PopFreeNode();
             let mut newNode = BNode::new(BTREE_PAGE_SIZE);
             //construc new node
             let mut size: usize = self.freelist.freed.len();
@@ -146,14 +276,9 @@ impl Tx{
             }
 
 
-            if ptr != 0
-            {
-                self.useNode(ptr, &newNode);
-            }
-            else {
-                ptr = self.appendNode(&newNode);
-                self.freelist.data.total += 1;
-            }
+            // appended, a page taken from the list would change the head while it's being extended
+            let ptr = self.appendNode(&newNode);
+            self.freelist.data.total += 1;
 
             if let Some(&ptrTail) = self.freelist.data.nodes.last()
             {
@@ -183,7 +308,7 @@ impl Tx{
             for i in (self.freelist.data.offset as usize)..head.flnSize() as usize
             {
                 let (ptr,ver) = head.flnPtrWithVersion(i);
-                newNode.flnSetPtrWithVersion( idx, ptr,self.freelist.version);
+                newNode.flnSetPtrWithVersion( idx, ptr,ver);
                 idx += 1;
             }
             newNode.flnSetTotal(self.freelist.data.total as u64 + count as u64);
@@ -214,8 +339,7 @@ impl Tx{
             self.nappend += 1;
         }
 
-        let newNode = node.clone();
-        self.freelist.updates.insert(ptr, Some(newNode));
+        self.setPage(ptr, node);
 
         ptr
     }
@@ -237,41 +361,97 @@ impl Tx{
         let ptr = self.pageflushed + self.nappend as u64;
         self.nappend += 1;
 
-        self.freelist.updates.insert(ptr, Some(newNode));
+        self.setPage(ptr, newNode);
 
         return ptr;
     }
 
     pub fn useNode(&mut self, ptr: u64, bnode: &BNode) {
 
-        let newNode = bnode.clone();
-        self.freelist.updates.insert(ptr, Some(newNode));
+        self.setPage(ptr, bnode.clone());
     }
 
+    // a page freed by a transaction that began on ver is reachable by the readers up to ver
     fn versionbefore(ver:u64,minReader:u64)->bool
     {
         return ver < minReader;
     }
 
-    
-    fn getMapped(&self,key:u64) -> Option<BNode>
+    // drop the pages freed by the commits lost in a crash, the master page still reaches them.
+    // they are the items newer than its version and the nodes past its pages, returns whether the list changed
+    pub fn trimFreeList(&mut self,version:u64)->bool
     {
-        let offset = key as usize * BTREE_PAGE_SIZE;
-        assert!(offset + BTREE_PAGE_SIZE <= self.len);
-        
-        if let Ok(mmap) = self.data.clone().read(){
+        let mut prev:u64 = 0;
+        let mut ptr = self.freelist.data.head;
+        let mut total:u64 = 0;
+        let mut changed = false;
+        while ptr != 0
+        {
+            let mut size:usize = 0;
+            if ptr < self.pageflushed
+            {
+                let mut node = self.get(ptr).unwrap();
+                while size < node.flnSize() as usize && node.flnPtrWithVersion(size).1 < version
+                {
+                    size += 1;
+                }
+                if size == node.flnSize() as usize
+                {
+                    total += size as u64 + 1;
+                    prev = ptr;
+                    ptr = node.flnNext();
+                    continue;
+                }
+                if size > 0
+                {
+                    node.flnSetHeader(size as u16, 0);
+                    self.useNode(ptr, &node);
+                    total += size as u64 + 1;
+                }
+            }
 
-            let mut newNode = BNode::new(BTREE_PAGE_SIZE);
-            //println!("index:{}",key);
-            newNode.copy_Content(mmap.ptr, offset, BTREE_PAGE_SIZE);
-            drop(mmap);
-            //newNode.copy_Data(&self.data,offset,BTREE_PAGE_SIZE);
-            //newNode.print();
-            return Some(newNode);    
+            if size == 0
+            {
+                if prev == 0
+                {
+                    self.freelist.data.head = 0;
+                }
+                else {
+                    let mut node = self.get(prev).unwrap();
+                    node.flnSetNext(0);
+                    self.useNode(prev, &node);
+                }
+            }
+            changed = true;
+            break;
+        }
 
+        if changed
+        {
+            let head = self.freelist.data.head;
+            if head != 0
+            {
+                let mut node = self.get(head).unwrap();
+                node.flnSetTotal(total);
+                self.useNode(head, &node);
+            }
+            self.freelist.data = FreeListData::new(head);
+            self.loadCache();
         }
-        println!("Get Lock Error!");
-        None
+        changed
+    }
+
+
+    // a copy of the committed page, the mapped one may be reused once the transaction is not pinned anymore
+    fn getMapped(&self,key:u64) -> Result<BNode,ContextError>
+    {
+        let offset = key as usize * BTREE_PAGE_SIZE;
+        assert!(offset + BTREE_PAGE_SIZE <= self.len);
+
+        let mmap = self.data.read().map_err(|_| ContextError::NodeNotFound)?;
+        let mut newNode = BNode::new(BTREE_PAGE_SIZE);
+        newNode.copy_Content(mmap.ptr, offset, BTREE_PAGE_SIZE);
+        Ok(newNode)
     }
 
 
@@ -282,15 +462,16 @@ impl Tx{
             return; 
         }
 
-        let mut node = self.getMapped(self.freelist.data.head).unwrap();
+        let mut node = self.get(self.freelist.data.head).unwrap();
         self.freelist.data.nodes.push(self.freelist.data.head);
         self.freelist.data.total = node.flnGetTotal() as usize;
         //self.freelist.data.offset = node.flnSize() as usize;
         let mut next = node.flnNext();
-        while next != 0
+        // a node past the used pages was appended by a commit lost in a crash
+        while next != 0 && next < self.pageflushed
         {
             self.freelist.data.nodes.push(next);
-            node = self.getMapped(next).unwrap();
+            node = self.get(next).unwrap();
             next = node.flnNext();
         }        
     }
@@ -72,4 +72,18 @@ impl DataTable{
             Rows: Vec::new(),
         }
     }
+
+    // the type of a column is taken from its first value that is not NULL
+    pub fn setTypes(&mut self){
+        self.Types.clear();
+        if self.Rows.len() == 0
+        {
+            return;
+        }
+        for i in 0..self.Rows[0].Vals.len()
+        {
+            let v = self.Rows.iter().map(|r| &r.Vals[i]).find(|v| v.isNull() == false);
+            self.Types.push(v.map_or(ValueType::BYTES, |v| v.GetValueType()));
+        }
+    }
 }
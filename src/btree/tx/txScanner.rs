@@ -59,9 +59,15 @@ impl<'a> TxScanner<'a> {
    pub fn Valid(&self)-> bool {
            if self.ininter.Valid() == true
            {
+                // an open range ends with the table or the index
+                let (key,_) = self.ininter.Deref();
+                if key.len() < 4 || key[0..4] != self.keyStart[0..4]
+                {
+                    return false;
+                }
+
                 if self.Cmp2.is_some()
                 {
-                    let (key,_) = self.ininter.Deref();
                     return crate::btree::scan::comp::cmpOK(key, &self.keyEnd.as_ref().unwrap(), &self.Cmp2.unwrap());
                 }
                 else {
@@ -1,11 +1,11 @@
-use crate::btree::{kv::{node::BNode, nodeinterface::BNodeReadInterface}, BTreeError};
+use crate::btree::{kv::{node::BNodeRef, nodeinterface::BNodeReadInterface}, BTreeError};
 
 use super::txinterface::TxReadContext;
 
 
 pub struct TxBIter<'a>{
     context: &'a dyn TxReadContext,    
-    pub path: Vec<BNode>, // from root to leaf
+    pub path: Vec<BNodeRef<'a>>, // from root to leaf
     pub pos: Vec<usize>, //indexes into nodes
     pub valid: bool,
 }
@@ -31,6 +31,16 @@ impl<'a> TxBIter<'a> {
         return (n.get_key(*self.pos.last().unwrap() as u16), n.get_val(*self.pos.last().unwrap() as u16))
     }
 
+    // the page is copied before the check, so it was still pinned when read
+    fn checkExpired(&mut self) -> Result<(),BTreeError> {
+        if let Err(err) = self.context.checkExpired()
+        {
+            self.valid = false;
+            return Err(err);
+        }
+        Ok(())
+    }
+
     pub fn Prev(&mut self) -> bool {
         
         let ret = self.interPrev(self.path.len() - 1);
@@ -71,7 +81,7 @@ impl<'a> TxBIter<'a> {
             // move to a slibing node
             _ = self.path.pop();
             _ = self.pos.pop();
-            self.interPrev(level - 1);
+            self.interPrev(level - 1)?;
         } 
         else {
             self.valid = false;
@@ -82,7 +92,8 @@ impl<'a> TxBIter<'a> {
         {
             // update the kid node
             let idx = self.path[level].get_ptr(self.pos[level]);
-            let kid = self.context.get(idx).unwrap();
+            let kid = self.context.getRef(idx).unwrap();
+            self.checkExpired()?;
             let pos = kid.nkeys() as usize -1;
 
             self.path.push(kid);
@@ -100,7 +111,7 @@ impl<'a> TxBIter<'a> {
         } 
         else if level > 0 
         { // move to a slibing node
-            self.interNext(level - 1);
+            self.interNext(level - 1)?;
             //_ = self.path.pop();
             //_ = self.pos.pop();
         } 
@@ -114,7 +125,8 @@ impl<'a> TxBIter<'a> {
         {
             // update the kid node
             let idx = self.path[level].get_ptr(self.pos[level]);
-            let kid = self.context.get(idx).unwrap();
+            let kid = self.context.getRef(idx).unwrap();
+            self.checkExpired()?;
             //kid.print();
 
             //std.debug.print("First Node {s} {s}\n", .{ kid.getKey(0), kid.getValue(0) });
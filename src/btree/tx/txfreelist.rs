@@ -2,6 +2,7 @@ use std::{collections::HashMap, sync::{Arc, RwLock}};
 use crate::btree::{kv::{node::{self, BNode}, nodeinterface::{BNodeFreeListInterface, BNodeReadInterface, BNodeWriteInterface}, ContextError, FREE_LIST_CAP_WITH_VERSION}, BTREE_PAGE_SIZE};
 use super::{txinterface::TxReadContext, txreader::TxReader, winmmap::Mmap};
 
+#[derive(Clone)]
 pub struct FreeListData{
     pub head: u64, //head of freeelist
     // cached pointers to list nodes for accessing both ends.
@@ -105,15 +106,15 @@ mod tests {
         tx.UpdateFreeList();
 
         let ptrTail = tx.freelist.data.nodes.last().unwrap();
-        assert_eq!(2,*ptrTail);
+        assert_eq!(15,*ptrTail);
         let nodeTail = tx.get(*ptrTail).unwrap();
         assert_eq!(1,nodeTail.flnSize());
         let nodeSecondTail = tx.get(14).unwrap();
-        assert_eq!(2,nodeSecondTail.flnNext());
+        assert_eq!(15,nodeSecondTail.flnNext());
 
         let nodeHead = tx.get(tx.freelist.data.head).unwrap();
-        assert_eq!(264,nodeHead.flnGetTotal());
-        assert_eq!(1,nodeHead.flnSize());
+        assert_eq!(266,nodeHead.flnGetTotal());
+        assert_eq!(2,nodeHead.flnSize());
 
     }
 
@@ -121,7 +122,7 @@ mod tests {
     fn test_freelist_popnode()
     {   
         let mut data: Vec<u8> = vec![0; BTREE_PAGE_SIZE*15];
-        let mut tx = preparenormalcase(&mut data,1);
+        let mut tx = preparenormalcase(&mut data,6);
 
         //pop one node
         let ptr = tx.GetFreeNode();
@@ -134,29 +135,18 @@ mod tests {
         assert_eq!(10,tx.freelist.data.total);
         assert_eq!(2,tx.freelist.data.offset);
 
-        let ptr = tx.GetFreeNode();
-        assert_eq!(11,ptr);
-        assert_eq!(9,tx.freelist.data.total);
-        assert_eq!(0,tx.freelist.data.offset);
-        assert_eq!(12,tx.freelist.data.head);
-
+        //the emptied node is freed, not reused
         let ptr = tx.GetFreeNode();
         assert_eq!(4,ptr);
         assert_eq!(8,tx.freelist.data.total);
         assert_eq!(1,tx.freelist.data.offset);
         assert_eq!(12,tx.freelist.data.head);
+        assert_eq!(vec![11],tx.freelist.freed);
 
         let ptr = tx.GetFreeNode();  //5
-        let ptr = tx.GetFreeNode();  //12
         let ptr = tx.GetFreeNode();  //6
         let ptr = tx.GetFreeNode();  //7
 
-        let ptr = tx.GetFreeNode();  //13
-        assert_eq!(13,ptr);
-        assert_eq!(3,tx.freelist.data.total);
-        assert_eq!(0,tx.freelist.data.offset);
-        assert_eq!(14,tx.freelist.data.head);
-
         let ptr = tx.GetFreeNode();  //8
         assert_eq!(8,ptr);
         assert_eq!(2,tx.freelist.data.total);
@@ -169,11 +159,50 @@ mod tests {
         assert_eq!(2,tx.freelist.data.offset);
         assert_eq!(14,tx.freelist.data.head);
 
-        let ptr = tx.GetFreeNode();  //9
-        assert_eq!(14,ptr);
+        let ptr = tx.GetFreeNode();
+        assert_eq!(0,ptr);
         assert_eq!(0,tx.freelist.data.total);
         assert_eq!(0,tx.freelist.data.offset);
         assert_eq!(0,tx.freelist.data.head);
+        assert_eq!(vec![11,12,13,14],tx.freelist.freed);
+
+        //the pages freed at version 4 and later are reachable by the reader
+        let mut data: Vec<u8> = vec![0; BTREE_PAGE_SIZE*15];
+        let mut tx = preparenormalcase(&mut data,4);
+        for ptr in [2,3,4,5]
+        {
+            assert_eq!(ptr,tx.GetFreeNode());
+        }
+        assert_eq!(0,tx.GetFreeNode());
+        assert_eq!(13,tx.freelist.data.head);
+        assert_eq!(0,tx.freelist.data.offset);
+    }
+
+    #[test]
+    fn test_freelist_trim()
+    {
+        //nothing newer than the master page
+        let mut data: Vec<u8> = vec![0; BTREE_PAGE_SIZE*15];
+        let mut tx = preparenormalcase(&mut data,6);
+        assert_eq!(false,tx.trimFreeList(6));
+        assert_eq!(0,tx.freelist.updates.len());
+
+        //the items of versions 4 and 5 were freed by lost commits
+        let mut data: Vec<u8> = vec![0; BTREE_PAGE_SIZE*15];
+        let mut tx = preparenormalcase(&mut data,6);
+        assert_eq!(true,tx.trimFreeList(4));
+        assert_eq!(vec![11,12],tx.freelist.data.nodes);
+        assert_eq!(6,tx.freelist.data.total);
+        assert_eq!(0,tx.get(12).unwrap().flnNext());
+        assert_eq!(6,tx.get(11).unwrap().flnGetTotal());
+
+        //a node past the used pages was appended by a lost commit
+        let mut data: Vec<u8> = vec![0; BTREE_PAGE_SIZE*15];
+        let mut tx = preparenormalcase(&mut data,6);
+        tx.pageflushed = 14;
+        assert_eq!(true,tx.trimFreeList(6));
+        assert_eq!(vec![11,12,13],tx.freelist.data.nodes);
+        assert_eq!(9,tx.freelist.data.total);
     }
 
     #[test]
@@ -247,6 +276,49 @@ mod tests {
         assert_eq!("Val1".as_bytes(),v);
     }
 
+    #[test]
+    fn test_undo()
+    {
+        let mut data: Vec<u8> = vec![0; BTREE_PAGE_SIZE*15];
+        let mut tx = preparenormalcase(&mut data,6);
+        let mut n: BNode = tx.get(1).unwrap();
+        n.set_header(BNODE_NODE, 1);
+        n.node_append_kv(0, 0, "Key1".as_bytes(), "Val1".as_bytes());
+        tx.useNode(1, &n);
+        assert_eq!(2,tx.GetFreeNode());
+        let state = |tx:&Tx| (tx.root,tx.nappend,tx.freelist.data.clone(),tx.freelist.freed.clone(),tx.get(1).unwrap().nkeys(),tx.freelist.updates.len());
+        let before = state(&tx);
+
+        //the statement empties a list node, changes a pending page and adds new ones
+        assert_eq!(true,tx.beginUndo());
+        assert_eq!(false,tx.beginUndo());
+        for _ in 0..3
+        {
+            tx.GetFreeNode();
+        }
+        let mut m: BNode = tx.get(1).unwrap();
+        m.set_header(BNODE_NODE, 0);
+        tx.useNode(1, &m);
+        let ptr = tx.add(n.clone());
+        tx.appendNode(&n);
+        tx.del(ptr);
+        tx.set_root(ptr);
+        assert_eq!(13,tx.freelist.data.head);
+        tx.endUndo(true);
+
+        let after = state(&tx);
+        assert_eq!((before.0,before.1,before.4,before.5),(after.0,after.1,after.4,after.5));
+        assert_eq!((before.2.head,before.2.nodes,before.2.total,before.2.offset),(after.2.head,after.2.nodes,after.2.total,after.2.offset));
+        assert_eq!(before.3,after.3);
+        assert_eq!(3,tx.GetFreeNode());
+
+        //kept when the statement succeeds
+        assert_eq!(true,tx.beginUndo());
+        tx.useNode(1, &m);
+        tx.endUndo(false);
+        assert_eq!(0,tx.get(1).unwrap().nkeys());
+    }
+
     fn prepaircase_nonefreelist(data:&mut Vec<u8>)->Tx
     {
         //master
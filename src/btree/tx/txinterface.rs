@@ -1,6 +1,6 @@
 use std::{collections::HashMap, sync::{Arc, MutexGuard, RwLock}};
 
-use crate::btree::{btree::request::{DeleteRequest, InsertReqest}, db::scanner::Scanner, kv::{node::BNode, ContextError}, scan::{biter::BIter, comp::OP_CMP}, table::{record::Record, table::TableDef}, BTreeError};
+use crate::btree::{btree::request::{DeleteRequest, InsertReqest}, db::scanner::Scanner, kv::{node::{BNode, BNodeRef}, ContextError}, scan::{biter::BIter, comp::OP_CMP}, table::{record::Record, table::TableDef}, BTreeError};
 use super::{tx::Tx, txScanner::TxScanner, txbiter::TxBIter, txreader::{self, TxReader}, txwriter::txwriter, winmmap::Mmap};
 
 pub trait TxReaderInterface {
@@ -18,6 +18,13 @@ pub trait TxInterface {
 pub trait TxReadContext{
     fn get_root(&self)->u64;
     fn get(&self,key:u64) ->  Option<BNode>;
+    // a pending page is read in place, a committed one is copied
+    fn getRef(&self,key:u64) -> Option<BNodeRef<'_>>;
+    // an expired reader doesn't pin its pages, what it reads afterwards may have been reused
+    fn checkExpired(&self)->Result<(),BTreeError>
+    {
+        Ok(())
+    }
 }
 
 pub trait TxWriteContext{
@@ -31,7 +38,7 @@ pub trait TxContent{
     fn open(&mut self)->Result<(),ContextError>;
     fn begin(& mut self)->Result<txwriter,ContextError>;
     fn commmit(& mut self, tx:&mut txwriter)->Result<(),ContextError>;
-    fn abort(& mut self,tx:&txwriter);
+    fn abort(& mut self,tx:&mut txwriter);
     fn beginread(&mut self)->Result<TxReader,ContextError>;
     fn endread(&mut self, reader:& TxReader);
 }
@@ -1,19 +1,24 @@
-use std::{collections::HashMap, sync::{Arc, RwLock, RwLockReadGuard}};
+use std::{collections::HashMap, sync::{Arc, RwLock, RwLockReadGuard}, time::{Duration, Instant}};
 
-use crate::btree::{db::TDEF_TABLE, kv::{node::BNode, nodeinterface::{BNodeReadInterface, BNodeWriteInterface}}, parser::{expr::Expr, lib::Parser, select::SelectExpr, statement::{ExprSQL, ExprSQLList, SQLExpr}}, scan::{self, biter::BIter, comp::OP_CMP}, table::{record::Record, table::TableDef, value::Value}, BTreeError, BTREE_PAGE_SIZE};
-use super::{txRecord::{DataRow, DataTable}, txScanner::TxScanner, txbiter::TxBIter, txinterface::{DBReadInterface, TxReadContext, TxReaderInterface}, winmmap::Mmap};
+use crate::btree::{db::TDEF_TABLE, kv::{node::{BNode, BNodeRef}, ContextError, nodeinterface::{BNodeReadInterface, BNodeWriteInterface}}, parser::{expr::Expr, lib::Parser, select::SelectExpr, statement::{ExprSQL, ExprSQLList, SQLExpr}}, scan::{self, biter::BIter, comp::OP_CMP}, table::{record::Record, table::TableDef, value::Value}, BTreeError, BTREE_PAGE_SIZE};
+use super::{catalog::Catalog, readers::ReaderTable, txRecord::{DataRow, DataTable}, txScanner::TxScanner, txbiter::TxBIter, txinterface::{DBReadInterface, TxReadContext, TxReaderInterface}, winmmap::Mmap};
 
 pub struct TxReader{
     data:Arc<RwLock<Mmap>>,
-    pub tables: Arc<RwLock<HashMap<Vec<u8>,TableDef>>>,
+    pub tables: Arc<Catalog>,
     root: u64,
     pub version:u64,
     pub index:usize,
-    len:usize
+    len:usize,
+    pub snapshots: Arc<RwLock<HashMap<String,(u64,u64)>>>, // named snapshots, root and version
+    pub start: Instant,
+    pub maxAge: Arc<RwLock<Option<Duration>>>, // older readers are invalid, their pages may be reused
+    pub registry: Option<Arc<ReaderTable>>, // where the reader is registered, it registers the AS OF readers too
 }
 
 impl DBReadInterface for TxReader{
     fn Scan(&self, cmp1: OP_CMP, cmp2: Option<OP_CMP>, key1:&Record, key2:Option<&Record>)->Result<TxScanner,BTreeError> {
+        self.checkExpired()?;
         if let Ok(indexNo) = key1.findIndexes()
         {
             return self.SeekRecord(indexNo, cmp1, cmp2, key1, key2);
@@ -25,7 +30,8 @@ impl DBReadInterface for TxReader{
 }
 
 impl TxReader{
-    pub fn new(data:Arc<RwLock<Mmap>>,root:u64,len:usize,version:u64,index:usize,tables: Arc<RwLock<HashMap<Vec<u8>,TableDef>>>) -> TxReader{
+    pub fn new(data:Arc<RwLock<Mmap>>,root:u64,len:usize,version:u64,index:usize,tables: Arc<Catalog>,
+        snapshots: Arc<RwLock<HashMap<String,(u64,u64)>>>) -> TxReader{
         TxReader{
             data:data,
             len:len,
@@ -33,11 +39,16 @@ impl TxReader{
             version:version,
             index:index,
             tables:tables,
+            snapshots:snapshots,
+            start:Instant::now(),
+            maxAge:Arc::new(RwLock::new(None)),
+            registry:None,
         }
     }
 
     pub fn ExecuteSQLStatments(&mut self,statements:String)->Result<Vec<DataTable>,BTreeError>
     {
+        self.checkExpired()?;
         let mut list = Vec::new();
         let ret = ExprSQLList().parse(&statements);
         if let Ok((ret,sqlExprList)) = ret
@@ -46,10 +57,7 @@ impl TxReader{
             {
                 match &sql1 {
                     SQLExpr::Select(expr) => {
-                        if let Ok(table) = self.ExecuteReader(&expr)
-                        {
-                            list.push(table);
-                        }
+                        list.push(self.ExecuteReader(&expr)?);
                     },
                     expr@Other => {
                        return Err(BTreeError::BadSQLStatement);
@@ -61,6 +69,38 @@ impl TxReader{
     }
 
     pub fn ExecuteReader(&mut self, cmd:&SelectExpr)->Result<DataTable,BTreeError>
+    {
+        self.checkExpired()?;
+        if let Some(name) = &cmd.AsOf
+        {
+            // registered like any reader, so a release of the snapshot meanwhile doesn't free its pages
+            if let Some(registry) = &self.registry
+            {
+                let mut reader = match registry.beginAt(name)
+                {
+                    Ok(reader) => reader,
+                    Err(ContextError::SnapshotNotFound) => return Err(BTreeError::SnapshotNotFound(name.clone())),
+                    Err(err) => return Err(err.into()),
+                };
+                let ret = reader.executeSelect(cmd);
+                registry.end(&reader);
+                return ret;
+            }
+
+            let snapshot = self.snapshots.read().unwrap().get(name).cloned();
+            match snapshot
+            {
+                Some((root,version)) => {
+                    let mut reader = TxReader::new(self.data.clone(), root, self.len, version, self.index, self.tables.clone(), self.snapshots.clone());
+                    return reader.executeSelect(cmd);
+                },
+                None => return Err(BTreeError::SnapshotNotFound(name.clone())),
+            }
+        }
+        self.executeSelect(cmd)
+    }
+
+    fn executeSelect(&mut self, cmd:&SelectExpr)->Result<DataTable,BTreeError>
     {
         let tdef = self.getTableDef(&cmd.Scan.Table.to_vec());
         if tdef.is_none()
@@ -93,6 +133,18 @@ impl TxReader{
             match &mut scanner {
                 Ok(cursor) =>{
                     cursor.into_iter()
+                    .filter(|x| 
+                        {
+                            if let Some(filter) = &cmd.Scan.Filter
+                            {
+                                Self::evalFilterExpr(&filter, &tdef,&x)
+                            }
+                            else {
+                                true
+                            }
+                        })
+                    .skip(cmd.Scan.Offset)
+                    .take(cmd.Scan.Limit)
                     .for_each(|r| 
                         {
                             let mut rc: DataRow = DataRow::new();
@@ -110,13 +162,9 @@ impl TxReader{
             }
         }
 
-        if txTable.Rows.len() > 0
-        {
-            for v in &txTable.Rows.get(0).as_ref().unwrap().Vals
-            {   
-                txTable.Types.push(v.GetValueType());
-            }
-        }
+        // the scan stops at the first page read after the reader expired
+        self.checkExpired()?;
+        txTable.setTypes();
 
         Ok(txTable)
 
@@ -175,7 +223,6 @@ impl TxReader{
             {
                 key2.unwrap().encodeKeyPartial(idxNumber as usize,&mut keyEnd,&cmp2.unwrap());
             }
-            //println!("KeyStart:{:?}  KeyEnd:{:?}",keyStart,keyEnd);
         }
 
         let iter = self.Seek(&keyStart, cmp1);
@@ -201,10 +248,9 @@ impl TxReader{
         let mut iter = TxBIter::new(self);
 
         let mut ptr = self.get_root();
-        let mut n = self.get(ptr).unwrap();
         let mut idx: usize = 0;
         while (ptr != 0) {
-            n = self.get(ptr).unwrap();
+            let n = self.getRef(ptr).unwrap();
             idx = n.nodeLookupLE(key) as usize;
 
             if n.btype() == crate::btree::BNODE_NODE {
@@ -247,18 +293,16 @@ impl TxReader{
 
     pub fn getTableDef(&mut self, name: &[u8]) -> Option<TableDef> {
 
-        if let tbs = self.tables.read().unwrap()
+        // resolved against the snapshot of this reader
+        if let Some(def) = self.tables.get(name,self.version)
         {
-            if let Some(def) = tbs.get(name)
-            {
-                return Some(def.clone());
-            }
+            return Some(def);
         }
 
         let defParsed =  self.getTableDefFromDB(name);
         if let Some(def) = defParsed
         {
-            self.tables.write().unwrap().insert(name.to_vec(), def.clone());
+            self.tables.insert(name,self.version,&def);
             return Some(def);
         }
 
@@ -267,6 +311,16 @@ impl TxReader{
 
    
     
+    fn readPage(&self,key:u64) -> Result<BNode,ContextError>{
+        let offset = key as usize * BTREE_PAGE_SIZE;
+        assert!(offset + BTREE_PAGE_SIZE <= self.len);
+
+        let mmap = self.data.read().map_err(|_| ContextError::NodeNotFound)?;
+        let mut newNode = BNode::new(BTREE_PAGE_SIZE);
+        newNode.copy_Content(mmap.ptr, offset, BTREE_PAGE_SIZE);
+        Ok(newNode)
+    }
+
     // Search a key from the tree
     fn treeSearch<T:BNodeReadInterface>(&self, treenode: &T, key: &[u8]) -> Option<Vec<u8>> {
         // where to find the key?
@@ -286,7 +340,7 @@ impl TxReader{
             },
             crate::btree::BNODE_NODE => {
                 let ptr = treenode.get_ptr(idx as usize);
-                let subNode = self.get(ptr);
+                let subNode = self.getRef(ptr);
                 match subNode{
                     Some(node) => {
                         return self.treeSearch(&node,key);
@@ -303,6 +357,7 @@ impl TxReaderInterface for TxReader{
 
     // get a single row by the primary key
     fn dbGet(&self,rec:&mut Record)->Result<bool,BTreeError> {
+        self.checkExpired()?;
         let bCheck = rec.checkPrimaryKey();
         if bCheck == false {
             return Err(BTreeError::PrimaryKeyIsNotSet);
@@ -324,7 +379,7 @@ impl TxReaderInterface for TxReader{
     }
 
     fn Get(&self, key:&[u8])  -> Option<Vec<u8>> {
-        let rootNode = self.get(self.get_root());
+        let rootNode = self.getRef(self.get_root());
         match rootNode{
             Some(root) => return self.treeSearch(&root,key),
             None => return None
@@ -359,23 +414,24 @@ impl TxReadContext for TxReader{
         return self.root;
     }
 
-    fn get(&self,key:u64) -> Option<BNode>{
-        let offset = key as usize * BTREE_PAGE_SIZE;
-        assert!(offset + BTREE_PAGE_SIZE <= self.len);
-        
-        if let Ok(mmap) = self.data.read(){
+    fn checkExpired(&self)->Result<(),BTreeError>
+    {
+        if let Some(age) = *self.maxAge.read().unwrap()
+        {
+            if self.start.elapsed() >= age
+            {
+                return Err(BTreeError::ReaderExpired);
+            }
+        }
+        Ok(())
+    }
 
-            let mut newNode = BNode::new(BTREE_PAGE_SIZE);
-            //println!("index:{}",key);
-            newNode.copy_Content(mmap.ptr, offset, BTREE_PAGE_SIZE);
-            drop(mmap);
-            //newNode.copy_Data(&self.data,offset,BTREE_PAGE_SIZE);
-            //newNode.print();
-            return Some(newNode);    
+    fn get(&self,key:u64) -> Option<BNode>{
+        self.readPage(key).ok()
+    }
 
-        }
-        println!("Get Lock Error!");
-        None
+    // copied, an expired reader's pages may be reused while it's still reading them
+    fn getRef(&self,key:u64) -> Option<BNodeRef<'_>>{
+        self.readPage(key).ok().map(BNodeRef::from)
     }
 }
-
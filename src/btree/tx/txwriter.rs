@@ -1,11 +1,40 @@
-use std::{collections::HashMap, fmt::Display, sync::{Arc, RwLock}};
+use std::{cell::RefCell, collections::HashMap, fmt::Display, sync::{Arc, RwLock}};
 
-use crate::btree::{btree::request::{DeleteRequest, InsertReqest}, db::{scanner::Scanner, INDEX_ADD, INDEX_DEL, TDEF_META, TDEF_TABLE}, kv::{node::BNode, nodeinterface::{BNodeOperationInterface, BNodeReadInterface, BNodeWriteInterface}}, parser::{delete::DeleteExpr, expr::Expr, insert::InsertExpr, lib::Parser, select::SelectExpr, statement::{ExprSQL, ExprSQLList, SQLExpr, ScanExpr}, update::UpdateExpr}, scan::comp::OP_CMP, table::{record::Record, table::TableDef, value::Value}, BTreeError, MODE_INSERT_ONLY, MODE_UPDATE_ONLY, MODE_UPSERT};
-use super::{tx::{self, Tx}, txRecord::{DataRow, DataTable}, txScanner::{self, TxScanner}, txbiter::TxBIter, txinterface::{DBTxInterface, TxInterface, TxReadContext, TxReaderInterface, TxWriteContext}};
+use crate::btree::{btree::{merge, request::{DeleteRequest, InsertReqest}}, db::{scanner::Scanner, INDEX_ADD, INDEX_DEL, TDEF_META, TDEF_TABLE}, kv::{ContextError, node::BNode, nodeinterface::{BNodeOperationInterface, BNodeReadInterface, BNodeWriteInterface}}, parser::{delete::DeleteExpr, expr::Expr, insert::InsertExpr, lib::Parser, select::SelectExpr, statement::{ExprSQL, ExprSQLList, SQLExpr, ScanExpr}, update::UpdateExpr}, scan::comp::OP_CMP, table::{record::Record, table::TableDef, value::{Value, ValueType}}, BTreeError, MODE_INSERT_ONLY, MODE_UPDATE_ONLY, MODE_UPSERT};
+use super::{catalog::Catalog, changefeed::{ChangeOp, RowChange}, groupcommit::Durability, tx::{self, Tx, TxSnapshot}, txRecord::{DataRow, DataTable}, txScanner::{self, TxScanner}, txbiter::TxBIter, txinterface::{DBTxInterface, TxInterface, TxReadContext, TxReaderInterface, TxWriteContext}};
 
 pub struct txwriter{
     pub context : Tx,
-    pub tables: Arc<RwLock<HashMap<Vec<u8>,TableDef>>>,
+    pub tables: Arc<Catalog>,
+    pub fillfactor: usize,
+    localTables: HashMap<Vec<u8>,TableDef>, // definitions resolved against this transaction
+    createdTables: Vec<Vec<u8>>, // tables created by this transaction, published at commit
+    savepoints: Vec<SavePoint>,
+    // key ranges read by this transaction, None is unbounded. checked at commit
+    reads: RefCell<Vec<(Option<Vec<u8>>,Option<Vec<u8>>)>>,
+    // kv changes in order, None is delete. replayed when the root is changed by others
+    pub writes: Vec<(Vec<u8>,Option<Vec<u8>>)>,
+    pub durability: Durability,
+    pub changes: Vec<RowChange>, // row changes delivered to subscribers after commit
+    pub finished: bool,
+}
+
+struct SavePoint{
+    name:String,
+    state:TxSnapshot,
+    createdTables:usize,
+    writes:usize,
+    changes:usize,
+}
+
+impl Drop for txwriter {
+    fn drop(&mut self) {
+        // a transaction dropped without commit is aborted
+        if self.finished == false
+        {
+            self.rollback();
+        }
+    }
 }
 
 impl DBTxInterface for txwriter{
@@ -25,26 +54,17 @@ impl DBTxInterface for txwriter{
             return Err(BTreeError::PrimaryKeyIsNotSet);
         }
 
-        let mut key = Vec::new();
-        rec.encodeKey(rec.def.Prefix, &mut key);
-
-        let mut request = DeleteRequest::new(&key);
-        let ret = self.Delete(&mut request);
-        if ret == false 
+        // a RESTRICT fails before anything is deleted
+        let mut children = Vec::new();
+        self.findReferences(rec, &mut children)?;
+        for (def,vals) in &children
         {
-            return Ok(false);
-        }
-
-        if rec.def.Indexes.len() == 0  {
-            return Ok(true);
+            let mut child = Record::new(def);
+            child.Vals = vals.clone();
+            self.deleteRow(&child);
         }
 
-        let mut old = Record::new(&rec.def);
-        old.decodeValues(&request.OldValue);
-        old.deencodeKey(&key);
-        self.indexOp(&mut old, INDEX_DEL);
-
-        return Ok(true);
+        Ok(self.deleteRow(rec))
     }
 
     fn AddTable(&mut self, tdef:&mut crate::btree::table::table::TableDef)-> Result<(),crate::btree::BTreeError> {
@@ -62,6 +82,12 @@ impl DBTxInterface for txwriter{
             }
         }
 
+        for i in 0..tdef.ForeignKeys.len()
+        {
+            self.checkForeignKeyDef(tdef, i)?;
+        }
+        tdef.checkExprTypes()?;
+
         assert!(0 == tdef.Prefix);
         let mut rMeta = Record::new(&TDEF_META);
 
@@ -82,6 +108,17 @@ impl DBTxInterface for txwriter{
 
         tdef.Prefix += 1;
 
+        for i in 0..tdef.Cols.len()
+        {
+            if tdef.isAutoIncrement(i)
+            {
+                match &tdef.Types[i] {
+                    ValueType::INT8 | ValueType::INT16 | ValueType::INT32 | ValueType::INT64 => self.setSequenceValue(&tdef.sequenceName(i), 0)?,
+                    _Other => return Err(BTreeError::ValueTypeWrong(String::from_utf8(tdef.Cols[i].to_vec()).unwrap())),
+                }
+            }
+        }
+
         let nPrefix: u32 = tdef.Indexes.len() as u32 + tdef.Prefix as u32 + 1;
         rMeta.Set("val".as_bytes(), Value::BYTES(nPrefix.to_le_bytes().to_vec()));
         self.dbUpdate(&mut rMeta, 0);
@@ -93,11 +130,244 @@ impl DBTxInterface for txwriter{
         rtable.Set("def".as_bytes(), Value::BYTES(str.as_bytes().to_vec()));
         self.dbUpdate(&mut rtable, 0);
 
+        for fk in &tdef.ForeignKeys
+        {
+            self.addReference(&fk.Table, &tdef.Name)?;
+        }
+
+        self.localTables.insert(tdef.Name.clone(), tdef.clone());
+        self.createdTables.push(tdef.Name.clone());
         Ok(())
     }
 
     fn UpdateRecord(&mut self, rec:&mut crate::btree::table::record::Record, mode: u16) -> Result<(),crate::btree::BTreeError> {
+        let checks = rec.def.checkExprs()?;
+        self.writeRecord(rec, mode, &checks)
+    }
+    
+}
+
+impl TxReaderInterface for txwriter{
+
+    // get a single row by the primary key
+    fn dbGet(&self,rec:&mut Record)->Result<bool,BTreeError> {
+        let bCheck = rec.checkPrimaryKey();
+        if bCheck == false {
+            return Err(BTreeError::PrimaryKeyIsNotSet);
+        }
+
+        let mut list:Vec<u8> = Vec::new();
+        rec.encodeKey(rec.def.Prefix,&mut list);
+
+        let val = self.Get(&list);
+        match &val {
+            Some(v)=>{
+                rec.decodeValues(&v);
+                return Ok(true);
+            },
+            Other=>{
+                return Ok(false);
+            }
+        }
+    }
+        
+    fn Get(&self, key:&[u8])  -> Option<Vec<u8>> {
+        self.recordRead(Some(key), Some(key));
+        let rootNode = self.context.getRef(self.context.get_root());
+        match rootNode{
+            Some(root) => return self.treeSearch(&root,key),
+            None => return None
+        }
+    }
+
+    fn Seek(&self, key:&[u8], cmp:crate::btree::scan::comp::OP_CMP) -> super::txbiter::TxBIter {
+        let mut iter = self.SeekLE(key);
+        if iter.Valid() {
+            if let OP_CMP::CMP_LE = cmp  
+            {
+                return iter;
+            }
+
+            let cur = iter.Deref();
+            if crate::btree::scan::comp::cmpOK(cur.0, key, &cmp) == false {
+                //off by one
+                if cmp.value() > 0 {
+                    _ = iter.Next();
+                } else {
+                    _ = iter.Prev();
+                }
+                return iter;
+            }
+        }
+        return iter;
+    }
+}
+
+
+impl TxInterface for txwriter{
+    fn Set(&mut self,req:&mut InsertReqest){
+        //the old value is read by the insert
+        self.recordRead(Some(req.Key), Some(req.Key));
+        self.InsertKV(req);
+        if req.Added || req.Updated
+        {
+            self.writes.push((req.Key.to_vec(),Some(req.Val.to_vec())));
+        }
+    }
+
+    fn Delete(&mut self, req: &mut DeleteRequest) -> bool{
+        self.recordRead(Some(req.Key), Some(req.Key));
+        let ret = self.DeleteKV(req);
+        if ret
+        {
+            self.writes.push((req.Key.to_vec(),None));
+        }
+        ret
+    }
+}
+
+impl txwriter{
+
+    pub fn new(context:Tx,tables:Arc<Catalog>)->Self{
+        txwriter{
+            context:context,
+            tables:tables,
+            fillfactor:crate::btree::BTREE_MIN_FILL_FACTOR,
+            localTables:HashMap::new(),
+            createdTables:Vec::new(),
+            savepoints:Vec::new(),
+            reads:RefCell::new(Vec::new()),
+            writes:Vec::new(),
+            durability:Durability::Full,
+            changes:Vec::new(),
+            finished:false,
+        }
+    }
+
+    // discard everything done by this transaction
+    pub fn rollback(&mut self){
+        self.context.freelist.updates.clear();
+        self.context.freelist.freed.clear();
+        self.context.nappend = 0;
+
+        self.localTables.clear();
+        self.createdTables.clear();
+        self.savepoints.clear();
+        self.reads.borrow_mut().clear();
+        self.writes.clear();
+        self.changes.clear();
+        self.finished = true;
+    }
+
+    fn mark(&self,name:&str)->SavePoint{
+        SavePoint{
+            name:name.to_string(),
+            state:self.context.snapshot(),
+            createdTables:self.createdTables.len(),
+            writes:self.writes.len(),
+            changes:self.changes.len(),
+        }
+    }
+
+    // undo everything done after the mark
+    fn restoreMark(&mut self,sp:&SavePoint){
+        self.context.restore(&sp.state);
+        self.truncate(sp.createdTables, sp.writes, sp.changes);
+    }
+
+    fn truncate(&mut self,createdTables:usize,writes:usize,changes:usize){
+        for name in &self.createdTables[createdTables..]
+        {
+            self.localTables.remove(name);
+        }
+        self.createdTables.truncate(createdTables);
+        self.writes.truncate(writes);
+        self.changes.truncate(changes);
+    }
+
+    // a statement that fails leaves nothing behind, the rows it changed before the error are undone.
+    // the pages are restored from the undo log of the statement, a savepoint would copy them all
+    fn statement<T>(&mut self,f:impl FnOnce(&mut Self)->Result<T,BTreeError>)->Result<T,BTreeError>{
+        let logged = self.context.beginUndo();
+        let (createdTables,writes,changes) = (self.createdTables.len(),self.writes.len(),self.changes.len());
+        let ret = f(self);
+        if logged
+        {
+            self.context.endUndo(ret.is_err());
+            if ret.is_err()
+            {
+                self.truncate(createdTables, writes, changes);
+            }
+        }
+        ret
+    }
+
+    // SAVEPOINT name, a savepoint with the same name is replaced
+    pub fn savepoint(&mut self,name:&str){
+        self.savepoints.retain(|sp| sp.name != name);
+        self.savepoints.push(self.mark(name));
+    }
+
+    // ROLLBACK TO name, the savepoint is kept and the later ones are dropped
+    pub fn rollbackTo(&mut self,name:&str)->Result<(),BTreeError>{
+        let pos = self.findSavePoint(name)?;
+        self.savepoints.truncate(pos + 1);
+
+        let sp = self.savepoints.pop().unwrap();
+        self.restoreMark(&sp);
+        self.savepoints.push(sp);
+        Ok(())
+    }
+
+    // RELEASE name, drop the savepoint and the later ones and keep the changes
+    pub fn release(&mut self,name:&str)->Result<(),BTreeError>{
+        let pos = self.findSavePoint(name)?;
+        self.savepoints.truncate(pos);
+        Ok(())
+    }
+
+    fn findSavePoint(&self,name:&str)->Result<usize,BTreeError>{
+        match self.savepoints.iter().rposition(|sp| sp.name == name)
+        {
+            Some(pos) => Ok(pos),
+            None => Err(BTreeError::SavePointNotFound(name.to_string())),
+        }
+    }
+
+    // the version of the snapshot this transaction works on
+    pub fn version(&self)->u64{
+        self.context.freelist.version
+    }
 
+    // check whether the keys written by another transaction touch anything this transaction has read
+    pub fn conflictsWith(&self,keys:&Vec<Vec<u8>>)->bool{
+        let reads = self.reads.borrow();
+        for key in keys
+        {
+            for (start,end) in reads.iter()
+            {
+                let afterStart = match start {
+                    Some(k) => crate::btree::util::compare_arrays(key, k) >= 0,
+                    None => true,
+                };
+                let beforeEnd = match end {
+                    Some(k) => crate::btree::util::compare_arrays(key, k) <= 0,
+                    None => true,
+                };
+                if afterStart && beforeEnd
+                {
+                    return true;
+                }
+            }
+        }
+        false
+    }
+
+    // UpdateRecord with the CHECK expressions of the table already parsed
+    fn writeRecord(&mut self, rec:&mut Record, mode: u16, checks:&[Expr]) -> Result<(),BTreeError> {
+
+        rec.setNulls();
+        Self::checkConstraints(rec, checks)?;
         let mut bCheck = rec.checkRecord();
         if bCheck == false {
             return Err(BTreeError::ColumnValueMissing);
@@ -112,6 +382,8 @@ impl DBTxInterface for txwriter{
         if bCheck == false {
             return Err(BTreeError::IndexesValueMissing);
         }
+        self.checkUnique(rec)?;
+        self.checkForeignKeys(rec)?;
 
         let mut key:Vec<u8> = Vec::new();
         rec.encodeKey(rec.def.Prefix, &mut key);
@@ -122,6 +394,16 @@ impl DBTxInterface for txwriter{
         let mut request = InsertReqest::new(&key,&v,mode);
         self.Set(&mut request);
 
+        if request.Added {
+            self.captureChange(rec.def, ChangeOp::Insert, &rec.Vals, None, Some(&rec.Vals));
+        }
+        else if request.Updated {
+            let mut old = Record::new(&rec.def);
+            old.decodeValues(&request.OldValue);
+            old.deencodeKey(&key);
+            self.captureChange(rec.def, ChangeOp::Update, &rec.Vals, Some(&old.Vals), Some(&rec.Vals));
+        }
+
         if (rec.def.Indexes.len() == 0) || (request.Updated == false) {
             return Ok(());
         }
@@ -143,76 +425,344 @@ impl DBTxInterface for txwriter{
 
         return Ok(());
     }
-    
-}
 
-impl TxReaderInterface for txwriter{
+    // NOT NULL and CHECK, a CHECK that is NULL passes
+    fn checkConstraints(rec:&Record, checks:&[Expr]) -> Result<(),BTreeError>
+    {
+        for i in 0..rec.def.Cols.len()
+        {
+            if rec.def.isNotNull(i) && (rec.Vals[i] == Value::None || rec.Vals[i].isNull())
+            {
+                return Err(BTreeError::NullNotAllowed(String::from_utf8(rec.def.Cols[i].to_vec()).unwrap()));
+            }
+        }
 
-    // get a single row by the primary key
-    fn dbGet(&self,rec:&mut Record)->Result<bool,BTreeError> {
-        let bCheck = rec.checkPrimaryKey();
-        if bCheck == false {
-            return Err(BTreeError::PrimaryKeyIsNotSet);
+        for (check,expr) in rec.def.Checks.iter().zip(checks)
+        {
+            match expr.eval(rec.def, &rec.Vals)? {
+                Value::BOOL(true) | Value::NULL => {},
+                _Other => return Err(BTreeError::CheckViolation(check.clone())),
+            }
         }
+        Ok(())
+    }
 
-        let mut list:Vec<u8> = Vec::new();
-        rec.encodeKey(rec.def.Prefix,&mut list);
+    // the row and its index entries, the rows referencing it are left to DeleteRecord
+    fn deleteRow(&mut self, rec:&Record) -> bool
+    {
+        let mut key = Vec::new();
+        rec.encodeKey(rec.def.Prefix, &mut key);
 
-        let val = self.Get(&list);
-        match &val {
-            Some(v)=>{
-                rec.decodeValues(&v);
-                return Ok(true);
-            },
-            Other=>{
-                return Ok(false);
+        let mut request = DeleteRequest::new(&key);
+        let ret = self.Delete(&mut request);
+        if ret == false 
+        {
+            return false;
+        }
+
+        let mut old = Record::new(&rec.def);
+        old.decodeValues(&request.OldValue);
+        old.deencodeKey(&key);
+        self.captureChange(rec.def, ChangeOp::Delete, &old.Vals, Some(&old.Vals), None);
+
+        if rec.def.Indexes.len() == 0  {
+            return true;
+        }
+
+        self.indexOp(&mut old, INDEX_DEL);
+
+        return true;
+    }
+
+    // by foreign key, the rows referencing this one, found through the index of the foreign key
+    fn referencingRows(&mut self, rec:&Record) -> Result<Vec<(TableDef,usize,Vec<Vec<Value>>)>,BTreeError>
+    {
+        let mut list = Vec::new();
+        for child in self.referencingTables(&rec.def.Name)?
+        {
+            let def = match self.getTableDef(&child) {
+                Some(def) => def,
+                None => continue,
+            };
+            for i in 0..def.ForeignKeys.len()
+            {
+                let fk = &def.ForeignKeys[i];
+                if fk.Table != rec.def.Name
+                {
+                    continue;
+                }
+
+                let mut key = Record::new(&def);
+                for j in 0..fk.Cols.len()
+                {
+                    let v = rec.Get(&fk.RefCols[j]).ok_or(BTreeError::ColumnNotFound(String::from_utf8_lossy(&fk.RefCols[j]).to_string()))?;
+                    key.Set(&fk.Cols[j], v)?;
+                }
+                let index = def.findSecondaryIndex(&fk.Cols.iter().collect())?;
+                let found: Vec<Vec<Value>> = match self.SeekRecord(index, OP_CMP::CMP_GE, Some(OP_CMP::CMP_LE), &key, Some(&key)) {
+                    Ok(cursor) => cursor.map(|row| row.Vals).collect(),
+                    Err(BTreeError::NextNotFound) => Vec::new(),
+                    Err(err) => return Err(err),
+                };
+                if found.len() > 0
+                {
+                    list.push((def.clone(), i, found));
+                }
             }
         }
+        Ok(list)
     }
-        
-    fn Get(&self, key:&[u8])  -> Option<Vec<u8>> {
-        let rootNode = self.context.get(self.context.get_root());
-        match rootNode{
-            Some(root) => return self.treeSearch(&root,key),
-            None => return None
+
+    // the rows referencing this one, children before their parents
+    fn findReferences(&mut self, rec:&Record, rows:&mut Vec<(TableDef,Vec<Value>)>) -> Result<(),BTreeError>
+    {
+        for (def,i,found) in self.referencingRows(rec)?
+        {
+            if def.ForeignKeys[i].Cascade == false
+            {
+                return Err(BTreeError::ForeignKeyViolation(def.foreignKeyName(i)));
+            }
+            for vals in found
+            {
+                let mut row = Record::new(&def);
+                row.Vals = vals;
+                self.findReferences(&row, rows)?;
+                rows.push((def.clone(), row.Vals));
+            }
         }
+        Ok(())
     }
 
-    fn Seek(&self, key:&[u8], cmp:crate::btree::scan::comp::OP_CMP) -> super::txbiter::TxBIter {
-        let mut iter = self.SeekLE(key);
-        if iter.Valid() {
-            if let OP_CMP::CMP_LE = cmp  
+    // an update changing the primary key moves the row. a RESTRICT fails while rows reference the old key,
+    // on a CASCADE they follow the new one
+    fn updateRow(&mut self, old:Vec<Value>, rec:&mut Record, checks:&[Expr]) -> Result<(),BTreeError>
+    {
+        let pk = rec.def.PKeys as usize + 1;
+        if old[0..pk] == rec.Vals[0..pk]
+        {
+            return self.writeRecord(rec, MODE_UPDATE_ONLY, checks);
+        }
+
+        let mut parent = Record::new(rec.def);
+        parent.Vals = old;
+        let children = self.referencingRows(&parent)?;
+        for (def,i,_) in &children
+        {
+            if def.ForeignKeys[*i].Cascade == false
             {
-                return iter;
+                return Err(BTreeError::ForeignKeyViolation(def.foreignKeyName(*i)));
             }
+        }
 
-            let cur = iter.Deref();
-            if crate::btree::scan::comp::cmpOK(cur.0, key, &cmp) == false {
-                //off by one
-                if cmp.value() > 0 {
-                    _ = iter.Next();
-                } else {
-                    _ = iter.Prev();
+        // the new key must be free, the insert below doesn't replace a row
+        let mut existing = Record::new(rec.def);
+        existing.Vals = rec.Vals.clone();
+        if self.dbGet(&mut existing)?
+        {
+            let cols: Vec<String> = rec.def.Cols[0..pk].iter().map(|c| String::from_utf8_lossy(c).to_string()).collect();
+            return Err(BTreeError::UniqueViolation(format!("{}({})", String::from_utf8_lossy(&rec.def.Name), cols.join(","))));
+        }
+
+        if self.deleteRow(&parent) == false
+        {
+            return Err(BTreeError::RecordNotFound);
+        }
+        self.writeRecord(rec, MODE_INSERT_ONLY, checks)?;
+
+        for (def,i,found) in children
+        {
+            let fk = &def.ForeignKeys[i];
+            let childChecks = def.checkExprs()?;
+            for vals in found
+            {
+                let mut child = Record::new(&def);
+                child.Vals = vals.clone();
+                for j in 0..fk.Cols.len()
+                {
+                    let v = rec.Get(&fk.RefCols[j]).ok_or(BTreeError::ColumnNotFound(String::from_utf8_lossy(&fk.RefCols[j]).to_string()))?;
+                    child.Set(&fk.Cols[j], v)?;
                 }
-                return iter;
+                self.updateRow(vals, &mut child, &childChecks)?;
             }
         }
-        return iter;
+        Ok(())
     }
-}
 
+    // the parent rows are looked up by their primary keys, a key with a NULL references nothing
+    fn checkForeignKeys(&mut self, rec:&Record) -> Result<(),BTreeError>
+    {
+        for i in 0..rec.def.ForeignKeys.len()
+        {
+            let fk = &rec.def.ForeignKeys[i];
+            let vals: Vec<Value> = fk.Cols.iter().map(|c| rec.Get(c).unwrap_or(Value::None)).collect();
+            if vals.iter().any(|v| *v == Value::None || v.isNull())
+            {
+                continue;
+            }
 
-impl TxInterface for txwriter{
-    fn Set(&mut self,req:&mut InsertReqest){
-        self.InsertKV(req);
+            let parent = self.getTableDef(&fk.Table).ok_or(BTreeError::TableNotFind)?;
+            let mut row = Record::new(&parent);
+            for j in 0..vals.len()
+            {
+                row.Set(&fk.RefCols[j], vals[j].clone())?;
+            }
+            if self.dbGet(&mut row)? == false
+            {
+                return Err(BTreeError::ForeignKeyViolation(rec.def.foreignKeyName(i)));
+            }
+        }
+        Ok(())
     }
 
-    fn Delete(&mut self, req: &mut DeleteRequest) -> bool{
-        self.DeleteKV(req)
+    // a foreign key references the primary key of an existing table, and its columns lead a secondary index
+    fn checkForeignKeyDef(&mut self, tdef:&TableDef, idx:usize) -> Result<(),BTreeError>
+    {
+        let fk = &tdef.ForeignKeys[idx];
+        let bad = || BTreeError::BadForeignKey(tdef.foreignKeyName(idx));
+        let parent = self.getTableDef(&fk.Table).ok_or(BTreeError::TableNotFind)?;
+        if fk.Cols.len() != fk.RefCols.len() || fk.RefCols[..] != parent.Cols[0..parent.PKeys as usize + 1]
+        {
+            return Err(bad());
+        }
+        for j in 0..fk.Cols.len()
+        {
+            let col = tdef.GetColumnIndex(&fk.Cols[j]).ok_or(BTreeError::ColumnNotFound(String::from_utf8_lossy(&fk.Cols[j]).to_string()))?;
+            if tdef.Types[col] != parent.Types[j]
+            {
+                return Err(bad());
+            }
+        }
+        if tdef.findSecondaryIndex(&fk.Cols.iter().collect()).is_err()
+        {
+            return Err(bad());
+        }
+        Ok(())
     }
-}
 
-impl txwriter{
+    // the tables with a foreign key to this one are kept in @meta, so a delete finds them without the others' definitions
+    fn referencesKey(name:&[u8]) -> Vec<u8>
+    {
+        let mut key = "references:".as_bytes().to_vec();
+        key.extend_from_slice(name);
+        key
+    }
+
+    fn referencingTables(&self, name:&[u8]) -> Result<Vec<Vec<u8>>,BTreeError>
+    {
+        let mut rMeta = Record::new(&TDEF_META);
+        rMeta.Set("key".as_bytes(), Value::BYTES(Self::referencesKey(name)))?;
+        if let Ok(true) = self.dbGet(&mut rMeta)
+        {
+            if let Some(Value::BYTES(v)) = rMeta.Get("val".as_bytes())
+            {
+                return serde_json::from_slice(&v).map_err(|_| BTreeError::ContextError(ContextError::LoadDataException));
+            }
+        }
+        Ok(Vec::new())
+    }
+
+    fn addReference(&mut self, parent:&[u8], child:&[u8]) -> Result<(),BTreeError>
+    {
+        let mut list = self.referencingTables(parent)?;
+        if list.iter().any(|t| t == child)
+        {
+            return Ok(());
+        }
+        list.push(child.to_vec());
+
+        let mut rMeta = Record::new(&TDEF_META);
+        rMeta.Set("key".as_bytes(), Value::BYTES(Self::referencesKey(parent)))?;
+        rMeta.Set("val".as_bytes(), Value::BYTES(serde_json::to_vec(&list).unwrap()))?;
+        self.dbUpdate(&mut rMeta, MODE_UPSERT)
+    }
+
+    // checked before the row is written, an entry of the row itself is not a duplicate
+    fn checkUnique(&self, rec:&Record) -> Result<(),BTreeError>
+    {
+        for i in 0..rec.def.Indexes.len()
+        {
+            if rec.def.isUniqueIndex(i) == false
+            {
+                continue;
+            }
+            // NULLs are distinct from each other
+            if rec.def.indexColumns(i).iter().any(|c| rec.Get(c).map_or(false, |v| v.isNull()))
+            {
+                continue;
+            }
+
+            let mut prefix = Vec::new();
+            rec.encodeUniqueKey(i, &mut prefix);
+            let mut own = Vec::new();
+            rec.encodeIndex(rec.def.IndexPrefixes[i], i, &mut own);
+            // up to the first key past the prefix
+            let mut end = prefix.clone();
+            while end.last() == Some(&0xff)
+            {
+                end.pop();
+            }
+            if let Some(b) = end.last_mut()
+            {
+                *b += 1;
+            }
+            self.recordRead(Some(&prefix), if end.len() > 0 { Some(&end) } else { None });
+
+            let mut iter = self.Seek(&prefix, OP_CMP::CMP_GE);
+            while iter.Valid()
+            {
+                let (key,_) = iter.Deref();
+                if key.starts_with(&prefix) == false
+                {
+                    break;
+                }
+                if key != &own[..]
+                {
+                    return Err(BTreeError::UniqueViolation(rec.def.indexName(i)));
+                }
+                iter.Next();
+            }
+        }
+        Ok(())
+    }
+
+    // internal tables are not captured
+    fn captureChange(&mut self,tdef:&TableDef,op:ChangeOp,vals:&Vec<Value>,old:Option<&Vec<Value>>,new:Option<&Vec<Value>>){
+        if tdef.Name.starts_with("@".as_bytes())
+        {
+            return;
+        }
+        self.changes.push(RowChange{
+            Table:tdef.Name.clone(),
+            Op:op,
+            Key:vals[0..tdef.PKeys as usize + 1].to_vec(),
+            Old:old.cloned(),
+            New:new.cloned(),
+        });
+    }
+
+    fn recordRead(&self,start:Option<&[u8]>,end:Option<&[u8]>){
+        self.reads.borrow_mut().push((start.map(|k| k.to_vec()),end.map(|k| k.to_vec())));
+    }
+
+    // the created tables are visible to others from the committed version on
+    pub fn publishTables(&mut self,version:u64){
+        for name in &self.createdTables
+        {
+            if let Some(def) = self.localTables.get(name)
+            {
+                self.tables.insert(name, version, def);
+            }
+        }
+        self.createdTables.clear();
+        self.savepoints.clear();
+        self.finished = true;
+    }
+
+    // set the minimum fill factor (percent of a page) used by delete
+    pub fn set_fillfactor(&mut self, fillfactor:usize){
+        assert!(fillfactor > 0 && fillfactor <= 50);
+        self.fillfactor = fillfactor;
+    }
 
     pub fn ExecuteSQLStatments(&mut self,statements:String)->Result<Vec<DataTable>,BTreeError>
     {
@@ -247,8 +797,12 @@ impl txwriter{
             //SQLExpr::Select(expr) => return self.executeSelect(expr),
             SQLExpr::Update(expr) => return self.executeUpdate(expr),
             SQLExpr::Delete(expr) => return self.executeDelete(expr),
-            SQLExpr::Insert(expr) => return self.executeInsert(expr),
+            SQLExpr::Insert(expr) => return self.executeInsert(expr).map(|(count,_)| count),
             SQLExpr::CreatTable(v) => return self.createTable(v),
+            SQLExpr::SavePoint(name) => { self.savepoint(name); return Ok(0); },
+            SQLExpr::RollbackTo(name) => { self.rollbackTo(name)?; return Ok(0); },
+            SQLExpr::Release(name) => { self.release(name)?; return Ok(0); },
+            SQLExpr::CreateSequence(name) => return self.createSequence(name),
             _Other => panic!("Not Supported")
         }
     }
@@ -289,6 +843,10 @@ impl txwriter{
 
     pub fn ExecuteReader(&mut self, cmd:&SelectExpr)->Result<DataTable,BTreeError>
     {
+        if cmd.AsOf.is_some()
+        {
+            return Err(BTreeError::OperationNotSupported("AS OF SNAPSHOT in a write transaction".to_string()));
+        }
         let tdef = self.getTableDef(&cmd.Scan.Table.to_vec());
         if tdef.is_none()
         {
@@ -321,13 +879,7 @@ impl txwriter{
         };
 
         self.search(&tdef, &cmd.Scan, fnProcessRecord);
-        if txTable.Rows.len() > 0
-        {
-            for v in &txTable.Rows.get(0).as_ref().unwrap().Vals
-            {   
-                txTable.Types.push(v.GetValueType());
-            }
-        }
+        txTable.setTypes();
         Ok(txTable)
 
     }
@@ -342,6 +894,11 @@ impl txwriter{
     }
 
     fn executeUpdate(&mut self, cmd:&UpdateExpr)->Result<usize,BTreeError>
+    {
+        self.statement(|tx| tx.updateRows(cmd))
+    }
+
+    fn updateRows(&mut self, cmd:&UpdateExpr)->Result<usize,BTreeError>
     {
         let tdef = self.getTableDef(&cmd.Scan.Table.to_vec());
         if tdef.is_none()
@@ -350,6 +907,7 @@ impl txwriter{
         }
         
         let tdef = tdef.unwrap();
+        let checks = tdef.checkExprs()?;
         let mut list = Vec::new();
         let mut count:usize = 0;
 
@@ -363,20 +921,19 @@ impl txwriter{
 
         for mut r in list
         {
+            let old = r.Vals.clone();
             for i in 0..cmd.Name.len()
             {
-                if let Ok(v) = cmd.Values[i].eval(&tdef,&r.Vals)
+                if let Ok(v) = cmd.Values[i].resolveNextVal(&mut |name| self.nextval(name))?.eval(&tdef,&r.Vals)
                 {
                     if let Err(ex) = r.Set(&cmd.Name[i], v)
                     {
-                        return Err(BTreeError::EvalException);
+                        return Err(ex);
                     }
                 }
             }
-            if let Ok(v) = self.UpdateRecord(&mut r,MODE_UPDATE_ONLY)
-            {
-                 count += 1;
-            }
+            self.updateRow(old, &mut r, &checks)?;
+            count += 1;
         }
 
         Ok(count)
@@ -384,6 +941,11 @@ impl txwriter{
     }
 
     fn executeDelete(&mut self, cmd:&DeleteExpr)->Result<usize,BTreeError>
+    {
+        self.statement(|tx| tx.deleteRows(cmd))
+    }
+
+    fn deleteRows(&mut self, cmd:&DeleteExpr)->Result<usize,BTreeError>
     {
         let tdef = self.getTableDef(&cmd.Scan.Table.to_vec());
         if tdef.is_none()
@@ -402,19 +964,24 @@ impl txwriter{
         };
 
         self.search(&tdef, &cmd.Scan, fnProcessRecord);
-        list.iter().for_each(
-            |row| {
-                if let Ok(true) = self.DeleteRecord(&row)
-                {
-                        count += 1;
-                }
+        for row in &list
+        {
+            if self.DeleteRecord(row)?
+            {
+                count += 1;
             }
-        );
+        }
 
         Ok(count)
     }
 
-    fn executeInsert(&mut self, cmd:&InsertExpr)->Result<usize,BTreeError>
+    // returns the count and, a row at a time, the values of the autoincrement columns
+    pub fn executeInsert(&mut self, cmd:&InsertExpr)->Result<(usize,Vec<Value>),BTreeError>
+    {
+        self.statement(|tx| tx.insertRows(cmd))
+    }
+
+    fn insertRows(&mut self, cmd:&InsertExpr)->Result<(usize,Vec<Value>),BTreeError>
     {
         let tdef = self.getTableDef(&cmd.TableName.to_vec());
         if tdef.is_none()
@@ -422,18 +989,89 @@ impl txwriter{
             return Err(BTreeError::TableNotFind);
         }
 
-        let mut recordes = cmd.createQuest(&tdef.as_ref().unwrap());
+        let tdef = tdef.unwrap();
+        let checks = tdef.checkExprs()?;
+        let mut recordes = cmd.createQuest(&tdef, &mut |name| self.nextval(name))?;
         let mut count:usize = 0;
-        for row in &mut recordes.unwrap()
+        let mut keys = Vec::new();
+        for row in &mut recordes
         {
-            if let Err(err) = self.UpdateRecord(row, MODE_INSERT_ONLY)
+            if let Err(err) = self.writeRecord(row, MODE_INSERT_ONLY, &checks)
             {
                 return Err(err);                
             }
             count += 1;
+
+            for i in 0..tdef.Cols.len()
+            {
+                if tdef.isAutoIncrement(i) == false
+                {
+                    continue;
+                }
+                // a value given explicitly moves the sequence past it
+                let name = tdef.sequenceName(i);
+                if let Some(v) = row.Vals[i].toI64()
+                {
+                    if v > self.sequenceValue(&name).unwrap_or(0)
+                    {
+                        self.setSequenceValue(&name, v)?;
+                    }
+                }
+                keys.push(row.Vals[i].clone());
+            }
         }
 
-        Ok(count)
+        Ok((count,keys))
+    }
+
+    // sequence counters are @meta rows like next_prefix, so they roll back with the transaction
+    fn sequenceKey(name:&[u8]) -> Vec<u8>
+    {
+        let mut key = "sequence:".as_bytes().to_vec();
+        key.extend_from_slice(name);
+        key
+    }
+
+    // the last value allocated, None when there is no such sequence
+    fn sequenceValue(&self, name:&[u8]) -> Option<i64>
+    {
+        let mut rMeta = Record::new(&TDEF_META);
+        rMeta.Set("key".as_bytes(), Value::BYTES(Self::sequenceKey(name))).ok()?;
+        if let Ok(true) = self.dbGet(&mut rMeta)
+        {
+            if let Some(Value::BYTES(v)) = rMeta.Get("val".as_bytes())
+            {
+                return Some(i64::from_le_bytes(v.try_into().unwrap()));
+            }
+        }
+        None
+    }
+
+    fn setSequenceValue(&mut self, name:&[u8], v:i64) -> Result<(),BTreeError>
+    {
+        let mut rMeta = Record::new(&TDEF_META);
+        rMeta.Set("key".as_bytes(), Value::BYTES(Self::sequenceKey(name)))?;
+        rMeta.Set("val".as_bytes(), Value::BYTES(v.to_le_bytes().to_vec()))?;
+        self.dbUpdate(&mut rMeta, MODE_UPSERT)
+    }
+
+    pub fn createSequence(&mut self, name:&str) -> Result<usize,BTreeError>
+    {
+        if self.sequenceValue(name.as_bytes()).is_some()
+        {
+            return Err(BTreeError::SequenceAlreadyExist(name.to_string()));
+        }
+        self.setSequenceValue(name.as_bytes(), 0)?;
+        Ok(1)
+    }
+
+    // nextval('seq'), a new sequence starts at 1
+    pub fn nextval(&mut self, name:&[u8]) -> Result<Value,BTreeError>
+    {
+        let v = self.sequenceValue(name)
+            .ok_or(BTreeError::SequenceNotFound(String::from_utf8_lossy(name).to_string()))? + 1;
+        self.setSequenceValue(name, v)?;
+        Ok(Value::INT64(v))
     }
 
 
@@ -493,7 +1131,21 @@ impl txwriter{
             {
                 key2.unwrap().encodeKeyPartial(idxNumber as usize,&mut keyEnd,&cmp2.unwrap());
             }
-            println!("KeyStart:{:?}  KeyEnd:{:?}",keyStart,keyEnd);
+        }
+
+        if key2.is_some()
+        {
+            if cmp1.value() > 0 {
+                self.recordRead(Some(&keyStart), Some(&keyEnd));
+            } else {
+                self.recordRead(Some(&keyEnd), Some(&keyStart));
+            }
+        }
+        else if cmp1.value() > 0 {
+            self.recordRead(Some(&keyStart), None);
+        }
+        else {
+            self.recordRead(None, Some(&keyStart));
         }
 
         let iter = self.Seek(&keyStart, cmp1);
@@ -565,23 +1217,22 @@ impl txwriter{
     }
 
     pub fn getTableDef(&mut self, name: &[u8]) -> Option<TableDef> {
-        if let tbs = self.tables.clone().read().unwrap()
+        if let Some(def) = self.localTables.get(name)
         {
-            if let Some(def) = tbs.get(name)
-            {
-                return Some(def.clone());
-            }
-            drop(tbs);
+            return Some(def.clone());
+        }
+        if let Some(def) = self.tables.get(name,self.version())
+        {
+            return Some(def);
         }
 
+        // the tables created by this transaction are always in localTables,
+        // anything else found here was committed before it began
         let defParsed =  self.getTableDefFromDB(name);
         if let Some(def) = defParsed
         {
-            if let mut tbs = self.tables.clone().write().unwrap()
-            {
-                tbs.insert(name.to_vec(), def.clone());
-                drop(tbs);
-            }
+            self.tables.insert(name,self.version(),&def);
+            self.localTables.insert(name.to_vec(), def.clone());
             return Some(def);
         }
 
@@ -591,6 +1242,7 @@ impl txwriter{
     // add a row to the table
     fn dbUpdate(&mut self, rec:&mut Record, mode: u16) -> Result<(),BTreeError> {
 
+        rec.setNulls();
         let mut bCheck = rec.checkRecord();
         if bCheck == false {
             return Err(BTreeError::ColumnValueMissing);
@@ -618,10 +1270,9 @@ impl txwriter{
         let mut iter = TxBIter::new(&self.context);
 
         let mut ptr = self.context.get_root();
-        let mut n = self.context.get(ptr).unwrap();
         let mut idx: usize = 0;
         while (ptr != 0) {
-            n = self.context.get(ptr).unwrap();
+            let n = self.context.getRef(ptr).unwrap();
             idx = n.nodeLookupLE(key) as usize;
 
             if n.btype() == crate::btree::BNODE_NODE {
@@ -656,7 +1307,7 @@ impl txwriter{
             },
             crate::btree::BNODE_NODE => {
                 let ptr = treenode.get_ptr(idx as usize);
-                let subNode = self.context.get(ptr);
+                let subNode = self.context.getRef(ptr);
                 match subNode{
                     Some(node) => {
                         return self.treeSearch(&node,key);
@@ -750,96 +1401,9 @@ impl txwriter{
             return None; // not found
         }
         _ = self.context.del(kptr);
-
-        let mut nodeUpdated = updated.unwrap();
-        let mut newNode = BNode::new(crate::btree::BTREE_PAGE_SIZE);
-        // check for merging
-        let (flagMerged,sibling) = self.shouldMerge(treenode, idx, &nodeUpdated);
-        match flagMerged {
-            0 => {
-                assert!(nodeUpdated.nkeys() > 0);
-                let ptr = self.context.add(nodeUpdated);
-
-                let updatedNode = self.context.get(ptr).unwrap();
-                let key = updatedNode.get_key(0);
-                let nodes = vec![(ptr,key.to_vec())]; 
-                newNode.nodeReplaceKidN(treenode, idx,nodes);
-            },
-            -1 => { //left
-                //print!("Merge Left.\n");
-                let mut merged = BNode::new(crate::btree::BTREE_PAGE_SIZE);
-                let nodeMerged = self.context.get(sibling.unwrap());
-                match nodeMerged
-                {
-                    Some(n) => {
-                        merged.nodeMerge(&n, &nodeUpdated);
-                        let prtMerged = self.context.add(merged);
-                        _ = self.context.del(treenode.get_ptr(idx as usize - 1));
-
-                        let nodeMerged = self.context.get(prtMerged).unwrap();
-                        newNode.nodeReplace2Kid(treenode, idx - 1, prtMerged, nodeMerged.get_key(0));
-                    },
-                    None => panic!("Get Node Exception idx: {:?}", sibling)
-                }
-            },
-            1 => { //right
-                //std.debug.print("Merge Right.\n", .{});
-                let mut merged = BNode::new(crate::btree::BTREE_PAGE_SIZE);
-                let nodeMerged = self.context.get(sibling.unwrap());
-                match nodeMerged
-                {
-                    Some(n) => {
-                        merged.nodeMerge( &nodeUpdated,&n);
-                        let prtMerged = self.context.add(merged);
-                        _ = self.context.del(treenode.get_ptr(idx as usize + 1));
-
-                        let nodeMerged = self.context.get(prtMerged).unwrap();
-                        newNode.nodeReplace2Kid(treenode, idx, prtMerged, nodeMerged.get_key(0));
-                    },
-                    None => panic!("Get Node Exception idx: {:?}", sibling)
-                }
-            },
-            other => {
-                panic!("Exception Merge Flag!");
-            },
-        }
-
-        return Some(newNode);
+        return Some(merge::replaceKid(&mut self.context, self.fillfactor, treenode, idx, updated.unwrap()));
     }
 
-    fn shouldMerge<T:BNodeReadInterface>(&self, treenode: &T, idx: u16, updated: &BNode)-> (i16,Option<u64>) {
-        if updated.nbytes() > crate::btree::BTREE_PAGE_SIZE / 4 {
-            return (0, None);
-        }
-
-        if  idx > 0 {
-            let sibling = self.context.get(treenode.get_ptr(idx as usize - 1));
-            match sibling{
-                Some(n) => {
-                    let merged:usize = n.nbytes() as usize + updated.nbytes() as usize - crate::btree::HEADER as usize;
-                    if merged <= crate::btree::BTREE_PAGE_SIZE {
-                        return (-1, Some(treenode.get_ptr(idx as usize - 1)));
-                    }        
-                },
-                None => panic!("Get Node Exception idx: {:?}", idx - 1)
-            }
-
-        }
-        if  idx + 1 < treenode.nkeys() {
-            let sibling = self.context.get(treenode.get_ptr(idx as usize + 1));
-            match sibling{
-                Some(n) => {
-                    let merged:usize = n.nbytes() as usize + updated.nbytes() as usize - crate::btree::HEADER as usize;
-                    if merged <= crate::btree::BTREE_PAGE_SIZE {
-                        return (1, Some(treenode.get_ptr(idx as usize + 1)));
-                    }        
-                },
-                None => panic!("Get Node Exception idx: {:?}", idx - 1)
-            }
-        }
-
-        return (0,None);
-    }
     //Interface for Insert KV
     fn InsertKV(&mut self, request:&mut InsertReqest) {
         assert!(request.Key.len() != 0);
@@ -1056,16 +1620,11 @@ mod tests {
     #[test]
     fn test_database_byIndexes()
     {
-        let tables = Arc::new(RwLock::new(HashMap::new()));
-        tables.write().unwrap().insert("@meta".as_bytes().to_vec(),TDEF_META.clone());
-        tables.write().unwrap().insert("@table".as_bytes().to_vec(),TDEF_TABLE.clone());
+        let tables = Arc::new(Catalog::new());
 
         let mut data: Vec<u8> = vec![0; BTREE_PAGE_SIZE*2];
         let mut tx = prepaircase_nonefreelist_noneNode(&mut data);
-        let mut dbinstance = txwriter{
-            context:tx,
-            tables:tables.clone()
-        };
+        let mut dbinstance = txwriter::new(tx,tables.clone());
 
         let mut table = TableDef{
             Prefix:0,
@@ -1074,7 +1633,7 @@ mod tests {
             Cols : vec!["id".as_bytes().to_vec() , "name".as_bytes().to_vec(),"address".as_bytes().to_vec(),"age".as_bytes().to_vec(),"married".as_bytes().to_vec() ] ,
             PKeys : 0,
             Indexes : vec![vec!["address".as_bytes().to_vec() , "married".as_bytes().to_vec()],vec!["name".as_bytes().to_vec()]],
-            IndexPrefixes : vec![],
+            ..Default::default()
         };
         //table.FixIndexes();
 
@@ -1133,16 +1692,11 @@ mod tests {
     #[test]
     fn test_table()
     {
-        let tables = Arc::new(RwLock::new(HashMap::new()));
-        tables.write().unwrap().insert("@meta".as_bytes().to_vec(),TDEF_META.clone());
-        tables.write().unwrap().insert("@table".as_bytes().to_vec(),TDEF_TABLE.clone());
+        let tables = Arc::new(Catalog::new());
 
         let mut data: Vec<u8> = vec![0; BTREE_PAGE_SIZE*2];
         let mut tx = prepaircase_nonefreelist_noneNode(&mut data);
-        let mut txwriter = txwriter{
-            context:tx,
-            tables:tables.clone()
-        };
+        let mut txwriter = txwriter::new(tx,tables.clone());
 
 
         let mut table = TableDef{
@@ -1152,7 +1706,7 @@ mod tests {
             Cols : vec!["id".as_bytes().to_vec() , "name".as_bytes().to_vec(),"address".as_bytes().to_vec(),"age".as_bytes().to_vec(),"married".as_bytes().to_vec() ] ,
             PKeys : 0,
             Indexes : vec![vec!["address".as_bytes().to_vec() , "married".as_bytes().to_vec()],vec!["age".as_bytes().to_vec()]],
-            IndexPrefixes : vec![],
+            ..Default::default()
         };
         //table.FixIndexes();
 
@@ -1208,11 +1762,8 @@ mod tests {
     {
         let mut data: Vec<u8> = vec![0; BTREE_PAGE_SIZE*2];
         let mut tx = prepaircase_nonefreelist_noneNode(&mut data);
-        let tables = Arc::new(RwLock::new(HashMap::new()));
-        let mut txwriter = txwriter{
-            context:tx,
-            tables:tables.clone()
-        };
+        let tables = Arc::new(Catalog::new());
+        let mut txwriter = txwriter::new(tx,tables.clone());
 
         let mut request = InsertReqest::new("3".as_bytes(), "33333".as_bytes(), crate::btree::MODE_UPSERT);
         txwriter.Set(&mut request);
@@ -1290,11 +1841,8 @@ mod tests {
     {
         let mut data: Vec<u8> = vec![0; BTREE_PAGE_SIZE*2];
         let mut tx = prepaircase_nonefreelist_noneNode(&mut data);
-        let tables = Arc::new(RwLock::new(HashMap::new()));
-        let mut txwriter = txwriter{
-            context:tx,
-            tables:tables.clone(),
-        };
+        let tables = Arc::new(Catalog::new());
+        let mut txwriter = txwriter::new(tx,tables.clone());
         
         let mut request = InsertReqest::new("1".as_bytes(), &[31;2500], crate::btree::MODE_UPSERT);
         txwriter.Set(&mut request);
@@ -1333,12 +1881,9 @@ mod tests {
     {
         let mut data: Vec<u8> = vec![0; BTREE_PAGE_SIZE*2];
         let mut tx = prepaircase_nonefreelist_noneNode(&mut data);
-        let tables = Arc::new(RwLock::new(HashMap::new()));
+        let tables = Arc::new(Catalog::new());
 
-        let mut txwriter = txwriter{
-            context:tx,
-            tables:tables.clone(),
-        };
+        let mut txwriter = txwriter::new(tx,tables.clone());
 
         let mut request = InsertReqest::new("3".as_bytes(), "33333".as_bytes(), crate::btree::MODE_UPSERT);
         txwriter.Set(&mut request);
@@ -1358,6 +1903,97 @@ mod tests {
         assert_eq!("33333".as_bytes(),reqDelete.OldValue);
     }
 
+    #[test]
+    fn test_delete_redistribute()
+    {
+        let mut data: Vec<u8> = vec![0; BTREE_PAGE_SIZE*2];
+        let mut tx = prepaircase_nonefreelist_noneNode(&mut data);
+        let tables = Arc::new(Catalog::new());
+        let mut txwriter = txwriter::new(tx,tables.clone());
+        txwriter.set_fillfactor(40);
+
+        for i in 10..40 {
+            let key = format!("key{}",i);
+            let val = vec![i as u8;500];
+            let mut request = InsertReqest::new(key.as_bytes(), &val, crate::btree::MODE_UPSERT);
+            txwriter.Set(&mut request);
+        }
+
+        //shrink the leftmost leaves, the right siblings are too big to be merged
+        for i in 10..36 {
+            if i % 7 == 0 {
+                continue;
+            }
+            let key = format!("key{}",i);
+            let mut request = DeleteRequest::new(key.as_bytes());
+            assert_eq!(true,txwriter.Delete(&mut request));
+        }
+
+        //the sentinel with key14,21,28 would be below 40% next to a leaf of 5, after borrowing both are above it
+        let root = txwriter.context.get(txwriter.context.get_root()).unwrap();
+        assert_eq!(crate::btree::BNODE_NODE,root.btype());
+        let leaves: Vec<BNode> = (0..root.nkeys()).map(|i| txwriter.context.get(root.get_ptr(i as usize)).unwrap()).collect();
+        assert_eq!(vec![5,4],leaves.iter().map(|n| n.nkeys()).collect::<Vec<u16>>());
+        assert_eq!("key35".as_bytes(),leaves[0].get_key(4));
+        for n in &leaves {
+            assert!(n.nbytes() > BTREE_PAGE_SIZE * 40 / 100);
+        }
+
+        for i in 10..40 {
+            let key = format!("key{}",i);
+            let v = txwriter.Get(key.as_bytes());
+            if i >= 36 || i % 7 == 0 {
+                assert_eq!(Some(vec![i as u8;500]),v);
+            } else {
+                assert_eq!(true,v.is_none());
+            }
+        }
+    }
+
+    #[test]
+    fn test_savepoint()
+    {
+        let tables = Arc::new(Catalog::new());
+
+        let mut data: Vec<u8> = vec![0; BTREE_PAGE_SIZE*2];
+        let mut tx = prepaircase_nonefreelist_noneNode(&mut data);
+        let mut txwriter = txwriter::new(tx,tables.clone());
+
+        let sql = r#"
+        create table person
+        ( 
+            id vchar,
+            name vchar,
+            primary key (id),
+            index (name),
+        );
+        insert into person ( id, name ) values ('1','Bob1');
+        savepoint sp1;
+        insert into person ( id, name ) values ('2','Bob2');
+        savepoint sp2;
+        insert into person ( id, name ) values ('3','Bob3');
+        rollback to sp1;
+        insert into person ( id, name ) values ('4','Bob4');
+        release sp1;
+        "#;
+        txwriter.ExecuteSQLStatments(sql.to_string()).unwrap();
+        assert_eq!(true,txwriter.rollbackTo("sp1").is_err());
+        assert_eq!(true,txwriter.rollbackTo("sp2").is_err());
+
+        let root = txwriter.context.get_root();
+        txwriter.savepoint("sp3");
+        txwriter.ExecuteSQLStatments("delete from person index by id = '1';".to_string()).unwrap();
+        txwriter.rollbackTo("sp3").unwrap();
+        assert_eq!(root,txwriter.context.get_root());
+
+        let tdef = txwriter.getTableDef("person".as_bytes()).unwrap();
+        for (id,found) in [("1",true),("2",false),("3",false),("4",true)] {
+            let mut r = Record::new(&tdef);
+            r.Set("id".as_bytes(), Value::BYTES(id.as_bytes().to_vec()));
+            assert_eq!(found,txwriter.dbGet(&mut r).unwrap());
+        }
+    }
+
     fn prepaircase_nonefreelist_noneNode(data:&mut Vec<u8>)->Tx
     {
         //master
@@ -23,6 +23,10 @@ pub fn compare_arrays(a: &[u8], b: &[u8]) ->i32 {
 pub fn deescapeString(content: &[u8]) -> Vec<u8> {
     let mut list:Vec<u8> = Vec::new();
     //println!("Before dedescapString: {:?}", content);
+    if content.len() == 0
+    {
+        return list;
+    }
     let mut idx: usize = 0;
     if content[idx] == 0xfe
     {
@@ -89,4 +93,17 @@ pub fn escapeString(content: &[u8], list:&mut Vec<u8>) {
         }
         idx += 1;
     }
-}
\ No newline at end of file
+}
+
+pub fn toHex(content: &[u8]) -> String {
+    content.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+// two digits a byte, either case
+pub fn fromHex(s: &str) -> Option<Vec<u8>> {
+    if s.len() % 2 != 0 || s.bytes().all(|c| c.is_ascii_hexdigit()) == false
+    {
+        return None;
+    }
+    (0..s.len()).step_by(2).map(|i| u8::from_str_radix(&s[i..i+2], 16).ok()).collect()
+}